@@ -0,0 +1,4 @@
+-- Add migration script here
+ALTER TABLE subscriptions_tokens
+    ADD COLUMN created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    ADD COLUMN consumed_at TIMESTAMPTZ NULL;
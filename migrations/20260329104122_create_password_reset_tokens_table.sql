@@ -0,0 +1,9 @@
+-- Add migration script here
+CREATE TABLE password_reset_tokens
+(
+    token_hash  TEXT        NOT NULL,
+    user_id     uuid        NOT NULL REFERENCES users (user_id) ON DELETE CASCADE,
+    created_at  TIMESTAMPTZ NOT NULL,
+    consumed_at TIMESTAMPTZ NULL,
+    PRIMARY KEY (token_hash)
+);
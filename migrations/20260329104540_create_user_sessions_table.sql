@@ -0,0 +1,8 @@
+-- Add migration script here
+CREATE TABLE user_sessions
+(
+    session_id uuid        NOT NULL,
+    user_id    uuid        NOT NULL REFERENCES users (user_id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (session_id)
+);
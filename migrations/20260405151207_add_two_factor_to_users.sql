@@ -0,0 +1,4 @@
+-- Add migration script here
+ALTER TABLE users
+    ADD COLUMN totp_secret  TEXT    NULL,
+    ADD COLUMN totp_enabled BOOLEAN NOT NULL DEFAULT false;
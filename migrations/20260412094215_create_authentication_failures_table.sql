@@ -0,0 +1,9 @@
+-- Add migration script here
+CREATE TABLE authentication_failures
+(
+    throttling_key  TEXT        NOT NULL,
+    failure_count   INTEGER     NOT NULL,
+    last_failure_at TIMESTAMPTZ NOT NULL,
+    locked_until    TIMESTAMPTZ NULL,
+    PRIMARY KEY (throttling_key)
+);
@@ -0,0 +1,13 @@
+-- Add migration script here
+CREATE TABLE api_tokens
+(
+    api_token_id uuid        NOT NULL,
+    user_id      uuid        NOT NULL REFERENCES users (user_id) ON DELETE CASCADE,
+    name         TEXT        NOT NULL,
+    token_hash   TEXT        NOT NULL UNIQUE,
+    scopes       TEXT[]      NOT NULL,
+    created_at   TIMESTAMPTZ NOT NULL,
+    expires_at   TIMESTAMPTZ NULL,
+    last_used_at TIMESTAMPTZ NULL,
+    PRIMARY KEY (api_token_id)
+);
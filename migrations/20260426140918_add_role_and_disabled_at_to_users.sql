@@ -0,0 +1,12 @@
+BEGIN;
+    ALTER TABLE users
+        ADD COLUMN role        TEXT        NULL,
+        ADD COLUMN disabled_at TIMESTAMPTZ NULL;
+
+    -- Accounts created before roles existed had full access
+    UPDATE users SET role = 'owner';
+
+    ALTER TABLE users
+        ALTER COLUMN role SET NOT NULL,
+        ADD CONSTRAINT users_role_check CHECK (role IN ('owner', 'editor', 'viewer'));
+COMMIT;
@@ -0,0 +1,10 @@
+BEGIN;
+    ALTER TABLE user_sessions
+        ADD COLUMN last_seen_at TIMESTAMPTZ NULL,
+        ADD COLUMN ip_address   TEXT        NULL,
+        ADD COLUMN user_agent   TEXT        NULL;
+
+    UPDATE user_sessions SET last_seen_at = created_at;
+
+    ALTER TABLE user_sessions ALTER COLUMN last_seen_at SET NOT NULL;
+COMMIT;
@@ -0,0 +1,15 @@
+-- Add migration script here
+CREATE TABLE audit_events
+(
+    audit_event_id uuid        NOT NULL,
+    occurred_at    TIMESTAMPTZ NOT NULL,
+    actor_user_id  uuid        NULL REFERENCES users (user_id) ON DELETE SET NULL,
+    actor          TEXT        NOT NULL,
+    action         TEXT        NOT NULL,
+    target         TEXT        NULL,
+    ip_address     TEXT        NULL,
+    outcome        TEXT        NOT NULL CHECK (outcome IN ('success', 'failure')),
+    PRIMARY KEY (audit_event_id)
+);
+
+CREATE INDEX audit_events_occurred_at_idx ON audit_events (occurred_at DESC);
@@ -0,0 +1,17 @@
+BEGIN;
+    ALTER TABLE newsletter_issues
+        ADD COLUMN status     TEXT        NULL,
+        ADD COLUMN created_at TIMESTAMPTZ NULL,
+        ALTER COLUMN published_at DROP NOT NULL;
+
+    -- Issues stored so far were published straight away.
+    UPDATE newsletter_issues SET status = 'sending', created_at = published_at;
+
+    ALTER TABLE newsletter_issues
+        ALTER COLUMN status SET NOT NULL,
+        ALTER COLUMN created_at SET NOT NULL,
+        ADD CONSTRAINT newsletter_issues_status_check
+            CHECK (status IN ('draft', 'sending')),
+        ADD CONSTRAINT newsletter_issues_published_at_check
+            CHECK (status = 'draft' OR published_at IS NOT NULL);
+COMMIT;
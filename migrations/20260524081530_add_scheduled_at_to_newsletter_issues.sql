@@ -0,0 +1,19 @@
+BEGIN;
+    ALTER TABLE newsletter_issues
+        ADD COLUMN scheduled_at TIMESTAMPTZ NULL,
+        DROP CONSTRAINT newsletter_issues_status_check,
+        DROP CONSTRAINT newsletter_issues_published_at_check;
+
+    ALTER TABLE newsletter_issues
+        ADD CONSTRAINT newsletter_issues_status_check
+            CHECK (status IN ('draft', 'scheduled', 'sending')),
+        ADD CONSTRAINT newsletter_issues_published_at_check
+            CHECK (status IN ('draft', 'scheduled') OR published_at IS NOT NULL),
+        ADD CONSTRAINT newsletter_issues_scheduled_at_check
+            CHECK (status <> 'scheduled' OR scheduled_at IS NOT NULL);
+
+    -- The scheduler looks for due issues on every tick.
+    CREATE INDEX newsletter_issues_scheduled_at_idx
+        ON newsletter_issues (scheduled_at)
+        WHERE status = 'scheduled';
+COMMIT;
@@ -0,0 +1,18 @@
+-- Add migration script here
+CREATE TABLE deliveries
+(
+    newsletter_issue_id uuid        NOT NULL REFERENCES newsletter_issues (newsletter_issue_id),
+    subscriber_email    TEXT        NOT NULL,
+    status              TEXT        NOT NULL
+        CHECK (status IN ('pending', 'sent', 'failed', 'skipped')),
+    n_attempts          INT         NOT NULL DEFAULT 0,
+    last_error          TEXT        NULL,
+    provider_message_id TEXT        NULL,
+    updated_at          TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (newsletter_issue_id, subscriber_email)
+);
+
+-- Tasks still queued when this runs are tracked from now on.
+INSERT INTO deliveries (newsletter_issue_id, subscriber_email, status, updated_at)
+SELECT newsletter_issue_id, subscriber_email, 'pending', now()
+FROM issue_delivery_queue;
@@ -0,0 +1,4 @@
+-- Add migration script here
+ALTER TABLE newsletter_issues
+    ADD COLUMN scheduled_by_user_id uuid NULL REFERENCES users (user_id) ON DELETE SET NULL,
+    ADD COLUMN scheduled_by         TEXT NULL;
@@ -0,0 +1,8 @@
+-- Add migration script here
+-- Invitations and password resets share these tokens but not their lifetime, which is therefore
+-- fixed when a token is issued.
+BEGIN;
+    ALTER TABLE password_reset_tokens ADD COLUMN expires_at TIMESTAMPTZ NULL;
+    UPDATE password_reset_tokens SET expires_at = created_at + interval '30 minutes';
+    ALTER TABLE password_reset_tokens ALTER COLUMN expires_at SET NOT NULL;
+COMMIT;
@@ -1,53 +1,99 @@
-use crate::configuration::{DatabaseSettings, Settings};
+use crate::authentication::{
+    TotpSecretCipher, reject_anonymous_users, reject_non_owners, reject_viewers,
+};
+use crate::configuration::{ApplicationSettings, DatabaseSettings, Settings};
+use crate::csrf::reject_forged_requests;
 use crate::email_client::EmailClient;
+use crate::issue_delivery_worker::run_worker_until_stopped;
+use crate::issue_scheduler::run_scheduler_until_stopped;
+use crate::routes::admin::api_tokens::get::api_tokens_page;
+use crate::routes::admin::api_tokens::post::{create_new_api_token, revoke_existing_api_token};
+use crate::routes::admin::audit_log::audit_log_page;
 use crate::routes::admin::dashboard::admin_dashboard;
+use crate::routes::admin::email::get::change_email_form;
+use crate::routes::admin::email::post::change_email;
+use crate::routes::admin::logout::log_out;
+use crate::routes::admin::newsletters::get::{
+    new_newsletter_issue_form, newsletter_issue_page, newsletter_issue_preview,
+    newsletter_issue_report, newsletter_issues_page,
+};
+use crate::routes::admin::newsletters::post::{
+    cancel_newsletter_issue_schedule, create_newsletter_draft, publish_newsletter_draft,
+    save_newsletter_draft, schedule_newsletter_issue, send_newsletter_test,
+};
 use crate::routes::admin::password::get::change_password_form;
 use crate::routes::admin::password::post::change_password;
-use crate::routes::confirm_subscription::confirm_subscription;
+use crate::routes::admin::sessions::get::sessions_page;
+use crate::routes::admin::sessions::post::{revoke_all_other_sessions, revoke_user_session};
+use crate::routes::admin::two_factor::get::two_factor_form;
+use crate::routes::admin::two_factor::post::{
+    confirm_two_factor_setup, start_two_factor_setup, turn_off_two_factor,
+};
+use crate::routes::admin::users::get::users_page;
+use crate::routes::admin::users::post::{
+    change_role, delete_account, disable_account, enable_account, invite_user,
+};
+use crate::routes::confirm_subscription::{confirm_subscription, resend_confirmation};
 use crate::routes::health_check::health_check_controller;
 use crate::routes::home::home;
 use crate::routes::login::get::login_form;
 use crate::routes::login::post::login;
-use crate::routes::newsletters::publish_newsletter;
+use crate::routes::login::two_factor::get::two_factor_login_form;
+use crate::routes::login::two_factor::post::two_factor_login;
+use crate::routes::newsletters::{
+    cancel_newsletter_schedule, get_delivery_report, publish_newsletter, reschedule_newsletter,
+};
+use crate::routes::password_reset::get::{forgot_password_form, reset_password_form};
+use crate::routes::password_reset::post::{request_password_reset, reset_password};
 use crate::routes::subscriptions::subscribe_controller;
-use actix_session::storage::RedisSessionStore;
+use crate::routes::unsubscribe::{unsubscribe, unsubscribe_form};
 use actix_session::SessionMiddleware;
-use actix_web::cookie::Key;
+use actix_session::storage::RedisSessionStore;
+use actix_web::cookie::{Key, SameSite};
 use actix_web::dev::Server;
-use actix_web::{web, App, HttpServer};
-use actix_web_flash_messages::storage::CookieMessageStore;
+use actix_web::middleware::from_fn;
+use actix_web::{App, HttpServer, web};
 use actix_web_flash_messages::FlashMessagesFramework;
+use actix_web_flash_messages::storage::CookieMessageStore;
 use secrecy::{ExposeSecret, SecretString};
-use sqlx::postgres::PgPoolOptions;
 use sqlx::PgPool;
+use sqlx::postgres::PgPoolOptions;
+use std::fmt::{Debug, Display};
 use std::net::{SocketAddr, TcpListener};
+use std::sync::Arc;
+use tokio::task::JoinError;
 use tracing_actix_web::TracingLogger;
 
+// The JSON extractor's default limit, so that the composer accepts the issues POST /newsletters
+// does.
+const NEWSLETTER_FORM_LIMIT: usize = 2 * 1024 * 1024;
+
 pub struct ApplicationBaseUrl(pub String);
 
+pub struct SubscriptionTokenTtl(pub chrono::Duration);
+
+pub struct PasswordResetTokenTtl(pub chrono::Duration);
+
+pub struct InvitationTokenTtl(pub chrono::Duration);
+
+pub struct AudienceTimezone(pub chrono_tz::Tz);
+
+pub struct TrustedProxies(pub Vec<ipnet::IpNet>);
+
 pub struct Application {
     socket_addr: SocketAddr,
     address: String,
     server: Server,
+    db_connection_pool: PgPool,
+    email_client: Arc<EmailClient>,
+    base_url: String,
 }
 
 impl Application {
     pub async fn build(configuration: Settings) -> Result<Self, anyhow::Error> {
         let connection_pool = Application::get_connection_pool(&configuration.database);
 
-        let sender_email = configuration
-            .email_client
-            .sender()
-            .expect("Invalid sender email address");
-
-        let timeout = configuration.email_client.timeout();
-
-        let email_client = EmailClient::new(
-            configuration.email_client.base_url,
-            sender_email,
-            configuration.email_client.authorization_token,
-            timeout,
-        );
+        let email_client = Arc::new(configuration.email_client.client()?);
 
         let address = format!(
             "{}:{}",
@@ -59,10 +105,9 @@ impl Application {
 
         let server = Application::start_server(
             tcp_listener,
-            connection_pool,
-            email_client,
-            configuration.application.base_url,
-            configuration.application.hmac_secret,
+            connection_pool.clone(),
+            email_client.clone(),
+            configuration.application.clone(),
             configuration.redis_uri,
         )
         .await?;
@@ -71,6 +116,9 @@ impl Application {
             socket_addr: address_assigned,
             address: address_assigned.to_string(),
             server,
+            db_connection_pool: connection_pool,
+            email_client,
+            base_url: configuration.application.base_url,
         })
     }
 
@@ -81,14 +129,35 @@ impl Application {
     async fn start_server(
         tcp_listener: TcpListener,
         db_connection_pool: PgPool,
-        email_client: EmailClient,
-        base_url: String,
-        hmac_secret: SecretString,
+        email_client: Arc<EmailClient>,
+        application_settings: ApplicationSettings,
         redis_uri: SecretString,
     ) -> Result<Server, anyhow::Error> {
         let db_connection_pool_data = web::Data::new(db_connection_pool);
-        let email_client_data = web::Data::new(email_client);
-        let application_base_url = web::Data::new(ApplicationBaseUrl(base_url));
+        let email_client_data = web::Data::from(email_client);
+        let subscription_token_ttl = web::Data::new(SubscriptionTokenTtl(
+            application_settings.subscription_token_ttl(),
+        ));
+        let password_reset_token_ttl = web::Data::new(PasswordResetTokenTtl(
+            application_settings.password_reset_token_ttl(),
+        ));
+        let invitation_token_ttl = web::Data::new(InvitationTokenTtl(
+            application_settings.invitation_token_ttl(),
+        ));
+        let audience_timezone =
+            web::Data::new(AudienceTimezone(application_settings.audience_timezone()?));
+        let application_base_url =
+            web::Data::new(ApplicationBaseUrl(application_settings.base_url));
+        let trusted_proxies = web::Data::new(TrustedProxies(application_settings.trusted_proxies));
+        let totp_secret_cipher = web::Data::new(TotpSecretCipher::new(
+            &application_settings.totp_encryption_key,
+        )?);
+        let login_throttle =
+            web::Data::new(application_settings.brute_force_protection.login_throttle());
+        let password_hashing =
+            web::Data::new(application_settings.password_hashing.password_hashing()?);
+        let security_headers = application_settings.security_headers;
+        let hmac_secret = application_settings.hmac_secret;
 
         let message_store =
             CookieMessageStore::builder(Key::from(hmac_secret.expose_secret().as_bytes())).build();
@@ -99,28 +168,161 @@ impl Application {
 
         let http_server = HttpServer::new(move || {
             App::new()
+                .wrap(from_fn(reject_forged_requests))
+                .wrap(security_headers.default_headers())
                 .wrap(message_framework.clone())
-                .wrap(SessionMiddleware::new(
-                    redis_store.clone(),
-                    secret_key.clone(),
-                ))
+                .wrap(
+                    SessionMiddleware::builder(redis_store.clone(), secret_key.clone())
+                        .cookie_same_site(SameSite::Lax)
+                        .build(),
+                )
                 .wrap(TracingLogger::default())
                 .route("/", web::get().to(home))
                 .route("/health_check", web::get().to(health_check_controller))
                 .route("/login", web::get().to(login_form))
                 .route("/login", web::post().to(login))
+                .route("/login/two-factor", web::get().to(two_factor_login_form))
+                .route("/login/two-factor", web::post().to(two_factor_login))
+                .route("/password-reset", web::get().to(forgot_password_form))
+                .route("/password-reset", web::post().to(request_password_reset))
+                .route(
+                    "/password-reset/confirm",
+                    web::get().to(reset_password_form),
+                )
+                .route("/password-reset/confirm", web::post().to(reset_password))
                 .route("/subscriptions", web::post().to(subscribe_controller))
                 .route(
                     "/subscriptions/confirm",
                     web::get().to(confirm_subscription),
                 )
+                .route(
+                    "/subscriptions/confirm/resend",
+                    web::post().to(resend_confirmation),
+                )
+                .route(
+                    "/subscriptions/unsubscribe",
+                    web::get().to(unsubscribe_form),
+                )
+                .route("/subscriptions/unsubscribe", web::post().to(unsubscribe))
                 .route("/newsletters", web::post().to(publish_newsletter))
-                .route("/admin/dashboard", web::get().to(admin_dashboard))
-                .route("/admin/password", web::get().to(change_password_form))
-                .route("/admin/password", web::post().to(change_password))
+                .route(
+                    "/newsletters/{newsletter_issue_id}/schedule",
+                    web::put().to(reschedule_newsletter),
+                )
+                .route(
+                    "/newsletters/{newsletter_issue_id}/deliveries",
+                    web::get().to(get_delivery_report),
+                )
+                .route(
+                    "/newsletters/{newsletter_issue_id}/schedule",
+                    web::delete().to(cancel_newsletter_schedule),
+                )
+                .service(
+                    web::scope("/admin")
+                        .wrap(from_fn(reject_anonymous_users))
+                        .route("/dashboard", web::get().to(admin_dashboard))
+                        .route("/password", web::get().to(change_password_form))
+                        .route("/password", web::post().to(change_password))
+                        .route("/email", web::get().to(change_email_form))
+                        .route("/email", web::post().to(change_email))
+                        .route("/logout", web::post().to(log_out))
+                        .route("/two-factor", web::get().to(two_factor_form))
+                        .route("/two-factor/setup", web::post().to(start_two_factor_setup))
+                        .route(
+                            "/two-factor/confirm",
+                            web::post().to(confirm_two_factor_setup),
+                        )
+                        .route("/two-factor/disable", web::post().to(turn_off_two_factor))
+                        .route("/sessions", web::get().to(sessions_page))
+                        .route(
+                            "/sessions/revoke-others",
+                            web::post().to(revoke_all_other_sessions),
+                        )
+                        .route(
+                            "/sessions/{session_id}/revoke",
+                            web::post().to(revoke_user_session),
+                        )
+                        .service(
+                            web::scope("/api-tokens")
+                                .wrap(from_fn(reject_viewers))
+                                .route("", web::get().to(api_tokens_page))
+                                .route("", web::post().to(create_new_api_token))
+                                .route(
+                                    "/{api_token_id}/revoke",
+                                    web::post().to(revoke_existing_api_token),
+                                ),
+                        )
+                        // Viewers may follow how an issue is being delivered, but nothing else about
+                        // newsletters.
+                        .route(
+                            "/newsletters/{newsletter_issue_id}/report",
+                            web::get().to(newsletter_issue_report),
+                        )
+                        .service(
+                            web::scope("/newsletters")
+                                .wrap(from_fn(reject_viewers))
+                                .app_data(web::FormConfig::default().limit(NEWSLETTER_FORM_LIMIT))
+                                .route("", web::get().to(newsletter_issues_page))
+                                .route("", web::post().to(create_newsletter_draft))
+                                .route("/new", web::get().to(new_newsletter_issue_form))
+                                .route(
+                                    "/{newsletter_issue_id}",
+                                    web::get().to(newsletter_issue_page),
+                                )
+                                .route(
+                                    "/{newsletter_issue_id}",
+                                    web::post().to(save_newsletter_draft),
+                                )
+                                .route(
+                                    "/{newsletter_issue_id}/preview",
+                                    web::get().to(newsletter_issue_preview),
+                                )
+                                .route(
+                                    "/{newsletter_issue_id}/test",
+                                    web::post().to(send_newsletter_test),
+                                )
+                                .route(
+                                    "/{newsletter_issue_id}/schedule",
+                                    web::post().to(schedule_newsletter_issue),
+                                )
+                                .route(
+                                    "/{newsletter_issue_id}/cancel-schedule",
+                                    web::post().to(cancel_newsletter_issue_schedule),
+                                )
+                                .route(
+                                    "/{newsletter_issue_id}/publish",
+                                    web::post().to(publish_newsletter_draft),
+                                ),
+                        )
+                        .service(
+                            web::scope("/audit-log")
+                                .wrap(from_fn(reject_non_owners))
+                                .route("", web::get().to(audit_log_page)),
+                        )
+                        .service(
+                            web::scope("/users")
+                                .wrap(from_fn(reject_non_owners))
+                                .route("", web::get().to(users_page))
+                                .route("", web::post().to(invite_user))
+                                .route("/{user_id}/role", web::post().to(change_role))
+                                .route("/{user_id}/disable", web::post().to(disable_account))
+                                .route("/{user_id}/enable", web::post().to(enable_account))
+                                .route("/{user_id}/delete", web::post().to(delete_account)),
+                        ),
+                )
+                // The CSRF check buffers form bodies before they reach the scopes above.
+                .app_data(web::PayloadConfig::new(NEWSLETTER_FORM_LIMIT))
                 .app_data(db_connection_pool_data.clone())
                 .app_data(email_client_data.clone())
                 .app_data(application_base_url.clone())
+                .app_data(subscription_token_ttl.clone())
+                .app_data(password_reset_token_ttl.clone())
+                .app_data(invitation_token_ttl.clone())
+                .app_data(audience_timezone.clone())
+                .app_data(trusted_proxies.clone())
+                .app_data(totp_secret_cipher.clone())
+                .app_data(login_throttle.clone())
+                .app_data(password_hashing.clone())
         })
         .listen(tcp_listener)?
         .run();
@@ -128,8 +330,23 @@ impl Application {
         Ok(http_server)
     }
 
-    pub async fn run_until_stopped(self) -> Result<(), std::io::Error> {
-        self.server.await
+    pub async fn run_until_stopped(self) -> Result<(), anyhow::Error> {
+        let server_task = tokio::spawn(self.server);
+        let scheduler_task =
+            tokio::spawn(run_scheduler_until_stopped(self.db_connection_pool.clone()));
+        let worker_task = tokio::spawn(run_worker_until_stopped(
+            self.db_connection_pool,
+            self.email_client,
+            self.base_url,
+        ));
+
+        tokio::select! {
+            outcome = server_task => report_exit("API", outcome),
+            outcome = worker_task => report_exit("Background worker", outcome),
+            outcome = scheduler_task => report_exit("Scheduler", outcome),
+        };
+
+        Ok(())
     }
 
     pub fn address(&self) -> &str {
@@ -140,3 +357,27 @@ impl Application {
         &self.socket_addr
     }
 }
+
+fn report_exit(task_name: &str, outcome: Result<Result<(), impl Debug + Display>, JoinError>) {
+    match outcome {
+        Ok(Ok(())) => {
+            tracing::info!("{} has exited", task_name)
+        }
+        Ok(Err(error)) => {
+            tracing::error!(
+                error.cause_chain = ?error,
+                error.message = %error,
+                "{} failed",
+                task_name
+            )
+        }
+        Err(error) => {
+            tracing::error!(
+                error.cause_chain = ?error,
+                error.message = %error,
+                "{} task failed to complete",
+                task_name
+            )
+        }
+    }
+}
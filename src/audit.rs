@@ -0,0 +1,206 @@
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{PgExecutor, PgPool};
+use uuid::Uuid;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AuditAction {
+    Login,
+    ChangePassword,
+    ChangeEmail,
+    PublishNewsletter,
+    ScheduleNewsletter,
+    RescheduleNewsletter,
+    CancelNewsletterSchedule,
+}
+
+impl AuditAction {
+    pub const ALL: [AuditAction; 7] = [
+        AuditAction::Login,
+        AuditAction::ChangePassword,
+        AuditAction::ChangeEmail,
+        AuditAction::PublishNewsletter,
+        AuditAction::ScheduleNewsletter,
+        AuditAction::RescheduleNewsletter,
+        AuditAction::CancelNewsletterSchedule,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::Login => "login",
+            AuditAction::ChangePassword => "change_password",
+            AuditAction::ChangeEmail => "change_email",
+            AuditAction::PublishNewsletter => "publish_newsletter",
+            AuditAction::ScheduleNewsletter => "schedule_newsletter",
+            AuditAction::RescheduleNewsletter => "reschedule_newsletter",
+            AuditAction::CancelNewsletterSchedule => "cancel_newsletter_schedule",
+        }
+    }
+}
+
+impl TryFrom<&str> for AuditAction {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        AuditAction::ALL
+            .into_iter()
+            .find(|action| action.as_str() == value)
+            .ok_or_else(|| format!("{} is not a supported audit action", value))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AuditOutcome {
+    Success,
+    Failure,
+}
+
+impl AuditOutcome {
+    pub const ALL: [AuditOutcome; 2] = [AuditOutcome::Success, AuditOutcome::Failure];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditOutcome::Success => "success",
+            AuditOutcome::Failure => "failure",
+        }
+    }
+}
+
+impl TryFrom<&str> for AuditOutcome {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        AuditOutcome::ALL
+            .into_iter()
+            .find(|outcome| outcome.as_str() == value)
+            .ok_or_else(|| format!("{} is not a supported audit outcome", value))
+    }
+}
+
+// The actor is stored by name as well, so that events remain readable once the user is deleted
+// and failed logins can name the username that was attempted.
+#[derive(Debug)]
+pub struct AuditEvent<'a> {
+    pub actor_user_id: Option<Uuid>,
+    pub actor: &'a str,
+    pub action: AuditAction,
+    pub target: Option<&'a str>,
+    pub ip_address: Option<&'a str>,
+    pub outcome: AuditOutcome,
+}
+
+// Failed logins are recorded under whatever username was submitted, so its length is capped.
+const MAX_ACTOR_LENGTH: usize = 64;
+
+fn truncate_actor(actor: &str) -> &str {
+    match actor.char_indices().nth(MAX_ACTOR_LENGTH) {
+        Some((end, _)) => &actor[..end],
+        None => actor,
+    }
+}
+
+#[tracing::instrument(name = "record_audit_event", skip(executor))]
+pub async fn record_audit_event(
+    executor: impl PgExecutor<'_>,
+    event: &AuditEvent<'_>,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO audit_events (
+            audit_event_id, occurred_at, actor_user_id, actor, action, target, ip_address, outcome
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        "#,
+        Uuid::new_v4(),
+        Utc::now(),
+        event.actor_user_id,
+        truncate_actor(event.actor),
+        event.action.as_str(),
+        event.target,
+        event.ip_address,
+        event.outcome.as_str()
+    )
+    .execute(executor)
+    .await
+    .context("Failed to record the audit event")?;
+
+    Ok(())
+}
+
+#[derive(Debug, Default)]
+pub struct AuditEventFilter {
+    pub action: Option<AuditAction>,
+    pub outcome: Option<AuditOutcome>,
+    pub actor: Option<String>,
+}
+
+pub struct StoredAuditEvent {
+    pub occurred_at: DateTime<Utc>,
+    pub actor: String,
+    pub action: String,
+    pub target: Option<String>,
+    pub ip_address: Option<String>,
+    pub outcome: String,
+}
+
+#[tracing::instrument(name = "list_audit_events", skip(db_connection_pool))]
+pub async fn list_audit_events(
+    db_connection_pool: &PgPool,
+    filter: &AuditEventFilter,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<StoredAuditEvent>, anyhow::Error> {
+    let events = sqlx::query_as!(
+        StoredAuditEvent,
+        r#"
+        SELECT occurred_at, actor, action, target, ip_address, outcome
+        FROM audit_events
+        WHERE ($1::TEXT IS NULL OR action = $1)
+            AND ($2::TEXT IS NULL OR outcome = $2)
+            AND ($3::TEXT IS NULL OR actor = $3)
+        ORDER BY occurred_at DESC, audit_event_id
+        LIMIT $4
+        OFFSET $5
+        "#,
+        filter.action.map(|action| action.as_str()),
+        filter.outcome.map(|outcome| outcome.as_str()),
+        filter.actor.as_deref(),
+        limit,
+        offset
+    )
+    .fetch_all(db_connection_pool)
+    .await
+    .context("Failed to list the audit events")?;
+
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audit::{AuditAction, AuditOutcome, MAX_ACTOR_LENGTH, truncate_actor};
+    use claims::assert_err;
+
+    #[test]
+    fn then_actions_and_outcomes_should_round_trip_through_their_names() {
+        for action in AuditAction::ALL {
+            assert_eq!(AuditAction::try_from(action.as_str()), Ok(action));
+        }
+
+        for outcome in AuditOutcome::ALL {
+            assert_eq!(AuditOutcome::try_from(outcome.as_str()), Ok(outcome));
+        }
+    }
+
+    #[test]
+    fn given_an_unknown_action_then_it_should_be_rejected() {
+        assert_err!(AuditAction::try_from("drop_tables"));
+    }
+
+    #[test]
+    fn given_a_long_actor_then_it_should_be_truncated_on_a_character_boundary() {
+        let actor = "é".repeat(MAX_ACTOR_LENGTH + 10);
+
+        assert_eq!(truncate_actor(&actor).chars().count(), MAX_ACTOR_LENGTH);
+        assert_eq!(truncate_actor("ursula"), "ursula");
+    }
+}
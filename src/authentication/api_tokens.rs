@@ -0,0 +1,189 @@
+use crate::authentication::AuthError;
+use crate::utils::hash_token;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use rand::distr::{Alphanumeric, SampleString};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+const API_TOKEN_PREFIX: &str = "z2p_";
+const API_TOKEN_LENGTH: usize = 40;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ApiTokenScope {
+    PublishNewsletters,
+    ReadSubscribers,
+    ReadDeliveries,
+}
+
+impl ApiTokenScope {
+    pub const ALL: [ApiTokenScope; 3] = [
+        ApiTokenScope::PublishNewsletters,
+        ApiTokenScope::ReadSubscribers,
+        ApiTokenScope::ReadDeliveries,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiTokenScope::PublishNewsletters => "newsletters:publish",
+            ApiTokenScope::ReadSubscribers => "subscribers:read",
+            ApiTokenScope::ReadDeliveries => "deliveries:read",
+        }
+    }
+}
+
+impl TryFrom<&str> for ApiTokenScope {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        ApiTokenScope::ALL
+            .into_iter()
+            .find(|scope| scope.as_str() == value)
+            .ok_or_else(|| format!("{} is not a supported API token scope", value))
+    }
+}
+
+pub struct ApiToken {
+    pub api_token_id: Uuid,
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+pub struct ApiTokenGrant {
+    pub user_id: Uuid,
+    scopes: Vec<String>,
+}
+
+impl ApiTokenGrant {
+    pub fn has_scope(&self, scope: ApiTokenScope) -> bool {
+        self.scopes.iter().any(|granted| granted == scope.as_str())
+    }
+}
+
+// The plain token is only returned here, to be shown once. Only its hash is stored.
+#[tracing::instrument(name = "create_api_token", skip(db_connection_pool))]
+pub async fn create_api_token(
+    db_connection_pool: &PgPool,
+    user_id: Uuid,
+    name: &str,
+    scopes: &[ApiTokenScope],
+    expires_at: Option<DateTime<Utc>>,
+) -> Result<String, anyhow::Error> {
+    let token = format!(
+        "{}{}",
+        API_TOKEN_PREFIX,
+        Alphanumeric.sample_string(&mut rand::rng(), API_TOKEN_LENGTH)
+    );
+    let scopes: Vec<String> = scopes.iter().map(|scope| scope.as_str().into()).collect();
+
+    sqlx::query!(
+        r#"
+        INSERT INTO api_tokens (api_token_id, user_id, name, token_hash, scopes, created_at, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        "#,
+        Uuid::new_v4(),
+        user_id,
+        name,
+        hash_token(&token),
+        &scopes,
+        Utc::now(),
+        expires_at
+    )
+    .execute(db_connection_pool)
+    .await
+    .context("Failed to store the API token")?;
+
+    Ok(token)
+}
+
+#[tracing::instrument(name = "list_api_tokens", skip(db_connection_pool))]
+pub async fn list_api_tokens(
+    db_connection_pool: &PgPool,
+    user_id: Uuid,
+) -> Result<Vec<ApiToken>, anyhow::Error> {
+    let api_tokens = sqlx::query_as!(
+        ApiToken,
+        r#"
+        SELECT api_token_id, name, scopes, created_at, expires_at, last_used_at
+        FROM api_tokens
+        WHERE user_id = $1
+        ORDER BY created_at DESC
+        "#,
+        user_id
+    )
+    .fetch_all(db_connection_pool)
+    .await
+    .context("Failed to retrieve the API tokens")?;
+
+    Ok(api_tokens)
+}
+
+#[tracing::instrument(name = "revoke_api_token", skip(db_connection_pool))]
+pub async fn revoke_api_token(
+    db_connection_pool: &PgPool,
+    user_id: Uuid,
+    api_token_id: Uuid,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        "DELETE FROM api_tokens WHERE api_token_id = $1 AND user_id = $2",
+        api_token_id,
+        user_id
+    )
+    .execute(db_connection_pool)
+    .await
+    .context("Failed to revoke the API token")?;
+
+    Ok(())
+}
+
+#[tracing::instrument(name = "validate_api_token", skip(token, db_connection_pool))]
+pub async fn validate_api_token(
+    token: &str,
+    db_connection_pool: &PgPool,
+) -> Result<ApiTokenGrant, AuthError> {
+    let row = sqlx::query!(
+        r#"
+        UPDATE api_tokens
+        SET last_used_at = now()
+        FROM users
+        WHERE api_tokens.token_hash = $1
+            AND (api_tokens.expires_at IS NULL OR api_tokens.expires_at > now())
+            AND users.user_id = api_tokens.user_id
+            AND users.disabled_at IS NULL
+        RETURNING api_tokens.user_id, api_tokens.scopes
+        "#,
+        hash_token(token)
+    )
+    .fetch_optional(db_connection_pool)
+    .await
+    .context("Failed to look up the API token")?
+    .ok_or_else(|| {
+        AuthError::InvalidCredentials(anyhow::anyhow!("Unknown or expired API token"))
+    })?;
+
+    Ok(ApiTokenGrant {
+        user_id: row.user_id,
+        scopes: row.scopes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::authentication::ApiTokenScope;
+    use claims::assert_err;
+
+    #[test]
+    fn then_every_scope_should_parse_back_from_its_name() {
+        for scope in ApiTokenScope::ALL {
+            assert_eq!(ApiTokenScope::try_from(scope.as_str()), Ok(scope));
+        }
+    }
+
+    #[test]
+    fn given_an_unknown_scope_then_it_should_be_rejected() {
+        assert_err!(ApiTokenScope::try_from("newsletters:delete"));
+    }
+}
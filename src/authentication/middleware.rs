@@ -0,0 +1,107 @@
+use crate::authentication::Role;
+use crate::authentication::sessions::{get_active_session_role, touch_session};
+use crate::session_state::TypedSession;
+use crate::utils::see_other;
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::error::{ErrorForbidden, ErrorInternalServerError, InternalError};
+use actix_web::middleware::Next;
+use actix_web::{FromRequest, HttpMessage, web};
+use sqlx::PgPool;
+use std::fmt::Display;
+use std::ops::Deref;
+use uuid::Uuid;
+
+#[derive(Copy, Clone, Debug)]
+pub struct UserId(Uuid);
+
+impl Display for UserId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl Deref for UserId {
+    type Target = Uuid;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+pub async fn reject_anonymous_users(
+    mut request: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let session = {
+        let (http_request, payload) = request.parts_mut();
+        TypedSession::from_request(http_request, payload).await
+    }?;
+
+    let user_id = session.get_user_id().map_err(ErrorInternalServerError)?;
+    let session_id = session.get_session_id().map_err(ErrorInternalServerError)?;
+
+    let (Some(user_id), Some(session_id)) = (user_id, session_id) else {
+        let error = anyhow::anyhow!("The user has not logged in");
+        return Err(InternalError::from_response(error, see_other("/login")).into());
+    };
+
+    let db_connection_pool = request
+        .app_data::<web::Data<PgPool>>()
+        .ok_or_else(|| ErrorInternalServerError("The database connection pool is not available"))?;
+
+    let Some(role) = get_active_session_role(db_connection_pool, session_id, user_id)
+        .await
+        .map_err(ErrorInternalServerError)?
+    else {
+        session.log_out();
+        let error = anyhow::anyhow!("The session has been revoked");
+        return Err(InternalError::from_response(error, see_other("/login")).into());
+    };
+
+    // Failing to record activity must not lock the user out.
+    if let Err(error) = touch_session(db_connection_pool, session_id).await {
+        tracing::warn!(error.cause_chain = ?error, "Failed to record the session's activity");
+    }
+
+    request.extensions_mut().insert(UserId(user_id));
+    request.extensions_mut().insert(role);
+    next.call(request).await
+}
+
+// These must be wrapped inside `reject_anonymous_users`, which provides the user's role.
+pub async fn reject_non_owners(
+    request: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    reject_roles_below(Role::Owner, &request)?;
+    next.call(request).await
+}
+
+pub async fn reject_viewers(
+    request: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    reject_roles_below(Role::Editor, &request)?;
+    next.call(request).await
+}
+
+fn reject_roles_below(
+    required_role: Role,
+    request: &ServiceRequest,
+) -> Result<(), actix_web::Error> {
+    let role = request
+        .extensions()
+        .get::<Role>()
+        .copied()
+        .ok_or_else(|| ErrorInternalServerError("The user's role is not available"))?;
+
+    if role < required_role {
+        return Err(ErrorForbidden(format!(
+            "This action requires the {} role",
+            required_role.as_str()
+        )));
+    }
+
+    Ok(())
+}
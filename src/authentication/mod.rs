@@ -0,0 +1,40 @@
+mod api_tokens;
+mod middleware;
+mod password;
+mod password_reset;
+mod roles;
+mod sessions;
+mod throttling;
+mod two_factor;
+mod users;
+
+pub use api_tokens::{
+    ApiToken, ApiTokenGrant, ApiTokenScope, create_api_token, list_api_tokens, revoke_api_token,
+    validate_api_token,
+};
+pub use middleware::{UserId, reject_anonymous_users, reject_non_owners, reject_viewers};
+pub use password::{
+    AuthError, Credentials, PasswordHashing, change_password, validate_credentials,
+    validate_new_password,
+};
+pub use password_reset::{
+    consume_password_reset_token, find_password_reset_token_owner, issue_password_reset_token,
+};
+pub use roles::{Role, get_active_user_role};
+pub use sessions::{
+    ActiveSession, SessionMetadata, list_active_sessions, register_session, revoke_all_sessions,
+    revoke_other_sessions, revoke_session,
+};
+pub use throttling::{
+    LoginThrottle, RateLimit, enforce_rate_limit, validate_credentials_with_throttling,
+    verify_second_factor_with_throttling,
+};
+pub use two_factor::{
+    TotpSecretCipher, TwoFactorStatus, disable_two_factor, enable_two_factor,
+    get_two_factor_status, is_two_factor_enabled, start_two_factor_enrollment,
+    verify_second_factor,
+};
+pub use users::{
+    User, change_user_email, change_user_role, create_user, delete_user, disable_user, enable_user,
+    get_user_email_address, list_users,
+};
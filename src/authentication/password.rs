@@ -0,0 +1,325 @@
+use anyhow::Context;
+use argon2::password_hash::SaltString;
+use argon2::password_hash::rand_core::OsRng;
+use argon2::{Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version};
+use secrecy::{ExposeSecret, SecretString};
+use sqlx::{PgExecutor, PgPool};
+
+const MIN_PASSWORD_LENGTH: usize = 12;
+const MAX_PASSWORD_LENGTH: usize = 128;
+
+#[derive(thiserror::Error, Debug)]
+pub enum AuthError {
+    #[error("Invalid credentials")]
+    InvalidCredentials(#[source] anyhow::Error),
+    #[error("Too many failed authentication attempts")]
+    TooManyAttempts { retry_after: std::time::Duration },
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+pub struct Credentials {
+    pub username: String,
+    pub password: SecretString,
+}
+
+#[derive(Clone)]
+pub struct PasswordHashing {
+    params: Params,
+    // Unknown usernames are checked against this hash, so that they take as long to reject as
+    // wrong passwords do.
+    dummy_password_hash: String,
+}
+
+impl PasswordHashing {
+    pub fn new(params: Params) -> Result<Self, anyhow::Error> {
+        let mut password_hashing = Self {
+            params,
+            dummy_password_hash: String::new(),
+        };
+
+        password_hashing.dummy_password_hash = password_hashing
+            .compute_password_hash(SecretString::from(uuid::Uuid::new_v4().to_string()))?
+            .expose_secret()
+            .to_owned();
+
+        Ok(password_hashing)
+    }
+
+    pub fn compute_password_hash(
+        &self,
+        password: SecretString,
+    ) -> Result<SecretString, anyhow::Error> {
+        let salt = SaltString::generate(&mut OsRng);
+
+        let password_hash = Argon2::new(Algorithm::Argon2id, Version::V0x13, self.params.clone())
+            .hash_password(password.expose_secret().as_bytes(), &salt)
+            .context("Failed to hash password")?
+            .to_string();
+
+        Ok(SecretString::from(password_hash))
+    }
+
+    fn needs_rehash(&self, password_hash: &PasswordHash) -> bool {
+        let Ok(stored_params) = Params::try_from(password_hash) else {
+            return true;
+        };
+
+        password_hash.algorithm != Algorithm::Argon2id.ident()
+            || password_hash.version != Some(Version::V0x13.into())
+            || stored_params.m_cost() != self.params.m_cost()
+            || stored_params.t_cost() != self.params.t_cost()
+            || stored_params.p_cost() != self.params.p_cost()
+    }
+}
+
+#[tracing::instrument(
+    name = "validate_credentials",
+    skip(credentials, password_hashing, db_connection_pool)
+)]
+pub async fn validate_credentials(
+    credentials: Credentials,
+    password_hashing: &PasswordHashing,
+    db_connection_pool: &PgPool,
+) -> Result<uuid::Uuid, AuthError> {
+    let mut user_id = None;
+    let mut password_hash = SecretString::from(password_hashing.dummy_password_hash.clone());
+
+    if let Some((user_id_stored, password_hash_stored)) =
+        get_stored_credentials(credentials.username.as_str(), db_connection_pool).await?
+    {
+        user_id = Some(user_id_stored);
+        password_hash = password_hash_stored;
+    }
+
+    let current_span = tracing::Span::current();
+    let password_hashing = password_hashing.clone();
+
+    let (password_hash, upgraded_password_hash) = tokio::task::spawn_blocking(move || {
+        current_span.in_scope(|| {
+            verify_password_hash(&password_hash, &credentials.password)?;
+
+            let upgraded_password_hash =
+                upgrade_password_hash(&password_hashing, &password_hash, credentials.password)?;
+
+            Ok::<_, AuthError>((password_hash, upgraded_password_hash))
+        })
+    })
+    .await
+    .context("Failed to spawn blocking task")??;
+
+    let user_id = user_id
+        .ok_or_else(|| anyhow::anyhow!("Unknown username"))
+        .map_err(AuthError::InvalidCredentials)?;
+
+    // A failed upgrade must not prevent the login: it will be attempted again on the next one.
+    if let Some(upgraded_password_hash) = upgraded_password_hash
+        && let Err(error) = store_upgraded_password_hash(
+            db_connection_pool,
+            user_id,
+            &password_hash,
+            &upgraded_password_hash,
+        )
+        .await
+    {
+        tracing::warn!(
+            error.cause_chain = ?error,
+            error.message = %error,
+            "Failed to store the upgraded password hash",
+        );
+    }
+
+    Ok(user_id)
+}
+
+#[tracing::instrument(name = "get_stored_credentials", skip(db_connection_pool))]
+async fn get_stored_credentials(
+    username: &str,
+    db_connection_pool: &PgPool,
+) -> Result<Option<(uuid::Uuid, SecretString)>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+            SELECT user_id, password_hash
+            FROM users
+            WHERE username = $1 AND disabled_at IS NULL
+        "#,
+        username
+    )
+    .fetch_optional(db_connection_pool)
+    .await
+    .context("Failed to perform the query to retrieve stored credentials.")?;
+
+    let fields = row.map(|row| (row.user_id, SecretString::from(row.password_hash)));
+
+    Ok(fields)
+}
+
+#[tracing::instrument(
+    name = "verify_password_hash",
+    skip(password_hash_stored, password_to_verify)
+)]
+fn verify_password_hash(
+    password_hash_stored: &SecretString,
+    password_to_verify: &SecretString,
+) -> Result<(), AuthError> {
+    let stored_password_hash_as_phc = PasswordHash::new(password_hash_stored.expose_secret())
+        .context("Failed to parse hash in PHC string format.")
+        .map_err(AuthError::UnexpectedError)?;
+
+    Argon2::default()
+        .verify_password(
+            password_to_verify.expose_secret().as_bytes(),
+            &stored_password_hash_as_phc,
+        )
+        .context("Invalid password")
+        .map_err(AuthError::InvalidCredentials)
+}
+
+// The plain password is only available right after it has been verified, so that is when a hash
+// computed with outdated parameters or another algorithm gets replaced.
+#[tracing::instrument(
+    name = "upgrade_password_hash",
+    skip(password_hashing, password_hash_stored, password)
+)]
+fn upgrade_password_hash(
+    password_hashing: &PasswordHashing,
+    password_hash_stored: &SecretString,
+    password: SecretString,
+) -> Result<Option<SecretString>, anyhow::Error> {
+    let stored_password_hash_as_phc = PasswordHash::new(password_hash_stored.expose_secret())
+        .context("Failed to parse hash in PHC string format.")?;
+
+    if !password_hashing.needs_rehash(&stored_password_hash_as_phc) {
+        return Ok(None);
+    }
+
+    password_hashing.compute_password_hash(password).map(Some)
+}
+
+#[tracing::instrument(
+    name = "store_upgraded_password_hash",
+    skip(db_connection_pool, previous_password_hash, upgraded_password_hash)
+)]
+async fn store_upgraded_password_hash(
+    db_connection_pool: &PgPool,
+    user_id: uuid::Uuid,
+    previous_password_hash: &SecretString,
+    upgraded_password_hash: &SecretString,
+) -> Result<(), anyhow::Error> {
+    // The previous hash is matched so that a concurrent password change is never overwritten.
+    sqlx::query!(
+        r#"
+            UPDATE users
+            SET password_hash = $1
+            WHERE user_id = $2 AND password_hash = $3
+        "#,
+        upgraded_password_hash.expose_secret(),
+        user_id,
+        previous_password_hash.expose_secret()
+    )
+    .execute(db_connection_pool)
+    .await
+    .context("Failed to store the upgraded password hash in the database.")?;
+
+    Ok(())
+}
+
+pub fn validate_new_password(
+    new_password: &SecretString,
+    new_password_check: &SecretString,
+) -> Result<(), String> {
+    if new_password.expose_secret() != new_password_check.expose_secret() {
+        return Err(
+            "You entered two different new passwords - the field values must match.".to_string(),
+        );
+    }
+
+    let new_password_length = new_password.expose_secret().chars().count();
+
+    if !(MIN_PASSWORD_LENGTH..=MAX_PASSWORD_LENGTH).contains(&new_password_length) {
+        return Err(format!(
+            "The new password must be between {} and {} characters long.",
+            MIN_PASSWORD_LENGTH, MAX_PASSWORD_LENGTH
+        ));
+    }
+
+    Ok(())
+}
+
+#[tracing::instrument(name = "change_password", skip(password, password_hashing, executor))]
+pub async fn change_password(
+    user_id: uuid::Uuid,
+    password: SecretString,
+    password_hashing: &PasswordHashing,
+    executor: impl PgExecutor<'_>,
+) -> Result<(), anyhow::Error> {
+    let current_span = tracing::Span::current();
+    let password_hashing = password_hashing.clone();
+
+    let password_hash = tokio::task::spawn_blocking(move || {
+        current_span.in_scope(|| password_hashing.compute_password_hash(password))
+    })
+    .await
+    .context("Failed to spawn blocking task")??;
+
+    sqlx::query!(
+        r#"
+            UPDATE users
+            SET password_hash = $1
+            WHERE user_id = $2
+        "#,
+        password_hash.expose_secret(),
+        user_id
+    )
+    .execute(executor)
+    .await
+    .context("Failed to change user's password in the database.")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::authentication::PasswordHashing;
+    use argon2::password_hash::SaltString;
+    use argon2::password_hash::rand_core::OsRng;
+    use argon2::{Algorithm, Argon2, Params, PasswordHash, PasswordHasher, Version};
+
+    fn password_hashing() -> PasswordHashing {
+        PasswordHashing::new(Params::new(19_456, 2, 1, None).unwrap()).unwrap()
+    }
+
+    fn hash_with(algorithm: Algorithm, params: Params) -> String {
+        Argon2::new(algorithm, Version::V0x13, params)
+            .hash_password(b"password", &SaltString::generate(&mut OsRng))
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn given_a_hash_with_the_target_params_then_it_should_not_need_a_rehash() {
+        let password_hash = hash_with(
+            Algorithm::Argon2id,
+            Params::new(19_456, 2, 1, None).unwrap(),
+        );
+
+        assert!(!password_hashing().needs_rehash(&PasswordHash::new(&password_hash).unwrap()));
+    }
+
+    #[test]
+    fn given_a_hash_with_older_params_then_it_should_need_a_rehash() {
+        let password_hash = hash_with(
+            Algorithm::Argon2id,
+            Params::new(15_000, 2, 1, None).unwrap(),
+        );
+
+        assert!(password_hashing().needs_rehash(&PasswordHash::new(&password_hash).unwrap()));
+    }
+
+    #[test]
+    fn given_a_hash_with_another_algorithm_then_it_should_need_a_rehash() {
+        let password_hash = hash_with(Algorithm::Argon2i, Params::new(19_456, 2, 1, None).unwrap());
+
+        assert!(password_hashing().needs_rehash(&PasswordHash::new(&password_hash).unwrap()));
+    }
+}
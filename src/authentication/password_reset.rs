@@ -0,0 +1,101 @@
+use crate::utils::hash_token;
+use anyhow::Context;
+use chrono::{Duration, Utc};
+use rand::distr::{Alphanumeric, SampleString};
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+#[tracing::instrument(name = "issue_password_reset_token", skip(db_connection_pool))]
+pub async fn issue_password_reset_token(
+    db_connection_pool: &PgPool,
+    user_id: Uuid,
+    ttl: Duration,
+) -> Result<String, anyhow::Error> {
+    let token = Alphanumeric.sample_string(&mut rand::rng(), 32);
+    let now = Utc::now();
+
+    let mut transaction = db_connection_pool
+        .begin()
+        .await
+        .context("Failed to get a database connection from the pool")?;
+
+    // Requesting a new link invalidates any link that was sent before.
+    let query = sqlx::query!(
+        "DELETE FROM password_reset_tokens WHERE user_id = $1",
+        user_id
+    );
+
+    transaction
+        .execute(query)
+        .await
+        .context("Failed to discard the previous password reset tokens")?;
+
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO password_reset_tokens (token_hash, user_id, created_at, expires_at)
+        VALUES ($1, $2, $3, $4)
+        "#,
+        hash_token(&token),
+        user_id,
+        now,
+        now + ttl
+    );
+
+    transaction
+        .execute(query)
+        .await
+        .context("Failed to store the password reset token")?;
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the transaction to store a password reset token")?;
+
+    Ok(token)
+}
+
+#[tracing::instrument(
+    name = "find_password_reset_token_owner",
+    skip(db_connection_pool, token)
+)]
+pub async fn find_password_reset_token_owner(
+    db_connection_pool: &PgPool,
+    token: &str,
+) -> Result<Option<Uuid>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT user_id
+        FROM password_reset_tokens
+        WHERE token_hash = $1 AND consumed_at IS NULL AND expires_at > now()
+        "#,
+        hash_token(token)
+    )
+    .fetch_optional(db_connection_pool)
+    .await
+    .context("Failed to look up the password reset token")?;
+
+    Ok(row.map(|row| row.user_id))
+}
+
+// Marking the token as consumed and reading its owner in one statement guarantees that a token
+// can only ever be redeemed once, even under concurrent requests.
+#[tracing::instrument(name = "consume_password_reset_token", skip(transaction, token))]
+pub async fn consume_password_reset_token(
+    transaction: &mut Transaction<'_, Postgres>,
+    token: &str,
+) -> Result<Option<Uuid>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        UPDATE password_reset_tokens
+        SET consumed_at = now()
+        WHERE token_hash = $1 AND consumed_at IS NULL AND expires_at > now()
+        RETURNING user_id
+        "#,
+        hash_token(token)
+    )
+    .fetch_optional(&mut **transaction)
+    .await
+    .context("Failed to consume the password reset token")?;
+
+    Ok(row.map(|row| row.user_id))
+}
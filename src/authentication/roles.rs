@@ -0,0 +1,75 @@
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+// Variants are declared from the least to the most privileged, so that roles can be compared.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    Viewer,
+    Editor,
+    Owner,
+}
+
+impl Role {
+    pub const ALL: [Role; 3] = [Role::Owner, Role::Editor, Role::Viewer];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Editor => "editor",
+            Role::Owner => "owner",
+        }
+    }
+}
+
+impl TryFrom<&str> for Role {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Role::ALL
+            .into_iter()
+            .find(|role| role.as_str() == value)
+            .ok_or_else(|| format!("{} is not a supported role", value))
+    }
+}
+
+#[tracing::instrument(name = "get_active_user_role", skip(db_connection_pool))]
+pub async fn get_active_user_role(
+    db_connection_pool: &PgPool,
+    user_id: Uuid,
+) -> Result<Option<Role>, anyhow::Error> {
+    let row = sqlx::query!(
+        "SELECT role FROM users WHERE user_id = $1 AND disabled_at IS NULL",
+        user_id
+    )
+    .fetch_optional(db_connection_pool)
+    .await
+    .context("Failed to retrieve the user's role")?;
+
+    row.map(|row| Role::try_from(row.role.as_str()).map_err(|error| anyhow::anyhow!(error)))
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::authentication::Role;
+    use claims::assert_err;
+
+    #[test]
+    fn then_roles_should_be_ordered_by_privilege() {
+        assert!(Role::Viewer < Role::Editor);
+        assert!(Role::Editor < Role::Owner);
+    }
+
+    #[test]
+    fn then_every_role_should_parse_back_from_its_name() {
+        for role in Role::ALL {
+            assert_eq!(Role::try_from(role.as_str()), Ok(role));
+        }
+    }
+
+    #[test]
+    fn given_an_unknown_role_then_it_should_be_rejected() {
+        assert_err!(Role::try_from("administrator"));
+    }
+}
@@ -0,0 +1,194 @@
+use crate::authentication::Role;
+use crate::utils::client_ip;
+use actix_web::HttpRequest;
+use actix_web::http::header::USER_AGENT;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{PgExecutor, PgPool};
+use uuid::Uuid;
+
+// The request's origin is kept so that users can recognise their sessions. The user agent is
+// client-controlled, hence the length cap.
+const MAX_USER_AGENT_LENGTH: usize = 256;
+
+#[derive(Debug, Default)]
+pub struct SessionMetadata {
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+impl SessionMetadata {
+    pub fn from_request(request: &HttpRequest) -> Self {
+        let ip_address = client_ip(request);
+        let user_agent = request
+            .headers()
+            .get(USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.chars().take(MAX_USER_AGENT_LENGTH).collect());
+
+        Self {
+            ip_address,
+            user_agent,
+        }
+    }
+}
+
+pub struct ActiveSession {
+    pub session_id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+// Session state lives in Redis, which cannot be queried by user. Every login is therefore
+// registered here as well, and a session is only honoured while its registration exists.
+#[tracing::instrument(name = "register_session", skip(db_connection_pool))]
+pub async fn register_session(
+    db_connection_pool: &PgPool,
+    user_id: Uuid,
+    metadata: &SessionMetadata,
+) -> Result<Uuid, anyhow::Error> {
+    let session_id = Uuid::new_v4();
+    let now = Utc::now();
+
+    sqlx::query!(
+        r#"
+        INSERT INTO user_sessions
+            (session_id, user_id, created_at, last_seen_at, ip_address, user_agent)
+        VALUES ($1, $2, $3, $3, $4, $5)
+        "#,
+        session_id,
+        user_id,
+        now,
+        metadata.ip_address,
+        metadata.user_agent
+    )
+    .execute(db_connection_pool)
+    .await
+    .context("Failed to register the session")?;
+
+    Ok(session_id)
+}
+
+#[tracing::instrument(name = "list_active_sessions", skip(db_connection_pool))]
+pub async fn list_active_sessions(
+    db_connection_pool: &PgPool,
+    user_id: Uuid,
+) -> Result<Vec<ActiveSession>, anyhow::Error> {
+    let sessions = sqlx::query_as!(
+        ActiveSession,
+        r#"
+        SELECT session_id, created_at, last_seen_at, ip_address, user_agent
+        FROM user_sessions
+        WHERE user_id = $1
+        ORDER BY last_seen_at DESC
+        "#,
+        user_id
+    )
+    .fetch_all(db_connection_pool)
+    .await
+    .context("Failed to list the user's sessions")?;
+
+    Ok(sessions)
+}
+
+// Writing on every request would turn each page view into a database write, so the timestamp is
+// only refreshed once it is stale.
+#[tracing::instrument(name = "touch_session", skip(db_connection_pool))]
+pub async fn touch_session(
+    db_connection_pool: &PgPool,
+    session_id: Uuid,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE user_sessions
+        SET last_seen_at = now()
+        WHERE session_id = $1 AND last_seen_at < now() - INTERVAL '1 minute'
+        "#,
+        session_id
+    )
+    .execute(db_connection_pool)
+    .await
+    .context("Failed to update the session's last activity")?;
+
+    Ok(())
+}
+
+// A session is only honoured while it is registered and its user has not been disabled. The
+// user's current role is returned along, so that a role change applies to existing sessions.
+#[tracing::instrument(name = "get_active_session_role", skip(db_connection_pool))]
+pub async fn get_active_session_role(
+    db_connection_pool: &PgPool,
+    session_id: Uuid,
+    user_id: Uuid,
+) -> Result<Option<Role>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT users.role
+        FROM user_sessions
+        JOIN users ON users.user_id = user_sessions.user_id
+        WHERE user_sessions.session_id = $1
+            AND user_sessions.user_id = $2
+            AND users.disabled_at IS NULL
+        "#,
+        session_id,
+        user_id
+    )
+    .fetch_optional(db_connection_pool)
+    .await
+    .context("Failed to look up the session")?;
+
+    row.map(|row| Role::try_from(row.role.as_str()).map_err(|error| anyhow::anyhow!(error)))
+        .transpose()
+}
+
+// Scoped by user, so that a session id cannot be used to revoke someone else's session.
+#[tracing::instrument(name = "revoke_session", skip(executor))]
+pub async fn revoke_session(
+    executor: impl PgExecutor<'_>,
+    user_id: Uuid,
+    session_id: Uuid,
+) -> Result<bool, anyhow::Error> {
+    let result = sqlx::query!(
+        "DELETE FROM user_sessions WHERE session_id = $1 AND user_id = $2",
+        session_id,
+        user_id
+    )
+    .execute(executor)
+    .await
+    .context("Failed to revoke the session")?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+#[tracing::instrument(name = "revoke_other_sessions", skip(executor))]
+pub async fn revoke_other_sessions(
+    executor: impl PgExecutor<'_>,
+    user_id: Uuid,
+    current_session_id: Uuid,
+) -> Result<u64, anyhow::Error> {
+    let result = sqlx::query!(
+        "DELETE FROM user_sessions WHERE user_id = $1 AND session_id <> $2",
+        user_id,
+        current_session_id
+    )
+    .execute(executor)
+    .await
+    .context("Failed to revoke the user's other sessions")?;
+
+    Ok(result.rows_affected())
+}
+
+#[tracing::instrument(name = "revoke_all_sessions", skip(executor))]
+pub async fn revoke_all_sessions(
+    executor: impl PgExecutor<'_>,
+    user_id: Uuid,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!("DELETE FROM user_sessions WHERE user_id = $1", user_id)
+        .execute(executor)
+        .await
+        .context("Failed to revoke the user's sessions")?;
+
+    Ok(())
+}
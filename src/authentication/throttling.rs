@@ -0,0 +1,321 @@
+use crate::authentication::{
+    AuthError, Credentials, PasswordHashing, validate_credentials, verify_second_factor,
+};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::time::Duration;
+use totp_rs::TOTP;
+use uuid::Uuid;
+
+pub struct LoginThrottle {
+    pub max_failures_per_username: i32,
+    pub max_failures_per_ip: i32,
+    pub lockout: chrono::Duration,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+// Caps how often an action can be performed, successful or not, over a rolling window.
+pub struct RateLimit {
+    pub max_attempts: i32,
+    pub window: chrono::Duration,
+}
+
+impl LoginThrottle {
+    fn delay_for_failures(&self, failures: i32) -> Duration {
+        if failures <= 0 {
+            return Duration::ZERO;
+        }
+
+        self.base_delay
+            .saturating_mul(2u32.saturating_pow(failures as u32 - 1))
+            .min(self.max_delay)
+    }
+}
+
+// Failures are counted both per username and per client IP: the former stops an attacker from
+// guessing one account's password from many addresses, the latter from spraying many accounts
+// from a single address.
+#[tracing::instrument(
+    name = "validate_credentials_with_throttling",
+    skip(credentials, throttle, password_hashing, db_connection_pool)
+)]
+pub async fn validate_credentials_with_throttling(
+    credentials: Credentials,
+    client_ip: Option<String>,
+    throttle: &LoginThrottle,
+    password_hashing: &PasswordHashing,
+    db_connection_pool: &PgPool,
+) -> Result<Uuid, AuthError> {
+    let username_key = format!("username:{}", credentials.username);
+    let throttling_keys = throttling_keys(
+        throttle,
+        (username_key.clone(), throttle.max_failures_per_username),
+        client_ip,
+    );
+
+    wait_for_throttling(db_connection_pool, throttle, &throttling_keys).await?;
+
+    match validate_credentials(credentials, password_hashing, db_connection_pool).await {
+        Ok(user_id) => {
+            reset_failures(db_connection_pool, &username_key).await?;
+
+            Ok(user_id)
+        }
+        Err(AuthError::InvalidCredentials(error)) => {
+            record_failures(db_connection_pool, throttle, &throttling_keys).await?;
+
+            Err(AuthError::InvalidCredentials(error))
+        }
+        Err(error) => Err(error),
+    }
+}
+
+// A correct password only gets an attacker as far as the second factor, whose six digits would
+// otherwise be quick to guess.
+#[tracing::instrument(
+    name = "verify_second_factor_with_throttling",
+    skip(totp, code, throttle, db_connection_pool)
+)]
+pub async fn verify_second_factor_with_throttling(
+    totp: &TOTP,
+    user_id: Uuid,
+    code: &str,
+    client_ip: Option<String>,
+    throttle: &LoginThrottle,
+    db_connection_pool: &PgPool,
+) -> Result<(), AuthError> {
+    let user_key = format!("two-factor:{}", user_id);
+    let throttling_keys = throttling_keys(
+        throttle,
+        (user_key.clone(), throttle.max_failures_per_username),
+        client_ip,
+    );
+
+    wait_for_throttling(db_connection_pool, throttle, &throttling_keys).await?;
+
+    if verify_second_factor(db_connection_pool, totp, user_id, code).await? {
+        reset_failures(db_connection_pool, &user_key).await?;
+
+        Ok(())
+    } else {
+        record_failures(db_connection_pool, throttle, &throttling_keys).await?;
+
+        Err(AuthError::InvalidCredentials(anyhow::anyhow!(
+            "Invalid authentication code"
+        )))
+    }
+}
+
+// Attempts are counted like authentication failures: once the limit is reached, the key is locked
+// out for a whole window.
+#[tracing::instrument(name = "enforce_rate_limit", skip(rate_limit, db_connection_pool))]
+pub async fn enforce_rate_limit(
+    throttling_key: &str,
+    rate_limit: &RateLimit,
+    db_connection_pool: &PgPool,
+) -> Result<(), AuthError> {
+    let (_, locked_until) = get_throttling_state(
+        db_connection_pool,
+        &[throttling_key.to_string()],
+        Utc::now() - rate_limit.window,
+    )
+    .await?;
+
+    if let Some(locked_until) = locked_until {
+        let retry_after = (locked_until - Utc::now()).to_std().unwrap_or_default();
+
+        return Err(AuthError::TooManyAttempts { retry_after });
+    }
+
+    record_failure(
+        db_connection_pool,
+        rate_limit.window,
+        throttling_key,
+        rate_limit.max_attempts,
+    )
+    .await?;
+
+    Ok(())
+}
+
+fn throttling_keys(
+    throttle: &LoginThrottle,
+    account_key: (String, i32),
+    client_ip: Option<String>,
+) -> Vec<(String, i32)> {
+    let mut throttling_keys = vec![account_key];
+
+    if let Some(client_ip) = client_ip {
+        throttling_keys.push((format!("ip:{}", client_ip), throttle.max_failures_per_ip));
+    }
+
+    throttling_keys
+}
+
+async fn wait_for_throttling(
+    db_connection_pool: &PgPool,
+    throttle: &LoginThrottle,
+    throttling_keys: &[(String, i32)],
+) -> Result<(), AuthError> {
+    let keys: Vec<String> = throttling_keys.iter().map(|(key, _)| key.clone()).collect();
+    let (recent_failures, locked_until) =
+        get_throttling_state(db_connection_pool, &keys, Utc::now() - throttle.lockout).await?;
+
+    if let Some(locked_until) = locked_until {
+        let retry_after = (locked_until - Utc::now()).to_std().unwrap_or_default();
+
+        return Err(AuthError::TooManyAttempts { retry_after });
+    }
+
+    tokio::time::sleep(throttle.delay_for_failures(recent_failures)).await;
+
+    Ok(())
+}
+
+async fn record_failures(
+    db_connection_pool: &PgPool,
+    throttle: &LoginThrottle,
+    throttling_keys: &[(String, i32)],
+) -> Result<(), anyhow::Error> {
+    for (throttling_key, max_failures) in throttling_keys {
+        record_failure(
+            db_connection_pool,
+            throttle.lockout,
+            throttling_key,
+            *max_failures,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+#[tracing::instrument(name = "get_throttling_state", skip(db_connection_pool))]
+async fn get_throttling_state(
+    db_connection_pool: &PgPool,
+    throttling_keys: &[String],
+    window_start: DateTime<Utc>,
+) -> Result<(i32, Option<DateTime<Utc>>), anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT
+            MAX(failure_count) FILTER (WHERE last_failure_at > $2) AS recent_failures,
+            MAX(locked_until) FILTER (WHERE locked_until > now()) AS locked_until
+        FROM authentication_failures
+        WHERE throttling_key = ANY($1)
+        "#,
+        throttling_keys,
+        window_start
+    )
+    .fetch_one(db_connection_pool)
+    .await
+    .context("Failed to retrieve the authentication failures")?;
+
+    Ok((row.recent_failures.unwrap_or_default(), row.locked_until))
+}
+
+#[tracing::instrument(name = "record_failure", skip(db_connection_pool))]
+async fn record_failure(
+    db_connection_pool: &PgPool,
+    lockout: chrono::Duration,
+    throttling_key: &str,
+    max_failures: i32,
+) -> Result<(), anyhow::Error> {
+    let failure_count = sqlx::query!(
+        r#"
+        INSERT INTO authentication_failures (throttling_key, failure_count, last_failure_at)
+        VALUES ($1, 1, now())
+        ON CONFLICT (throttling_key) DO UPDATE
+        SET
+            failure_count = CASE
+                WHEN authentication_failures.last_failure_at < $2 THEN 1
+                ELSE authentication_failures.failure_count + 1
+            END,
+            last_failure_at = now()
+        RETURNING failure_count
+        "#,
+        throttling_key,
+        Utc::now() - lockout
+    )
+    .fetch_one(db_connection_pool)
+    .await
+    .context("Failed to record the authentication failure")?
+    .failure_count;
+
+    if failure_count < max_failures {
+        return Ok(());
+    }
+
+    let locked_until = Utc::now() + lockout;
+
+    sqlx::query!(
+        r#"
+        UPDATE authentication_failures
+        SET failure_count = 0, locked_until = $2
+        WHERE throttling_key = $1
+        "#,
+        throttling_key,
+        locked_until
+    )
+    .execute(db_connection_pool)
+    .await
+    .context("Failed to lock out the throttling key")?;
+
+    tracing::warn!(
+        throttling_key,
+        failure_count,
+        %locked_until,
+        "Locking out after too many failed authentication attempts",
+    );
+
+    Ok(())
+}
+
+#[tracing::instrument(name = "reset_failures", skip(db_connection_pool))]
+async fn reset_failures(
+    db_connection_pool: &PgPool,
+    throttling_key: &str,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        "DELETE FROM authentication_failures WHERE throttling_key = $1",
+        throttling_key
+    )
+    .execute(db_connection_pool)
+    .await
+    .context("Failed to reset the authentication failures")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::authentication::LoginThrottle;
+    use std::time::Duration;
+
+    fn throttle() -> LoginThrottle {
+        LoginThrottle {
+            max_failures_per_username: 5,
+            max_failures_per_ip: 20,
+            lockout: chrono::Duration::minutes(15),
+            base_delay: Duration::from_millis(250),
+            max_delay: Duration::from_secs(2),
+        }
+    }
+
+    #[test]
+    fn given_no_failures_then_it_should_not_delay() {
+        assert_eq!(throttle().delay_for_failures(0), Duration::ZERO);
+    }
+
+    #[test]
+    fn given_repeated_failures_then_the_delay_should_double_up_to_the_maximum() {
+        let throttle = throttle();
+
+        assert_eq!(throttle.delay_for_failures(1), Duration::from_millis(250));
+        assert_eq!(throttle.delay_for_failures(2), Duration::from_millis(500));
+        assert_eq!(throttle.delay_for_failures(3), Duration::from_secs(1));
+        assert_eq!(throttle.delay_for_failures(10), Duration::from_secs(2));
+    }
+}
@@ -0,0 +1,379 @@
+use crate::utils::hash_token;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Nonce};
+use anyhow::Context;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use rand::distr::{Alphanumeric, SampleString};
+use secrecy::{ExposeSecret, SecretString};
+use sqlx::{Executor, PgPool};
+use std::time::{SystemTime, UNIX_EPOCH};
+use totp_rs::{Algorithm, TOTP};
+use uuid::Uuid;
+
+const TOTP_ISSUER: &str = "zero2prod";
+const TOTP_SECRET_LENGTH: usize = 20;
+const NONCE_LENGTH: usize = 12;
+const RECOVERY_CODES_COUNT: usize = 10;
+const RECOVERY_CODE_LENGTH: usize = 10;
+
+pub struct TotpSecretCipher(Aes256Gcm);
+
+impl TotpSecretCipher {
+    pub fn new(key: &SecretString) -> Result<Self, anyhow::Error> {
+        let key = STANDARD
+            .decode(key.expose_secret())
+            .context("The TOTP encryption key is not valid base64")?;
+        let cipher = Aes256Gcm::new_from_slice(&key)
+            .map_err(|_| anyhow::anyhow!("The TOTP encryption key must be 32 bytes long"))?;
+
+        Ok(Self(cipher))
+    }
+
+    fn encrypt(&self, secret: &[u8]) -> Result<String, anyhow::Error> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .0
+            .encrypt(&nonce, secret)
+            .map_err(|_| anyhow::anyhow!("Failed to encrypt the TOTP secret"))?;
+
+        Ok(STANDARD.encode([nonce.as_slice(), &ciphertext].concat()))
+    }
+
+    fn decrypt(&self, encrypted_secret: &str) -> Result<Vec<u8>, anyhow::Error> {
+        let bytes = STANDARD
+            .decode(encrypted_secret)
+            .context("The stored TOTP secret is not valid base64")?;
+
+        if bytes.len() < NONCE_LENGTH {
+            anyhow::bail!("The stored TOTP secret is too short");
+        }
+
+        let (nonce, ciphertext) = bytes.split_at(NONCE_LENGTH);
+
+        self.0
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| anyhow::anyhow!("Failed to decrypt the TOTP secret"))
+    }
+}
+
+pub enum TwoFactorStatus {
+    Disabled,
+    Pending(TOTP),
+    Enabled(TOTP),
+}
+
+#[tracing::instrument(name = "get_two_factor_status", skip(db_connection_pool, cipher))]
+pub async fn get_two_factor_status(
+    db_connection_pool: &PgPool,
+    cipher: &TotpSecretCipher,
+    user_id: Uuid,
+) -> Result<TwoFactorStatus, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT username, totp_secret, totp_enabled
+        FROM users
+        WHERE user_id = $1
+        "#,
+        user_id
+    )
+    .fetch_one(db_connection_pool)
+    .await
+    .context("Failed to retrieve the two-factor settings")?;
+
+    let Some(encrypted_secret) = row.totp_secret else {
+        return Ok(TwoFactorStatus::Disabled);
+    };
+
+    let totp = TOTP::new(
+        Algorithm::SHA1,
+        6,
+        1,
+        30,
+        cipher.decrypt(&encrypted_secret)?,
+        Some(TOTP_ISSUER.to_string()),
+        row.username,
+    )
+    .context("Failed to build the TOTP generator")?;
+
+    if row.totp_enabled {
+        Ok(TwoFactorStatus::Enabled(totp))
+    } else {
+        Ok(TwoFactorStatus::Pending(totp))
+    }
+}
+
+#[tracing::instrument(name = "is_two_factor_enabled", skip(db_connection_pool))]
+pub async fn is_two_factor_enabled(
+    db_connection_pool: &PgPool,
+    user_id: Uuid,
+) -> Result<bool, anyhow::Error> {
+    let row = sqlx::query!("SELECT totp_enabled FROM users WHERE user_id = $1", user_id)
+        .fetch_one(db_connection_pool)
+        .await
+        .context("Failed to retrieve the two-factor settings")?;
+
+    Ok(row.totp_enabled)
+}
+
+#[tracing::instrument(name = "start_two_factor_enrollment", skip(db_connection_pool, cipher))]
+pub async fn start_two_factor_enrollment(
+    db_connection_pool: &PgPool,
+    cipher: &TotpSecretCipher,
+    user_id: Uuid,
+) -> Result<(), anyhow::Error> {
+    let secret: [u8; TOTP_SECRET_LENGTH] = rand::random();
+
+    sqlx::query!(
+        r#"
+        UPDATE users
+        SET totp_secret = $1
+        WHERE user_id = $2 AND totp_enabled = false
+        "#,
+        cipher.encrypt(&secret)?,
+        user_id
+    )
+    .execute(db_connection_pool)
+    .await
+    .context("Failed to store the pending TOTP secret")?;
+
+    Ok(())
+}
+
+// Returns the recovery codes in plain text. They are only stored hashed, so this is the only
+// chance to show them to the user.
+#[tracing::instrument(name = "enable_two_factor", skip(db_connection_pool))]
+pub async fn enable_two_factor(
+    db_connection_pool: &PgPool,
+    user_id: Uuid,
+) -> Result<Vec<String>, anyhow::Error> {
+    let mut transaction = db_connection_pool
+        .begin()
+        .await
+        .context("Failed to get a database connection from the pool")?;
+
+    let query = sqlx::query!(
+        "UPDATE users SET totp_enabled = true WHERE user_id = $1",
+        user_id
+    );
+
+    transaction
+        .execute(query)
+        .await
+        .context("Failed to enable two-factor authentication")?;
+
+    let query = sqlx::query!("DELETE FROM recovery_codes WHERE user_id = $1", user_id);
+
+    transaction
+        .execute(query)
+        .await
+        .context("Failed to discard the previous recovery codes")?;
+
+    let recovery_codes: Vec<String> = (0..RECOVERY_CODES_COUNT)
+        .map(|_| Alphanumeric.sample_string(&mut rand::rng(), RECOVERY_CODE_LENGTH))
+        .map(|code| code.to_lowercase())
+        .collect();
+
+    for recovery_code in &recovery_codes {
+        let query = sqlx::query!(
+            "INSERT INTO recovery_codes (user_id, code_hash) VALUES ($1, $2)",
+            user_id,
+            hash_token(recovery_code)
+        );
+
+        transaction
+            .execute(query)
+            .await
+            .context("Failed to store a recovery code")?;
+    }
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the transaction to enable two-factor authentication")?;
+
+    Ok(recovery_codes)
+}
+
+#[tracing::instrument(name = "disable_two_factor", skip(db_connection_pool))]
+pub async fn disable_two_factor(
+    db_connection_pool: &PgPool,
+    user_id: Uuid,
+) -> Result<(), anyhow::Error> {
+    let mut transaction = db_connection_pool
+        .begin()
+        .await
+        .context("Failed to get a database connection from the pool")?;
+
+    let query = sqlx::query!(
+        r#"
+        UPDATE users
+        SET totp_secret = NULL, totp_enabled = false, totp_last_used_step = NULL
+        WHERE user_id = $1
+        "#,
+        user_id
+    );
+
+    transaction
+        .execute(query)
+        .await
+        .context("Failed to disable two-factor authentication")?;
+
+    let query = sqlx::query!("DELETE FROM recovery_codes WHERE user_id = $1", user_id);
+
+    transaction
+        .execute(query)
+        .await
+        .context("Failed to delete the recovery codes")?;
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the transaction to disable two-factor authentication")?;
+
+    Ok(())
+}
+
+// Accepts either a code from the authenticator app or one of the unused recovery codes, which is
+// consumed in the process.
+#[tracing::instrument(name = "verify_second_factor", skip(db_connection_pool, totp, code))]
+pub async fn verify_second_factor(
+    db_connection_pool: &PgPool,
+    totp: &TOTP,
+    user_id: Uuid,
+    code: &str,
+) -> Result<bool, anyhow::Error> {
+    let code: String = code
+        .chars()
+        .filter(|character| !character.is_whitespace())
+        .collect();
+
+    if accept_totp_code(db_connection_pool, totp, user_id, &code).await? {
+        return Ok(true);
+    }
+
+    let consumed_codes = sqlx::query!(
+        r#"
+        UPDATE recovery_codes
+        SET used_at = now()
+        WHERE user_id = $1 AND code_hash = $2 AND used_at IS NULL
+        "#,
+        user_id,
+        hash_token(&code.to_lowercase())
+    )
+    .execute(db_connection_pool)
+    .await
+    .context("Failed to consume the recovery code")?
+    .rows_affected();
+
+    Ok(consumed_codes > 0)
+}
+
+// A code stays valid for a few time steps, so the last accepted step is stored and only later ones
+// are accepted: an intercepted code cannot be replayed, even concurrently.
+#[tracing::instrument(name = "accept_totp_code", skip(db_connection_pool, totp, code))]
+async fn accept_totp_code(
+    db_connection_pool: &PgPool,
+    totp: &TOTP,
+    user_id: Uuid,
+    code: &str,
+) -> Result<bool, anyhow::Error> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .context("Failed to read the system time")?
+        .as_secs();
+
+    let Some(time_step) = matching_time_step(totp, code, now) else {
+        return Ok(false);
+    };
+
+    let accepted = sqlx::query!(
+        r#"
+        UPDATE users
+        SET totp_last_used_step = $2
+        WHERE user_id = $1 AND (totp_last_used_step IS NULL OR totp_last_used_step < $2)
+        "#,
+        user_id,
+        time_step as i64
+    )
+    .execute(db_connection_pool)
+    .await
+    .context("Failed to record the accepted TOTP time step")?
+    .rows_affected();
+
+    Ok(accepted > 0)
+}
+
+fn matching_time_step(totp: &TOTP, code: &str, time: u64) -> Option<u64> {
+    let current_step = time / totp.step;
+    let exact_totp = TOTP {
+        skew: 0,
+        ..totp.clone()
+    };
+
+    (current_step.saturating_sub(totp.skew as u64)..=current_step + totp.skew as u64)
+        .rev()
+        .find(|time_step| exact_totp.check(code, time_step * totp.step))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::authentication::two_factor::{TotpSecretCipher, matching_time_step};
+    use claims::{assert_err, assert_none, assert_some_eq};
+    use secrecy::SecretString;
+    use totp_rs::{Algorithm, TOTP};
+
+    fn cipher() -> TotpSecretCipher {
+        TotpSecretCipher::new(&SecretString::from(
+            "aOv0LMvLHHiWVJ3d+eZInfxG2sUiCqF13jYOBhHaTgk=",
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn then_it_should_decrypt_what_it_encrypted() {
+        let cipher = cipher();
+
+        let encrypted_secret = cipher.encrypt(b"a totp secret").unwrap();
+
+        assert_ne!(encrypted_secret.as_bytes(), b"a totp secret");
+        assert_eq!(cipher.decrypt(&encrypted_secret).unwrap(), b"a totp secret");
+    }
+
+    #[test]
+    fn given_a_tampered_secret_then_it_should_fail_to_decrypt() {
+        let cipher = cipher();
+        let mut encrypted_secret = cipher.encrypt(b"a totp secret").unwrap();
+        encrypted_secret.replace_range(16..20, "AAAA");
+
+        assert_err!(cipher.decrypt(&encrypted_secret));
+    }
+
+    #[test]
+    fn given_a_key_with_the_wrong_length_then_it_should_be_rejected() {
+        assert!(TotpSecretCipher::new(&SecretString::from("c2hvcnQ=")).is_err());
+        assert!(
+            TotpSecretCipher::new(&SecretString::from(
+                "aOv0LMvLHHiWVJ3d+eZInfxG2sUiCqF13jYOBhHaTgk="
+            ))
+            .is_ok()
+        );
+    }
+
+    #[test]
+    fn then_it_should_find_the_time_step_a_code_was_generated_for() {
+        let totp = TOTP::new_unchecked(Algorithm::SHA1, 6, 1, 30, vec![7; 20], None, String::new());
+        let now = 1_700_000_000;
+        let current_step = now / 30;
+
+        assert_some_eq!(
+            matching_time_step(&totp, &totp.generate(now), now),
+            current_step
+        );
+        assert_some_eq!(
+            matching_time_step(&totp, &totp.generate(now - 30), now),
+            current_step - 1
+        );
+        assert_none!(matching_time_step(&totp, &totp.generate(now - 90), now));
+    }
+}
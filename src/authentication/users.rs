@@ -0,0 +1,199 @@
+use crate::authentication::{PasswordHashing, Role, revoke_all_sessions};
+use anyhow::Context;
+use rand::distr::{Alphanumeric, SampleString};
+use secrecy::{ExposeSecret, SecretString};
+use sqlx::{PgExecutor, PgPool};
+use uuid::Uuid;
+
+pub struct User {
+    pub user_id: Uuid,
+    pub username: String,
+    pub email: Option<String>,
+    pub role: Role,
+    pub disabled: bool,
+}
+
+#[tracing::instrument(name = "list_users", skip(db_connection_pool))]
+pub async fn list_users(db_connection_pool: &PgPool) -> Result<Vec<User>, anyhow::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT user_id, username, email, role, disabled_at IS NOT NULL AS "disabled!"
+        FROM users
+        ORDER BY username
+        "#
+    )
+    .fetch_all(db_connection_pool)
+    .await
+    .context("Failed to retrieve the users")?;
+
+    rows.into_iter()
+        .map(|row| {
+            Ok(User {
+                user_id: row.user_id,
+                username: row.username,
+                email: row.email,
+                role: Role::try_from(row.role.as_str()).map_err(|error| anyhow::anyhow!(error))?,
+                disabled: row.disabled,
+            })
+        })
+        .collect()
+}
+
+// Invited users start with a random password nobody knows, and choose their own through a
+// password reset link. Returns `None` if the username or the email is already taken.
+#[tracing::instrument(name = "create_user", skip(password_hashing, db_connection_pool))]
+pub async fn create_user(
+    db_connection_pool: &PgPool,
+    username: &str,
+    email: &str,
+    role: Role,
+    password_hashing: &PasswordHashing,
+) -> Result<Option<Uuid>, anyhow::Error> {
+    let user_id = Uuid::new_v4();
+    let password = SecretString::from(Alphanumeric.sample_string(&mut rand::rng(), 32));
+    let current_span = tracing::Span::current();
+    let password_hashing = password_hashing.clone();
+
+    let password_hash = tokio::task::spawn_blocking(move || {
+        current_span.in_scope(|| password_hashing.compute_password_hash(password))
+    })
+    .await
+    .context("Failed to spawn blocking task")??;
+
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO users (user_id, username, password_hash, email, role)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        user_id,
+        username,
+        password_hash.expose_secret(),
+        email,
+        role.as_str()
+    )
+    .execute(db_connection_pool)
+    .await;
+
+    match result {
+        Ok(_) => Ok(Some(user_id)),
+        Err(sqlx::Error::Database(error)) if error.is_unique_violation() => Ok(None),
+        Err(error) => Err(anyhow::Error::new(error).context("Failed to create the user")),
+    }
+}
+
+#[tracing::instrument(name = "get_user_email_address", skip(db_connection_pool))]
+pub async fn get_user_email_address(
+    db_connection_pool: &PgPool,
+    user_id: Uuid,
+) -> Result<Option<String>, anyhow::Error> {
+    let row = sqlx::query!("SELECT email FROM users WHERE user_id = $1", user_id)
+        .fetch_one(db_connection_pool)
+        .await
+        .context("Failed to retrieve the user's email address")?;
+
+    Ok(row.email)
+}
+
+// Returns `false` if another user already has this email address.
+#[tracing::instrument(name = "change_user_email", skip(executor))]
+pub async fn change_user_email(
+    executor: impl PgExecutor<'_>,
+    user_id: Uuid,
+    email: &str,
+) -> Result<bool, anyhow::Error> {
+    let result = sqlx::query!(
+        "UPDATE users SET email = $1 WHERE user_id = $2",
+        email,
+        user_id
+    )
+    .execute(executor)
+    .await;
+
+    match result {
+        Ok(_) => Ok(true),
+        Err(sqlx::Error::Database(error)) if error.is_unique_violation() => Ok(false),
+        Err(error) => Err(anyhow::Error::new(error).context("Failed to change the user's email")),
+    }
+}
+
+#[tracing::instrument(name = "change_user_role", skip(db_connection_pool))]
+pub async fn change_user_role(
+    db_connection_pool: &PgPool,
+    user_id: Uuid,
+    role: Role,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        "UPDATE users SET role = $1 WHERE user_id = $2",
+        role.as_str(),
+        user_id
+    )
+    .execute(db_connection_pool)
+    .await
+    .context("Failed to change the user's role")?;
+
+    Ok(())
+}
+
+#[tracing::instrument(name = "disable_user", skip(db_connection_pool))]
+pub async fn disable_user(db_connection_pool: &PgPool, user_id: Uuid) -> Result<(), anyhow::Error> {
+    let mut transaction = db_connection_pool
+        .begin()
+        .await
+        .context("Failed to get a database connection from the pool")?;
+
+    sqlx::query!(
+        "UPDATE users SET disabled_at = now() WHERE user_id = $1 AND disabled_at IS NULL",
+        user_id
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to disable the user")?;
+
+    revoke_all_sessions(&mut *transaction, user_id).await?;
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the transaction to disable a user")?;
+
+    Ok(())
+}
+
+#[tracing::instrument(name = "enable_user", skip(db_connection_pool))]
+pub async fn enable_user(db_connection_pool: &PgPool, user_id: Uuid) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        "UPDATE users SET disabled_at = NULL WHERE user_id = $1",
+        user_id
+    )
+    .execute(db_connection_pool)
+    .await
+    .context("Failed to enable the user")?;
+
+    Ok(())
+}
+
+#[tracing::instrument(name = "delete_user", skip(db_connection_pool))]
+pub async fn delete_user(db_connection_pool: &PgPool, user_id: Uuid) -> Result<(), anyhow::Error> {
+    let mut transaction = db_connection_pool
+        .begin()
+        .await
+        .context("Failed to get a database connection from the pool")?;
+
+    // Saved idempotent responses are the only rows referencing a user without cascading.
+    sqlx::query!("DELETE FROM idempotency WHERE user_id = $1", user_id)
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to delete the user's idempotency keys")?;
+
+    sqlx::query!("DELETE FROM users WHERE user_id = $1", user_id)
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to delete the user")?;
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the transaction to delete a user")?;
+
+    Ok(())
+}
@@ -1,6 +1,15 @@
+use crate::authentication::{LoginThrottle, PasswordHashing};
 use crate::domain::SubscriberEmail;
+use crate::email_client::{
+    EmailClient, OutboxTransport, PostmarkTransport, RetryPolicy, SmtpTransport,
+};
+use actix_web::middleware::DefaultHeaders;
+use anyhow::Context;
+use chrono_tz::Tz;
 use secrecy::{ExposeSecret, SecretString};
-use serde_aux::field_attributes::deserialize_number_from_string;
+use serde_aux::field_attributes::{
+    deserialize_number_from_string, deserialize_option_number_from_string,
+};
 use sqlx::{
     ConnectOptions,
     postgres::{PgConnectOptions, PgSslMode},
@@ -33,14 +42,86 @@ pub struct ApplicationSettings {
     pub host: String,
     pub base_url: String,
     pub hmac_secret: SecretString,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub subscription_token_ttl_hours: i64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub password_reset_token_ttl_minutes: i64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub invitation_token_ttl_days: i64,
+    pub totp_encryption_key: SecretString,
+    // IANA name, used to interpret the times at which issues are scheduled from the admin area.
+    pub audience_timezone: String,
+    // Networks of the reverse proxies in front of the application, whose forwarding headers are
+    // trusted to carry the client's address.
+    #[serde(default)]
+    pub trusted_proxies: Vec<ipnet::IpNet>,
+    pub brute_force_protection: BruteForceProtectionSettings,
+    pub password_hashing: PasswordHashingSettings,
+    pub security_headers: SecurityHeadersSettings,
+}
+
+#[derive(Clone, serde::Deserialize)]
+pub struct BruteForceProtectionSettings {
+    pub max_failures_per_username: i32,
+    pub max_failures_per_ip: i32,
+    pub lockout_seconds: i64,
+    pub base_delay_milliseconds: u64,
+    pub max_delay_milliseconds: u64,
+}
+
+#[derive(Clone, serde::Deserialize)]
+pub struct PasswordHashingSettings {
+    pub memory_cost_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+#[derive(Clone, serde::Deserialize)]
+pub struct SecurityHeadersSettings {
+    pub content_security_policy: String,
+    pub frame_options: String,
+    pub referrer_policy: String,
+    // HSTS is only sent when set, as it must not be enabled where TLS is not terminated.
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    pub hsts_max_age_seconds: Option<u64>,
 }
 
 #[derive(Clone, serde::Deserialize)]
 pub struct EmailClientSettings {
+    pub provider: EmailProvider,
     pub base_url: String,
     pub sender_email: String,
     pub authorization_token: SecretString,
     pub timeout_milliseconds: u64,
+    pub retry_policy: RetryPolicySettings,
+    pub smtp: Option<SmtpSettings>,
+    pub outbox_directory: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmailProvider {
+    Postmark,
+    Smtp,
+    Outbox,
+}
+
+#[derive(Clone, serde::Deserialize)]
+pub struct SmtpSettings {
+    pub host: String,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<SecretString>,
+    pub require_tls: bool,
+}
+
+#[derive(Clone, serde::Deserialize)]
+pub struct RetryPolicySettings {
+    pub max_attempts: u32,
+    pub base_delay_milliseconds: u64,
+    pub max_jitter_milliseconds: u64,
+    pub max_retry_after_milliseconds: u64,
 }
 
 impl DatabaseSettings {
@@ -59,7 +140,7 @@ impl DatabaseSettings {
         PgConnectOptions::new()
             .host(&self.host)
             .username(&self.username)
-            .password(&self.password.expose_secret())
+            .password(self.password.expose_secret())
             .port(self.port)
             .ssl_mode(ssl_mode)
     }
@@ -69,9 +150,121 @@ impl ApplicationSettings {
     pub fn address(&self) -> String {
         format!("{}:{}", self.host, self.port)
     }
+
+    pub fn subscription_token_ttl(&self) -> chrono::Duration {
+        chrono::Duration::hours(self.subscription_token_ttl_hours)
+    }
+
+    pub fn password_reset_token_ttl(&self) -> chrono::Duration {
+        chrono::Duration::minutes(self.password_reset_token_ttl_minutes)
+    }
+
+    pub fn invitation_token_ttl(&self) -> chrono::Duration {
+        chrono::Duration::days(self.invitation_token_ttl_days)
+    }
+
+    pub fn audience_timezone(&self) -> Result<Tz, anyhow::Error> {
+        self.audience_timezone
+            .parse()
+            .map_err(|_| anyhow::anyhow!("{} is not a valid timezone", self.audience_timezone))
+    }
+}
+
+impl BruteForceProtectionSettings {
+    pub fn login_throttle(&self) -> LoginThrottle {
+        LoginThrottle {
+            max_failures_per_username: self.max_failures_per_username,
+            max_failures_per_ip: self.max_failures_per_ip,
+            lockout: chrono::Duration::seconds(self.lockout_seconds),
+            base_delay: Duration::from_millis(self.base_delay_milliseconds),
+            max_delay: Duration::from_millis(self.max_delay_milliseconds),
+        }
+    }
+}
+
+impl PasswordHashingSettings {
+    pub fn password_hashing(&self) -> Result<PasswordHashing, anyhow::Error> {
+        let params = argon2::Params::new(
+            self.memory_cost_kib,
+            self.iterations,
+            self.parallelism,
+            None,
+        )
+        .context("Invalid Argon2 parameters")?;
+
+        PasswordHashing::new(params)
+    }
+}
+
+impl SecurityHeadersSettings {
+    pub fn default_headers(&self) -> DefaultHeaders {
+        let default_headers = DefaultHeaders::new()
+            .add((
+                "Content-Security-Policy",
+                self.content_security_policy.as_str(),
+            ))
+            .add(("X-Frame-Options", self.frame_options.as_str()))
+            .add(("Referrer-Policy", self.referrer_policy.as_str()))
+            .add(("X-Content-Type-Options", "nosniff"));
+
+        match self.hsts_max_age_seconds {
+            Some(max_age_seconds) => default_headers.add((
+                "Strict-Transport-Security",
+                format!("max-age={}", max_age_seconds),
+            )),
+            None => default_headers,
+        }
+    }
 }
 
 impl EmailClientSettings {
+    pub fn client(self) -> Result<EmailClient, anyhow::Error> {
+        let sender_email = self.sender().map_err(|error| anyhow::anyhow!(error))?;
+        let timeout = self.timeout();
+        let retry_policy = self.retry_policy();
+
+        let email_client = match self.provider {
+            EmailProvider::Postmark => EmailClient::new(
+                sender_email,
+                PostmarkTransport::new(self.base_url, self.authorization_token, timeout),
+                retry_policy,
+            ),
+            EmailProvider::Smtp => {
+                let smtp = self.smtp.ok_or_else(|| {
+                    anyhow::anyhow!("The `smtp` settings are required by the smtp email provider")
+                })?;
+                let credentials = smtp.username.zip(smtp.password);
+
+                EmailClient::new(
+                    sender_email,
+                    SmtpTransport::new(
+                        &smtp.host,
+                        smtp.port,
+                        credentials,
+                        smtp.require_tls,
+                        timeout,
+                    )?,
+                    retry_policy,
+                )
+            }
+            EmailProvider::Outbox => {
+                let outbox_directory = self.outbox_directory.ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "The `outbox_directory` setting is required by the outbox email provider"
+                    )
+                })?;
+
+                EmailClient::new(
+                    sender_email,
+                    OutboxTransport::new(outbox_directory.into())?,
+                    retry_policy,
+                )
+            }
+        };
+
+        Ok(email_client)
+    }
+
     pub fn sender(&self) -> Result<SubscriberEmail, String> {
         SubscriberEmail::parse(self.sender_email.clone())
     }
@@ -79,6 +272,15 @@ impl EmailClientSettings {
     pub fn timeout(&self) -> Duration {
         Duration::from_millis(self.timeout_milliseconds)
     }
+
+    pub fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: self.retry_policy.max_attempts,
+            base_delay: Duration::from_millis(self.retry_policy.base_delay_milliseconds),
+            max_jitter: Duration::from_millis(self.retry_policy.max_jitter_milliseconds),
+            max_retry_after: Duration::from_millis(self.retry_policy.max_retry_after_milliseconds),
+        }
+    }
 }
 
 pub enum Environment {
@@ -144,3 +346,60 @@ fn get_settings(environment: Environment) -> Result<Settings, config::ConfigErro
 
     settings.try_deserialize::<Settings>()
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::configuration::{SecurityHeadersSettings, Settings};
+    use actix_web::HttpResponse;
+    use actix_web::test::{TestRequest, call_service, init_service};
+    use actix_web::{App, web};
+
+    fn settings(hsts_max_age_seconds: Option<u64>) -> SecurityHeadersSettings {
+        SecurityHeadersSettings {
+            content_security_policy: "default-src 'none'".into(),
+            frame_options: "DENY".into(),
+            referrer_policy: "no-referrer".into(),
+            hsts_max_age_seconds,
+        }
+    }
+
+    #[tokio::test]
+    async fn given_an_hsts_max_age_then_it_should_send_hsts() {
+        let app = init_service(
+            App::new()
+                .wrap(settings(Some(31_536_000)).default_headers())
+                .route("/", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let response = call_service(&app, TestRequest::get().uri("/").to_request()).await;
+
+        assert_eq!(
+            response.headers().get("Strict-Transport-Security").unwrap(),
+            "max-age=31536000"
+        );
+    }
+
+    #[test]
+    fn given_no_totp_encryption_key_then_production_settings_should_be_rejected() {
+        let configuration_directory = std::env::current_dir().unwrap().join("configuration");
+        let settings = config::Config::builder()
+            .add_source(config::File::from(
+                configuration_directory.join("base.yaml"),
+            ))
+            .add_source(config::File::from(
+                configuration_directory.join("production.yaml"),
+            ))
+            .set_override("application.hmac_secret", "secret")
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let error = settings
+            .try_deserialize::<Settings>()
+            .err()
+            .expect("The settings should be rejected");
+
+        assert!(error.to_string().contains("totp_encryption_key"));
+    }
+}
@@ -0,0 +1,114 @@
+use crate::application::ApplicationBaseUrl;
+use crate::session_state::TypedSession;
+use actix_web::body::MessageBody;
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::error::{ErrorForbidden, ErrorInternalServerError};
+use actix_web::http::{Method, header};
+use actix_web::middleware::Next;
+use actix_web::{FromRequest, web};
+use subtle::ConstantTimeEq;
+
+const CSRF_TOKEN_FIELD: &str = "csrf_token";
+
+#[derive(serde::Deserialize)]
+struct CsrfTokenForm {
+    csrf_token: String,
+}
+
+pub fn csrf_token_field(session: &TypedSession) -> Result<String, actix_web::Error> {
+    let csrf_token = session
+        .get_or_insert_csrf_token()
+        .map_err(ErrorInternalServerError)?;
+
+    Ok(format!(
+        r#"<input type="hidden" name="{}" value="{}" />"#,
+        CSRF_TOKEN_FIELD, csrf_token
+    ))
+}
+
+// Form posts to the login flow and to the admin area must come from a page of ours: they have to
+// carry the token embedded in the forms we render, and, when the browser tells us where they come
+// from, that origin must be ours as well.
+pub async fn reject_forged_requests(
+    mut request: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    if request.method() != Method::POST || !is_protected_path(request.path()) {
+        return next.call(request).await;
+    }
+
+    if !is_same_origin(&request) {
+        return Err(ErrorForbidden("The request comes from another origin"));
+    }
+
+    let expected_csrf_token = {
+        let (http_request, payload) = request.parts_mut();
+        TypedSession::from_request(http_request, payload).await
+    }?
+    .get_csrf_token()
+    .map_err(ErrorInternalServerError)?;
+
+    let body = request.extract::<web::Bytes>().await?;
+    let submitted_csrf_token = std::str::from_utf8(&body)
+        .ok()
+        .and_then(|body| web::Query::<CsrfTokenForm>::from_query(body).ok())
+        .map(|form| form.into_inner().csrf_token);
+
+    // The form handlers still have to read the body that was consumed above.
+    request.set_payload(Payload::from(body));
+
+    match (expected_csrf_token, submitted_csrf_token) {
+        (Some(expected), Some(submitted))
+            if bool::from(expected.as_bytes().ct_eq(submitted.as_bytes())) =>
+        {
+            next.call(request).await
+        }
+        _ => Err(ErrorForbidden("The CSRF token is missing or invalid")),
+    }
+}
+
+fn is_protected_path(path: &str) -> bool {
+    ["/login", "/admin"]
+        .iter()
+        .any(|prefix| path == *prefix || path.starts_with(&format!("{}/", prefix)))
+}
+
+// Browsers send `Origin` with every form post, but other clients may omit it, in which case the
+// token alone is relied upon. Our own origin comes from the configuration rather than from the
+// request, whose `Host` and forwarding headers are set by the client.
+fn is_same_origin(request: &ServiceRequest) -> bool {
+    let Some(origin) = request.headers().get(header::ORIGIN) else {
+        return true;
+    };
+
+    let Some(application_origin) = request
+        .app_data::<web::Data<ApplicationBaseUrl>>()
+        .and_then(|base_url| reqwest::Url::parse(&base_url.0).ok())
+        .map(|base_url| base_url.origin().ascii_serialization())
+    else {
+        return false;
+    };
+
+    origin
+        .to_str()
+        .is_ok_and(|origin| origin == application_origin)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::csrf::is_protected_path;
+
+    #[test]
+    fn then_it_should_protect_the_login_flow_and_the_admin_area() {
+        assert!(is_protected_path("/login"));
+        assert!(is_protected_path("/login/two-factor"));
+        assert!(is_protected_path("/admin/password"));
+    }
+
+    #[test]
+    fn then_it_should_not_protect_public_endpoints() {
+        assert!(!is_protected_path("/newsletters"));
+        assert!(!is_protected_path("/subscriptions"));
+        assert!(!is_protected_path("/administrator"));
+    }
+}
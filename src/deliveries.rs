@@ -0,0 +1,172 @@
+use chrono::{DateTime, Utc};
+use sqlx::{Executor, PgExecutor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeliveryStatus {
+    Pending,
+    Sent,
+    Failed,
+    Skipped,
+}
+
+impl DeliveryStatus {
+    pub const ALL: [DeliveryStatus; 4] = [
+        DeliveryStatus::Pending,
+        DeliveryStatus::Sent,
+        DeliveryStatus::Failed,
+        DeliveryStatus::Skipped,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeliveryStatus::Pending => "pending",
+            DeliveryStatus::Sent => "sent",
+            DeliveryStatus::Failed => "failed",
+            DeliveryStatus::Skipped => "skipped",
+        }
+    }
+}
+
+impl TryFrom<&str> for DeliveryStatus {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        DeliveryStatus::ALL
+            .into_iter()
+            .find(|status| status.as_str() == value)
+            .ok_or_else(|| format!("{} is not a supported delivery status", value))
+    }
+}
+
+#[derive(Debug)]
+pub struct DeliveryAttempt<'a> {
+    pub status: DeliveryStatus,
+    pub attempts: u32,
+    pub error: Option<&'a str>,
+    pub provider_message_id: Option<&'a str>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq, serde::Serialize)]
+pub struct DeliveryCounts {
+    pub pending: i64,
+    pub sent: i64,
+    pub failed: i64,
+    pub skipped: i64,
+}
+
+pub struct FailedDelivery {
+    pub subscriber_email: String,
+    pub n_attempts: i32,
+    pub last_error: Option<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[tracing::instrument(name = "create_pending_deliveries", skip(transaction))]
+pub async fn create_pending_deliveries(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO deliveries (newsletter_issue_id, subscriber_email, status, updated_at)
+        SELECT newsletter_issue_id, subscriber_email, 'pending', now()
+        FROM issue_delivery_queue
+        WHERE newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id,
+    );
+
+    transaction.execute(query).await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(name = "record_delivery_attempt", skip(executor))]
+pub async fn record_delivery_attempt(
+    executor: impl PgExecutor<'_>,
+    newsletter_issue_id: Uuid,
+    subscriber_email: &str,
+    attempt: &DeliveryAttempt<'_>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE deliveries
+        SET status = $3,
+            n_attempts = n_attempts + $4,
+            last_error = $5,
+            provider_message_id = $6,
+            updated_at = now()
+        WHERE newsletter_issue_id = $1 AND subscriber_email = $2
+        "#,
+        newsletter_issue_id,
+        subscriber_email,
+        attempt.status.as_str(),
+        attempt.attempts as i32,
+        attempt.error,
+        attempt.provider_message_id,
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(name = "get_delivery_counts", skip(db_connection_pool))]
+pub async fn get_delivery_counts(
+    db_connection_pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<DeliveryCounts, sqlx::Error> {
+    sqlx::query_as!(
+        DeliveryCounts,
+        r#"
+        SELECT
+            COUNT(*) FILTER (WHERE status = 'pending') AS "pending!",
+            COUNT(*) FILTER (WHERE status = 'sent') AS "sent!",
+            COUNT(*) FILTER (WHERE status = 'failed') AS "failed!",
+            COUNT(*) FILTER (WHERE status = 'skipped') AS "skipped!"
+        FROM deliveries
+        WHERE newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_one(db_connection_pool)
+    .await
+}
+
+#[tracing::instrument(name = "list_failed_deliveries", skip(db_connection_pool))]
+pub async fn list_failed_deliveries(
+    db_connection_pool: &PgPool,
+    newsletter_issue_id: Uuid,
+    limit: i64,
+) -> Result<Vec<FailedDelivery>, sqlx::Error> {
+    sqlx::query_as!(
+        FailedDelivery,
+        r#"
+        SELECT subscriber_email, n_attempts, last_error, updated_at
+        FROM deliveries
+        WHERE newsletter_issue_id = $1 AND status = 'failed'
+        ORDER BY updated_at DESC
+        LIMIT $2
+        "#,
+        newsletter_issue_id,
+        limit
+    )
+    .fetch_all(db_connection_pool)
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::deliveries::DeliveryStatus;
+    use claims::assert_err;
+
+    #[test]
+    fn then_statuses_should_round_trip_through_their_names() {
+        for status in DeliveryStatus::ALL {
+            assert_eq!(DeliveryStatus::try_from(status.as_str()), Ok(status));
+        }
+
+        assert_err!(DeliveryStatus::try_from("bounced"));
+    }
+}
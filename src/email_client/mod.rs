@@ -0,0 +1,761 @@
+mod outbox;
+mod postmark;
+mod smtp;
+
+use crate::domain::SubscriberEmail;
+use crate::utils::error_chain_fmt;
+use lettre::message::header::{HeaderName, HeaderValue};
+use std::fmt::Debug;
+use std::time::Duration;
+
+pub use outbox::OutboxTransport;
+pub use postmark::PostmarkTransport;
+pub use smtp::SmtpTransport;
+
+#[derive(thiserror::Error)]
+pub enum SendEmailError {
+    #[error("A transient error was encountered while sending the email")]
+    Transient(#[source] anyhow::Error),
+    #[error("The email provider rejected the email")]
+    Permanent(#[source] anyhow::Error),
+    #[error("The email provider rate limited the request")]
+    RateLimited {
+        retry_after: Option<Duration>,
+        #[source]
+        source: anyhow::Error,
+    },
+    // Sending again could deliver the email twice, so it is never retried.
+    #[error("The email provider did not confirm whether it accepted the email")]
+    Unconfirmed(#[source] anyhow::Error),
+}
+
+impl Debug for SendEmailError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl SendEmailError {
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            SendEmailError::Transient(_) | SendEmailError::RateLimited { .. } => true,
+            SendEmailError::Permanent(_) | SendEmailError::Unconfirmed(_) => false,
+        }
+    }
+}
+
+pub struct Email<'a> {
+    pub from: &'a SubscriberEmail,
+    pub to: &'a SubscriberEmail,
+    pub subject: &'a str,
+    pub html_content: &'a str,
+    pub text_content: &'a str,
+    pub headers: &'a [(&'a str, &'a str)],
+}
+
+impl Email<'_> {
+    fn to_mime_message(&self) -> Result<lettre::Message, SendEmailError> {
+        let from = self
+            .from
+            .as_ref()
+            .parse()
+            .map_err(|error| SendEmailError::Permanent(anyhow::Error::new(error)))?;
+        let to = self
+            .to
+            .as_ref()
+            .parse()
+            .map_err(|error| SendEmailError::Permanent(anyhow::Error::new(error)))?;
+
+        let mut builder = lettre::Message::builder()
+            .from(from)
+            .to(to)
+            .subject(self.subject);
+
+        for (name, value) in self.headers {
+            let name = HeaderName::new_from_ascii(name.to_string())
+                .map_err(|error| SendEmailError::Permanent(anyhow::Error::new(error)))?;
+
+            builder = builder.raw_header(HeaderValue::new(name, value.to_string()));
+        }
+
+        builder
+            .multipart(lettre::message::MultiPart::alternative_plain_html(
+                self.text_content.to_owned(),
+                self.html_content.to_owned(),
+            ))
+            .map_err(|error| SendEmailError::Permanent(anyhow::Error::new(error)))
+    }
+}
+
+pub struct OutgoingEmail<'a> {
+    pub to: &'a SubscriberEmail,
+    pub subject: &'a str,
+    pub html_content: &'a str,
+    pub text_content: &'a str,
+    pub headers: &'a [(&'a str, &'a str)],
+}
+
+pub type BatchResults = Vec<Result<Option<String>, SendEmailError>>;
+
+#[async_trait::async_trait]
+pub trait EmailTransport: Send + Sync {
+    // Returns the identifier the provider assigned to the message, when it reports one.
+    async fn send(&self, email: &Email<'_>) -> Result<Option<String>, SendEmailError>;
+
+    // Transports with a batch endpoint report how many messages a single call may carry.
+    fn max_batch_size(&self) -> Option<usize> {
+        None
+    }
+
+    // How many of the emails, counting from the first one, a single batch call may carry. It is
+    // at least one, so that an email too large for any batch is still sent and turned down.
+    fn batch_len(&self, emails: &[Email<'_>]) -> usize {
+        emails.len().min(self.max_batch_size().unwrap_or(1).max(1))
+    }
+
+    // An error means the batch as a whole was not accepted. Otherwise there is one result per
+    // email, in the order they were given.
+    async fn send_batch(&self, emails: &[Email<'_>]) -> Result<BatchResults, SendEmailError> {
+        let mut results = Vec::with_capacity(emails.len());
+
+        for email in emails {
+            results.push(self.send(email).await);
+        }
+
+        Ok(results)
+    }
+}
+
+pub struct SendOutcome<T = Option<String>> {
+    pub attempts: u32,
+    pub result: Result<T, SendEmailError>,
+}
+
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_jitter: Duration,
+    pub max_retry_after: Duration,
+}
+
+impl RetryPolicy {
+    fn delay_for_attempt(&self, attempt: u32, error: &SendEmailError) -> Duration {
+        let backoff = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt - 1));
+        let jitter_milliseconds = rand::random_range(0..=self.max_jitter.as_millis() as u64);
+        let delay = backoff + Duration::from_millis(jitter_milliseconds);
+
+        match error {
+            SendEmailError::RateLimited {
+                retry_after: Some(retry_after),
+                ..
+            } => delay.max((*retry_after).min(self.max_retry_after)),
+            _ => delay,
+        }
+    }
+}
+
+pub struct EmailClient {
+    sender: SubscriberEmail,
+    transport: Box<dyn EmailTransport>,
+    retry_policy: RetryPolicy,
+}
+
+impl EmailClient {
+    pub fn new(
+        sender: SubscriberEmail,
+        transport: impl EmailTransport + 'static,
+        retry_policy: RetryPolicy,
+    ) -> Self {
+        Self {
+            sender,
+            transport: Box::new(transport),
+            retry_policy,
+        }
+    }
+
+    pub async fn send_email(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+    ) -> Result<(), SendEmailError> {
+        self.send_email_with_headers(recipient, subject, html_content, text_content, &[])
+            .await
+            .result
+            .map(|_| ())
+    }
+
+    pub async fn send_email_with_headers(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+        headers: &[(&str, &str)],
+    ) -> SendOutcome {
+        let email = Email {
+            from: &self.sender,
+            to: recipient,
+            subject,
+            html_content,
+            text_content,
+            headers,
+        };
+
+        self.with_retries(|| self.transport.send(&email)).await
+    }
+
+    pub fn max_batch_size(&self) -> Option<usize> {
+        self.transport.max_batch_size()
+    }
+
+    // Splits the emails into consecutive batches, each of which fits in a single batch call.
+    pub fn split_into_batches<'a, 'b>(
+        &self,
+        emails: &'b [OutgoingEmail<'a>],
+    ) -> Vec<&'b [OutgoingEmail<'a>]> {
+        let emails_to_send: Vec<Email> = emails.iter().map(|email| self.email(email)).collect();
+        let mut batches = Vec::new();
+        let mut start = 0;
+
+        while start < emails.len() {
+            let end = start + self.transport.batch_len(&emails_to_send[start..]);
+            batches.push(&emails[start..end]);
+            start = end;
+        }
+
+        batches
+    }
+
+    // A batch is retried as a whole only when the provider answered that it could not take it,
+    // which means that none of its emails went out. Emails of an accepted batch that were turned
+    // down for a passing reason are sent again together, in a batch of their own.
+    pub async fn send_batch(&self, emails: &[OutgoingEmail<'_>]) -> SendOutcome<Vec<SendOutcome>> {
+        let emails: Vec<Email> = emails.iter().map(|email| self.email(email)).collect();
+
+        let outcome = self
+            .with_retries(|| self.transport.send_batch(&emails))
+            .await;
+
+        let results = match outcome.result {
+            Ok(results) => results,
+            Err(error) => {
+                return SendOutcome {
+                    attempts: outcome.attempts,
+                    result: Err(error),
+                };
+            }
+        };
+
+        let mut outcomes: Vec<SendOutcome> = results
+            .into_iter()
+            .map(|result| SendOutcome {
+                attempts: outcome.attempts,
+                result,
+            })
+            .collect();
+
+        loop {
+            let retryable: Vec<usize> = (0..outcomes.len())
+                .filter(|&index| {
+                    outcomes[index]
+                        .result
+                        .as_ref()
+                        .is_err_and(SendEmailError::is_retryable)
+                        && outcomes[index].attempts < self.retry_policy.max_attempts
+                })
+                .collect();
+
+            let Some(&first) = retryable.first() else {
+                break;
+            };
+
+            let attempt = outcomes[first].attempts;
+
+            if let Err(error) = &outcomes[first].result {
+                let delay = self.retry_policy.delay_for_attempt(attempt, error);
+
+                tracing::warn!(
+                    n_emails = retryable.len(),
+                    attempt,
+                    delay_milliseconds = delay.as_millis() as u64,
+                    "Some emails of the batch were turned down. Retrying them",
+                );
+
+                tokio::time::sleep(delay).await;
+            }
+
+            let retried_emails: Vec<Email> = retryable
+                .iter()
+                .map(|&index| Email { ..emails[index] })
+                .collect();
+
+            match self.transport.send_batch(&retried_emails).await {
+                Ok(results) => {
+                    for (&index, result) in retryable.iter().zip(results) {
+                        outcomes[index] = SendOutcome {
+                            attempts: attempt + 1,
+                            result,
+                        };
+                    }
+                }
+                // The emails keep the error they were turned down with. A batch that was not
+                // taken is tried again while attempts remain, anything else ends the retries.
+                Err(error) => {
+                    tracing::warn!(
+                        error.cause_chain = ?error,
+                        error.message = %error,
+                        "Failed to send the emails turned down from the batch again",
+                    );
+
+                    for &index in &retryable {
+                        outcomes[index].attempts = attempt + 1;
+                    }
+
+                    if !error.is_retryable() {
+                        break;
+                    }
+                }
+            }
+        }
+
+        SendOutcome {
+            attempts: outcome.attempts,
+            result: Ok(outcomes),
+        }
+    }
+
+    fn email<'a>(&'a self, email: &OutgoingEmail<'a>) -> Email<'a> {
+        Email {
+            from: &self.sender,
+            to: email.to,
+            subject: email.subject,
+            html_content: email.html_content,
+            text_content: email.text_content,
+            headers: email.headers,
+        }
+    }
+
+    async fn with_retries<T, F, Fut>(&self, send: F) -> SendOutcome<T>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T, SendEmailError>>,
+    {
+        let first_outcome = SendOutcome {
+            attempts: 1,
+            result: send().await,
+        };
+
+        self.retry(first_outcome, send).await
+    }
+
+    // Keeps sending until it succeeds, fails for good or runs out of attempts.
+    async fn retry<T, F, Fut>(&self, mut outcome: SendOutcome<T>, send: F) -> SendOutcome<T>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T, SendEmailError>>,
+    {
+        loop {
+            match &outcome.result {
+                Err(error)
+                    if error.is_retryable()
+                        && outcome.attempts < self.retry_policy.max_attempts =>
+                {
+                    let delay = self.retry_policy.delay_for_attempt(outcome.attempts, error);
+
+                    tracing::warn!(
+                        error.cause_chain = ?error,
+                        error.message = %error,
+                        attempt = outcome.attempts,
+                        delay_milliseconds = delay.as_millis() as u64,
+                        "Failed to send email. Retrying",
+                    );
+
+                    tokio::time::sleep(delay).await;
+
+                    outcome = SendOutcome {
+                        attempts: outcome.attempts + 1,
+                        result: send().await,
+                    };
+                }
+                _ => return outcome,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::SubscriberEmail;
+    use crate::email_client::{
+        EmailClient, OutgoingEmail, PostmarkTransport, RetryPolicy, SendEmailError,
+    };
+    use claims::{assert_matches, assert_ok, assert_ok_eq};
+    use fake::faker::internet::en::SafeEmail;
+    use fake::faker::lorem::en::{Paragraph, Sentence};
+    use fake::{Fake, Faker};
+    use secrecy::SecretString;
+    use std::time::Duration;
+    use wiremock::matchers::{any, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn given_a_500_response_then_it_should_return_error() {
+        let mock_server = MockServer::start().await;
+        let sender_email = SubscriberEmail::parse(SafeEmail().fake()).unwrap();
+        let authorization_token_mock: String = Faker.fake::<String>();
+        let email_client = EmailClient::new(
+            sender_email,
+            PostmarkTransport::new(
+                mock_server.uri(),
+                SecretString::from(authorization_token_mock),
+                Duration::from_millis(200),
+            ),
+            retry_policy(),
+        );
+
+        let subscriber_email = email();
+        let subject: String = subject();
+        let content: String = content();
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(500))
+            .expect(3)
+            .mount(&mock_server)
+            .await;
+
+        let result = email_client
+            .send_email(&subscriber_email, &subject, &content, &content)
+            .await;
+
+        assert_matches!(result, Err(SendEmailError::Transient(_)));
+    }
+
+    #[tokio::test]
+    async fn given_a_422_response_then_it_should_return_a_permanent_error_without_retrying() {
+        let mock_server = MockServer::start().await;
+        let sender_email = SubscriberEmail::parse(SafeEmail().fake()).unwrap();
+        let authorization_token_mock: String = Faker.fake::<String>();
+        let email_client = EmailClient::new(
+            sender_email,
+            PostmarkTransport::new(
+                mock_server.uri(),
+                SecretString::from(authorization_token_mock),
+                Duration::from_millis(200),
+            ),
+            retry_policy(),
+        );
+
+        let subscriber_email = email();
+        let subject: String = subject();
+        let content: String = content();
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(422))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let result = email_client
+            .send_email(&subscriber_email, &subject, &content, &content)
+            .await;
+
+        assert_matches!(result, Err(SendEmailError::Permanent(_)));
+    }
+
+    #[tokio::test]
+    async fn given_a_429_response_then_it_should_return_a_rate_limited_error() {
+        let mock_server = MockServer::start().await;
+        let sender_email = SubscriberEmail::parse(SafeEmail().fake()).unwrap();
+        let authorization_token_mock: String = Faker.fake::<String>();
+        let email_client = EmailClient::new(
+            sender_email,
+            PostmarkTransport::new(
+                mock_server.uri(),
+                SecretString::from(authorization_token_mock),
+                Duration::from_millis(200),
+            ),
+            retry_policy(),
+        );
+
+        let subscriber_email = email();
+        let subject: String = subject();
+        let content: String = content();
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "0"))
+            .expect(3)
+            .mount(&mock_server)
+            .await;
+
+        let result = email_client
+            .send_email(&subscriber_email, &subject, &content, &content)
+            .await;
+
+        assert_matches!(
+            result,
+            Err(SendEmailError::RateLimited {
+                retry_after: Some(_),
+                ..
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn given_a_transient_failure_followed_by_a_success_then_it_should_return_ok() {
+        let mock_server = MockServer::start().await;
+        let sender_email = SubscriberEmail::parse(SafeEmail().fake()).unwrap();
+        let authorization_token_mock: String = Faker.fake::<String>();
+        let email_client = EmailClient::new(
+            sender_email,
+            PostmarkTransport::new(
+                mock_server.uri(),
+                SecretString::from(authorization_token_mock),
+                Duration::from_millis(200),
+            ),
+            retry_policy(),
+        );
+
+        let subscriber_email = email();
+        let subject: String = subject();
+        let content: String = content();
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let result = email_client
+            .send_email(&subscriber_email, &subject, &content, &content)
+            .await;
+
+        assert_ok!(result);
+    }
+
+    #[tokio::test]
+    async fn given_a_response_timeout_then_it_should_return_error() {
+        let mock_server = MockServer::start().await;
+        let sender_email = SubscriberEmail::parse(SafeEmail().fake()).unwrap();
+        let authorization_token_mock: String = Faker.fake::<String>();
+        let email_client = EmailClient::new(
+            sender_email,
+            PostmarkTransport::new(
+                mock_server.uri(),
+                SecretString::from(authorization_token_mock),
+                Duration::from_millis(200),
+            ),
+            retry_policy(),
+        );
+
+        let subscriber_email = email();
+        let subject: String = subject();
+        let content: String = content();
+
+        let response = ResponseTemplate::new(200).set_delay(Duration::from_secs(11));
+
+        Mock::given(any())
+            .respond_with(response)
+            .expect(3)
+            .mount(&mock_server)
+            .await;
+
+        let result = email_client
+            .send_email(&subscriber_email, &subject, &content, &content)
+            .await;
+
+        assert_matches!(result, Err(SendEmailError::Transient(_)));
+    }
+
+    #[tokio::test]
+    async fn given_a_transient_batch_failure_then_it_should_retry_the_whole_batch() {
+        let mock_server = MockServer::start().await;
+        let sender_email = SubscriberEmail::parse(SafeEmail().fake()).unwrap();
+        let authorization_token_mock: String = Faker.fake::<String>();
+        let email_client = EmailClient::new(
+            sender_email,
+            PostmarkTransport::new(
+                mock_server.uri(),
+                SecretString::from(authorization_token_mock),
+                Duration::from_millis(200),
+            ),
+            retry_policy(),
+        );
+
+        let subscriber_email = email();
+        let subject: String = subject();
+        let content: String = content();
+
+        Mock::given(path("/email/batch"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        Mock::given(path("/email/batch"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                { "ErrorCode": 0, "Message": "OK", "MessageID": "message-1" },
+            ])))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client
+            .send_batch(&[OutgoingEmail {
+                to: &subscriber_email,
+                subject: &subject,
+                html_content: &content,
+                text_content: &content,
+                headers: &[],
+            }])
+            .await;
+
+        assert_eq!(outcome.attempts, 2);
+        let outcomes = outcome.result.unwrap();
+        assert_eq!(outcomes.len(), 1);
+        assert_eq!(outcomes[0].attempts, 2);
+        assert_ok_eq!(&outcomes[0].result, &Some("message-1".to_string()));
+    }
+
+    #[tokio::test]
+    async fn given_a_message_turned_down_for_a_passing_reason_then_it_should_be_batched_again() {
+        let mock_server = MockServer::start().await;
+        let sender_email = SubscriberEmail::parse(SafeEmail().fake()).unwrap();
+        let email_client = EmailClient::new(
+            sender_email,
+            PostmarkTransport::new(
+                mock_server.uri(),
+                SecretString::from(Faker.fake::<String>()),
+                Duration::from_millis(200),
+            ),
+            retry_policy(),
+        );
+        let recipients = [email(), email()];
+        let subject: String = subject();
+        let content: String = content();
+
+        Mock::given(path("/email/batch"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                { "ErrorCode": 0, "Message": "OK", "MessageID": "message-1" },
+                { "ErrorCode": 100, "Message": "Maintenance" },
+            ])))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        Mock::given(path("/email/batch"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                { "ErrorCode": 0, "Message": "OK", "MessageID": "message-2" },
+            ])))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let emails: Vec<_> = recipients
+            .iter()
+            .map(|recipient| OutgoingEmail {
+                to: recipient,
+                subject: &subject,
+                html_content: &content,
+                text_content: &content,
+                headers: &[],
+            })
+            .collect();
+        let outcome = email_client.send_batch(&emails).await;
+
+        let outcomes = outcome.result.unwrap();
+        assert_eq!(outcomes[0].attempts, 1);
+        assert_ok_eq!(&outcomes[0].result, &Some("message-1".to_string()));
+        assert_eq!(outcomes[1].attempts, 2);
+        assert_ok_eq!(&outcomes[1].result, &Some("message-2".to_string()));
+
+        let requests = mock_server.received_requests().await.unwrap();
+        let retried_batch: Vec<serde_json::Value> =
+            serde_json::from_slice(&requests[1].body).unwrap();
+        assert_eq!(retried_batch.len(), 1);
+        assert_eq!(retried_batch[0]["To"], recipients[1].as_ref());
+    }
+
+    #[tokio::test]
+    async fn given_a_batch_timeout_then_it_should_not_be_sent_again() {
+        let mock_server = MockServer::start().await;
+        let sender_email = SubscriberEmail::parse(SafeEmail().fake()).unwrap();
+        let email_client = EmailClient::new(
+            sender_email,
+            PostmarkTransport::new(
+                mock_server.uri(),
+                SecretString::from(Faker.fake::<String>()),
+                Duration::from_millis(200),
+            ),
+            retry_policy(),
+        );
+        let subscriber_email = email();
+        let subject: String = subject();
+        let content: String = content();
+
+        Mock::given(path("/email/batch"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(1)))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client
+            .send_batch(&[OutgoingEmail {
+                to: &subscriber_email,
+                subject: &subject,
+                html_content: &content,
+                text_content: &content,
+                headers: &[],
+            }])
+            .await;
+
+        assert_eq!(outcome.attempts, 1);
+        assert!(matches!(outcome.result, Err(SendEmailError::Unconfirmed(_))));
+    }
+
+    #[test]
+    fn given_a_retry_after_above_the_maximum_then_the_delay_should_be_clamped() {
+        let error = SendEmailError::RateLimited {
+            retry_after: Some(Duration::from_secs(3600)),
+            source: anyhow::anyhow!("Too many requests"),
+        };
+
+        let delay = retry_policy().delay_for_attempt(1, &error);
+
+        assert_eq!(delay, Duration::from_millis(100));
+    }
+
+    fn retry_policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(10),
+            max_jitter: Duration::from_millis(10),
+            max_retry_after: Duration::from_millis(100),
+        }
+    }
+
+    fn subject() -> String {
+        Sentence(1..2).fake()
+    }
+
+    fn content() -> String {
+        Paragraph(1..10).fake()
+    }
+
+    fn email() -> SubscriberEmail {
+        SubscriberEmail::parse(SafeEmail().fake()).unwrap()
+    }
+}
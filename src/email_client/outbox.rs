@@ -0,0 +1,78 @@
+use crate::email_client::{Email, EmailTransport, SendEmailError};
+use lettre::{AsyncFileTransport, AsyncTransport, Tokio1Executor};
+use std::path::PathBuf;
+
+pub struct OutboxTransport {
+    mailer: AsyncFileTransport<Tokio1Executor>,
+}
+
+impl OutboxTransport {
+    pub fn new(directory: PathBuf) -> Result<Self, anyhow::Error> {
+        std::fs::create_dir_all(&directory)?;
+
+        Ok(Self {
+            mailer: AsyncFileTransport::new(directory),
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailTransport for OutboxTransport {
+    async fn send(&self, email: &Email<'_>) -> Result<Option<String>, SendEmailError> {
+        let message = email.to_mime_message()?;
+
+        let message_id = self
+            .mailer
+            .send(message)
+            .await
+            .map_err(|error| SendEmailError::Transient(error.into()))?;
+
+        tracing::info!(message_id, "Email written to the outbox");
+
+        Ok(Some(message_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::SubscriberEmail;
+    use crate::email_client::{Email, EmailTransport, OutboxTransport};
+    use claims::assert_ok;
+    use fake::Fake;
+    use fake::faker::internet::en::SafeEmail;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn then_it_should_write_an_eml_file_to_the_outbox_directory() {
+        let directory = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        let transport = OutboxTransport::new(directory.clone()).unwrap();
+        let sender = SubscriberEmail::parse(SafeEmail().fake()).unwrap();
+        let recipient = SubscriberEmail::parse(SafeEmail().fake()).unwrap();
+
+        let result = transport
+            .send(&Email {
+                from: &sender,
+                to: &recipient,
+                subject: "Newsletter title",
+                html_content: "<p>Newsletter HTML content</p>",
+                text_content: "Newsletter plain content",
+                headers: &[],
+            })
+            .await;
+
+        assert_ok!(result);
+
+        let files: Vec<_> = std::fs::read_dir(&directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].extension().unwrap(), "eml");
+
+        let content = std::fs::read_to_string(&files[0]).unwrap();
+        assert!(content.contains("Subject: Newsletter title"));
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,644 @@
+use crate::email_client::{BatchResults, Email, EmailTransport, SendEmailError};
+use reqwest::header::RETRY_AFTER;
+use reqwest::{Client, StatusCode};
+use secrecy::{ExposeSecret, SecretString};
+use std::time::Duration;
+
+pub struct PostmarkTransport {
+    http_client: Client,
+    base_url: String,
+    authorization_token: SecretString,
+}
+
+impl PostmarkTransport {
+    pub fn new(base_url: String, authorization_token: SecretString, timeout: Duration) -> Self {
+        let http_client = Client::builder().timeout(timeout).build().unwrap();
+
+        Self {
+            http_client,
+            base_url,
+            authorization_token,
+        }
+    }
+}
+
+// Postmark rejects batch requests carrying more messages or a larger payload than this.
+const MAX_BATCH_SIZE: usize = 500;
+const MAX_BATCH_PAYLOAD_SIZE: usize = 50 * 1024 * 1024;
+
+// Error codes Postmark reports for a single message of a batch that are worth retrying. Any other
+// code means the message itself was turned down.
+const MAINTENANCE_ERROR_CODE: u32 = 100;
+const RATE_LIMIT_ERROR_CODE: u32 = 429;
+
+impl PostmarkTransport {
+    async fn post(
+        &self,
+        path: &str,
+        request_body: &impl serde::Serialize,
+        classify_request_error: fn(reqwest::Error) -> SendEmailError,
+    ) -> Result<reqwest::Response, SendEmailError> {
+        let url = format!("{}{}", self.base_url, path);
+
+        let response = self
+            .http_client
+            .post(url)
+            .header(
+                "X-Postmark-Server-Token",
+                self.authorization_token.expose_secret(),
+            )
+            .json(request_body)
+            .send()
+            .await
+            .map_err(classify_request_error)?;
+
+        let status = response.status();
+        let retry_after = response
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_retry_after);
+
+        response.error_for_status().map_err(|error| {
+            if status == StatusCode::TOO_MANY_REQUESTS {
+                SendEmailError::RateLimited {
+                    retry_after,
+                    source: error.into(),
+                }
+            } else if status.is_server_error() {
+                SendEmailError::Transient(error.into())
+            } else {
+                SendEmailError::Permanent(error.into())
+            }
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailTransport for PostmarkTransport {
+    async fn send(&self, email: &Email<'_>) -> Result<Option<String>, SendEmailError> {
+        let response = self
+            .post(
+                "/email",
+                &SendEmailRequestBody::from(email),
+                classify_request_error,
+            )
+            .await?;
+
+        // The message has been accepted at this point, so an unreadable body only loses its id.
+        let message_id = response
+            .json::<SendEmailResponseBody>()
+            .await
+            .ok()
+            .and_then(|body| body.message_id);
+
+        Ok(message_id)
+    }
+
+    fn max_batch_size(&self) -> Option<usize> {
+        Some(MAX_BATCH_SIZE)
+    }
+
+    fn batch_len(&self, emails: &[Email<'_>]) -> usize {
+        // The JSON array adds its brackets, and a comma between each message.
+        let mut payload_size = 1;
+
+        let batch_len = emails
+            .iter()
+            .take(MAX_BATCH_SIZE)
+            .take_while(|email| {
+                payload_size += payload_size_of(email) + 1;
+                payload_size <= MAX_BATCH_PAYLOAD_SIZE
+            })
+            .count();
+
+        batch_len.max(1).min(emails.len())
+    }
+
+    async fn send_batch(&self, emails: &[Email<'_>]) -> Result<BatchResults, SendEmailError> {
+        if emails.len() > MAX_BATCH_SIZE {
+            return Err(SendEmailError::Permanent(anyhow::anyhow!(
+                "A batch carries at most {} emails, {} were given",
+                MAX_BATCH_SIZE,
+                emails.len()
+            )));
+        }
+
+        let request_body: Vec<SendEmailRequestBody> =
+            emails.iter().map(SendEmailRequestBody::from).collect();
+        let payload_size = serde_json::to_vec(&request_body)
+            .map_err(|error| SendEmailError::Permanent(error.into()))?
+            .len();
+
+        if payload_size > MAX_BATCH_PAYLOAD_SIZE {
+            return Err(SendEmailError::Permanent(anyhow::anyhow!(
+                "A batch carries at most {} bytes, {} were given",
+                MAX_BATCH_PAYLOAD_SIZE,
+                payload_size
+            )));
+        }
+
+        let response = self
+            .post("/email/batch", &request_body, classify_batch_request_error)
+            .await?;
+
+        // Postmark answers 200 even when some of the messages are rejected, listing an outcome for
+        // each of them. Without that list there is no telling which ones went out, so none of them
+        // is reported as sent, nor sent again.
+        let response_body: Vec<SendEmailResponseBody> = response.json().await.map_err(|error| {
+            tracing::error!(
+                error.message = %error,
+                "Failed to read the outcomes of an accepted batch",
+            );
+
+            SendEmailError::Unconfirmed(error.into())
+        })?;
+
+        if response_body.len() != emails.len() {
+            tracing::error!(
+                n_outcomes = response_body.len(),
+                n_emails = emails.len(),
+                "Postmark did not report an outcome for every email of the batch",
+            );
+
+            return Err(SendEmailError::Unconfirmed(anyhow::anyhow!(
+                "Postmark reported {} outcomes for a batch of {} emails",
+                response_body.len(),
+                emails.len()
+            )));
+        }
+
+        Ok(response_body
+            .into_iter()
+            .map(SendEmailResponseBody::into_result)
+            .collect())
+    }
+}
+
+fn payload_size_of(email: &Email<'_>) -> usize {
+    serde_json::to_vec(&SendEmailRequestBody::from(email))
+        .expect("An email is made of strings, which always serialize")
+        .len()
+}
+
+// Retry-After carries either a number of seconds or an HTTP-date.
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    if let Ok(seconds) = value.trim().parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let retry_at = chrono::DateTime::parse_from_rfc2822(value.trim()).ok()?;
+
+    Some(
+        (retry_at.with_timezone(&chrono::Utc) - chrono::Utc::now())
+            .to_std()
+            .unwrap_or_default(),
+    )
+}
+
+fn classify_request_error(error: reqwest::Error) -> SendEmailError {
+    if error.is_timeout() || error.is_connect() || error.is_request() {
+        SendEmailError::Transient(error.into())
+    } else {
+        SendEmailError::Permanent(error.into())
+    }
+}
+
+// Short of a refused connection, Postmark may have taken a batch it never answered for. Sending it
+// again would then deliver every one of its emails twice.
+fn classify_batch_request_error(error: reqwest::Error) -> SendEmailError {
+    if error.is_connect() {
+        SendEmailError::Transient(error.into())
+    } else if error.is_timeout() || error.is_request() {
+        SendEmailError::Unconfirmed(error.into())
+    } else {
+        SendEmailError::Permanent(error.into())
+    }
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct SendEmailRequestBody<'a> {
+    from: &'a str,
+    to: &'a str,
+    subject: &'a str,
+    html_body: &'a str,
+    text_body: &'a str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    headers: Vec<SendEmailRequestHeader<'a>>,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct SendEmailRequestHeader<'a> {
+    name: &'a str,
+    value: &'a str,
+}
+
+impl<'a> From<&'a Email<'a>> for SendEmailRequestBody<'a> {
+    fn from(email: &'a Email<'a>) -> Self {
+        Self {
+            from: email.from.as_ref(),
+            to: email.to.as_ref(),
+            subject: email.subject,
+            html_body: email.html_content,
+            text_body: email.text_content,
+            headers: email
+                .headers
+                .iter()
+                .map(|(name, value)| SendEmailRequestHeader { name, value })
+                .collect(),
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SendEmailResponseBody {
+    #[serde(default)]
+    error_code: u32,
+    #[serde(default)]
+    message: String,
+    #[serde(rename = "MessageID")]
+    message_id: Option<String>,
+}
+
+impl SendEmailResponseBody {
+    fn into_result(self) -> Result<Option<String>, SendEmailError> {
+        if self.error_code == 0 {
+            return Ok(self.message_id);
+        }
+
+        let error = anyhow::anyhow!("Postmark error {}: {}", self.error_code, self.message);
+
+        match self.error_code {
+            MAINTENANCE_ERROR_CODE => Err(SendEmailError::Transient(error)),
+            RATE_LIMIT_ERROR_CODE => Err(SendEmailError::RateLimited {
+                retry_after: None,
+                source: error,
+            }),
+            _ => Err(SendEmailError::Permanent(error)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::SubscriberEmail;
+    use crate::email_client::postmark::parse_retry_after;
+    use crate::email_client::{Email, EmailTransport, PostmarkTransport, SendEmailError};
+    use claims::{assert_matches, assert_ok, assert_ok_eq};
+    use fake::faker::internet::en::SafeEmail;
+    use fake::faker::lorem::en::{Paragraph, Sentence};
+    use fake::{Fake, Faker};
+    use secrecy::SecretString;
+    use std::time::Duration;
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    struct SendEmailRequestBodyMatcher {
+        expected_body: serde_json::Value,
+    }
+
+    impl SendEmailRequestBodyMatcher {
+        pub fn create(expected_body: serde_json::Value) -> SendEmailRequestBodyMatcher {
+            SendEmailRequestBodyMatcher { expected_body }
+        }
+    }
+
+    impl wiremock::Match for SendEmailRequestBodyMatcher {
+        fn matches(&self, request: &wiremock::Request) -> bool {
+            let request_body_result: Result<serde_json::value::Value, _> =
+                serde_json::from_slice(&request.body);
+
+            if let Ok(request_body) = request_body_result {
+                request_body.get("From") == self.expected_body.get("From")
+                    && request_body.get("To") == self.expected_body.get("To")
+                    && request_body.get("Subject") == self.expected_body.get("Subject")
+                    && request_body.get("HtmlBody") == self.expected_body.get("HtmlBody")
+                    && request_body.get("TextBody") == self.expected_body.get("TextBody")
+            } else {
+                false
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn then_it_should_send_email() {
+        let mock_server = MockServer::start().await;
+        let sender_email = email();
+        let authorization_token_mock: String = Faker.fake::<String>();
+        let transport = PostmarkTransport::new(
+            mock_server.uri(),
+            SecretString::from(authorization_token_mock.clone()),
+            Duration::from_millis(200),
+        );
+        let subscriber_email = email();
+        let subject: String = subject();
+        let content: String = content();
+
+        let request_body_expected = serde_json::json!({
+            "From": sender_email.as_ref(),
+            "To": subscriber_email.as_ref(),
+            "Subject": subject,
+            "HtmlBody": content,
+            "TextBody": content,
+        });
+
+        Mock::given(header("X-Postmark-Server-Token", authorization_token_mock))
+            .and(header("Content-Type", "application/json"))
+            .and(path("/email"))
+            .and(method("POST"))
+            .and(SendEmailRequestBodyMatcher::create(request_body_expected))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let result = transport
+            .send(&Email {
+                from: &sender_email,
+                to: &subscriber_email,
+                subject: &subject,
+                html_content: &content,
+                text_content: &content,
+                headers: &[],
+            })
+            .await;
+
+        assert_ok!(result);
+    }
+
+    #[tokio::test]
+    async fn given_a_message_id_in_the_response_then_it_should_return_it() {
+        let mock_server = MockServer::start().await;
+        let transport = PostmarkTransport::new(
+            mock_server.uri(),
+            SecretString::from(Faker.fake::<String>()),
+            Duration::from_millis(200),
+        );
+        let sender_email = email();
+        let subscriber_email = email();
+
+        Mock::given(path("/email"))
+            .and(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "To": subscriber_email.as_ref(),
+                "MessageID": "b7bc2f4a-e38e-4336-af7d-e6c392c2f817",
+                "ErrorCode": 0,
+                "Message": "OK",
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let result = transport
+            .send(&Email {
+                from: &sender_email,
+                to: &subscriber_email,
+                subject: &subject(),
+                html_content: &content(),
+                text_content: &content(),
+                headers: &[],
+            })
+            .await;
+
+        assert_ok_eq!(
+            result,
+            Some("b7bc2f4a-e38e-4336-af7d-e6c392c2f817".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn given_a_batch_then_it_should_return_one_outcome_per_email_in_order() {
+        let mock_server = MockServer::start().await;
+        let transport = PostmarkTransport::new(
+            mock_server.uri(),
+            SecretString::from(Faker.fake::<String>()),
+            Duration::from_millis(200),
+        );
+        let sender_email = email();
+        let recipients = [email(), email()];
+        let subject = subject();
+        let content = content();
+        let emails: Vec<_> = recipients
+            .iter()
+            .map(|recipient| Email {
+                from: &sender_email,
+                to: recipient,
+                subject: &subject,
+                html_content: &content,
+                text_content: &content,
+                headers: &[],
+            })
+            .collect();
+
+        Mock::given(path("/email/batch"))
+            .and(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                {
+                    "To": recipients[0].as_ref(),
+                    "MessageID": "b7bc2f4a-e38e-4336-af7d-e6c392c2f817",
+                    "ErrorCode": 0,
+                    "Message": "OK",
+                },
+                {
+                    "ErrorCode": 406,
+                    "Message": "You tried to send to a recipient that has been marked as inactive.",
+                },
+            ])))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let results = transport.send_batch(&emails).await.unwrap();
+
+        assert_eq!(results.len(), 2);
+        assert_ok_eq!(
+            &results[0],
+            &Some("b7bc2f4a-e38e-4336-af7d-e6c392c2f817".to_string())
+        );
+        assert_matches!(&results[1], Err(SendEmailError::Permanent(_)));
+
+        let request = &mock_server.received_requests().await.unwrap()[0];
+        let request_body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        assert_eq!(request_body[0]["To"], recipients[0].as_ref());
+        assert_eq!(request_body[1]["To"], recipients[1].as_ref());
+    }
+
+    #[tokio::test]
+    async fn given_a_batch_with_passing_errors_then_they_should_be_retryable() {
+        let mock_server = MockServer::start().await;
+        let transport = PostmarkTransport::new(
+            mock_server.uri(),
+            SecretString::from(Faker.fake::<String>()),
+            Duration::from_millis(200),
+        );
+        let sender_email = email();
+        let recipient = email();
+        let emails: Vec<_> = (0..2)
+            .map(|_| Email {
+                from: &sender_email,
+                to: &recipient,
+                subject: "Newsletter title",
+                html_content: "<p>Newsletter HTML content</p>",
+                text_content: "Newsletter plain content",
+                headers: &[],
+            })
+            .collect();
+
+        Mock::given(path("/email/batch"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                { "ErrorCode": 100, "Message": "Maintenance" },
+                { "ErrorCode": 429, "Message": "Rate limit exceeded" },
+            ])))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let results = transport.send_batch(&emails).await.unwrap();
+
+        assert_matches!(&results[0], Err(SendEmailError::Transient(_)));
+        assert_matches!(&results[1], Err(SendEmailError::RateLimited { .. }));
+    }
+
+    #[tokio::test]
+    async fn given_an_accepted_batch_without_usable_outcomes_then_none_should_count_as_sent() {
+        let mock_server = MockServer::start().await;
+        let transport = PostmarkTransport::new(
+            mock_server.uri(),
+            SecretString::from(Faker.fake::<String>()),
+            Duration::from_millis(200),
+        );
+        let sender_email = email();
+        let recipient = email();
+        let emails: Vec<_> = (0..2)
+            .map(|_| Email {
+                from: &sender_email,
+                to: &recipient,
+                subject: "Newsletter title",
+                html_content: "<p>Newsletter HTML content</p>",
+                text_content: "Newsletter plain content",
+                headers: &[],
+            })
+            .collect();
+
+        Mock::given(path("/email/batch"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("not json"))
+            .up_to_n_times(1)
+            .mount(&mock_server)
+            .await;
+
+        Mock::given(path("/email/batch"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                { "ErrorCode": 0, "Message": "OK", "MessageID": "message-1" },
+            ])))
+            .mount(&mock_server)
+            .await;
+
+        for _ in 0..2 {
+            let result = transport.send_batch(&emails).await;
+
+            assert_matches!(result, Err(SendEmailError::Unconfirmed(_)));
+        }
+    }
+
+    #[tokio::test]
+    async fn given_more_emails_than_a_batch_can_carry_then_it_should_not_send_any() {
+        let mock_server = MockServer::start().await;
+        let transport = PostmarkTransport::new(
+            mock_server.uri(),
+            SecretString::from(Faker.fake::<String>()),
+            Duration::from_millis(200),
+        );
+        let sender_email = email();
+        let recipient = email();
+        let emails: Vec<_> = (0..501)
+            .map(|_| Email {
+                from: &sender_email,
+                to: &recipient,
+                subject: "Newsletter title",
+                html_content: "<p>Newsletter HTML content</p>",
+                text_content: "Newsletter plain content",
+                headers: &[],
+            })
+            .collect();
+
+        Mock::given(path("/email/batch"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&mock_server)
+            .await;
+
+        let result = transport.send_batch(&emails).await;
+
+        assert_matches!(result, Err(SendEmailError::Permanent(_)));
+    }
+
+    #[test]
+    fn given_emails_larger_than_a_batch_payload_then_they_should_be_split() {
+        let transport = PostmarkTransport::new(
+            "http://localhost".into(),
+            SecretString::from(Faker.fake::<String>()),
+            Duration::from_millis(200),
+        );
+        let sender_email = email();
+        let recipient = email();
+        let html_content = "a".repeat(20 * 1024 * 1024);
+        let emails: Vec<_> = (0..3)
+            .map(|_| Email {
+                from: &sender_email,
+                to: &recipient,
+                subject: "Newsletter title",
+                html_content: &html_content,
+                text_content: "Newsletter plain content",
+                headers: &[],
+            })
+            .collect();
+
+        assert_eq!(transport.batch_len(&emails), 2);
+        assert_eq!(transport.batch_len(&emails[2..]), 1);
+        assert_eq!(transport.batch_len(&emails[..0]), 0);
+    }
+
+    #[test]
+    fn given_a_retry_after_in_seconds_then_it_should_be_parsed() {
+        assert_eq!(parse_retry_after("120"), Some(Duration::from_secs(120)));
+    }
+
+    #[test]
+    fn given_a_retry_after_http_date_then_it_should_be_parsed_relative_to_now() {
+        let retry_at = chrono::Utc::now() + chrono::Duration::seconds(120);
+        let value = retry_at.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+
+        let retry_after = parse_retry_after(&value).unwrap();
+
+        assert!(retry_after > Duration::from_secs(110));
+        assert!(retry_after <= Duration::from_secs(120));
+    }
+
+    #[test]
+    fn given_a_retry_after_http_date_in_the_past_then_it_should_be_zero() {
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+            Some(Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn given_an_invalid_retry_after_then_it_should_be_ignored() {
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+
+    fn subject() -> String {
+        Sentence(1..2).fake()
+    }
+
+    fn content() -> String {
+        Paragraph(1..10).fake()
+    }
+
+    fn email() -> SubscriberEmail {
+        SubscriberEmail::parse(SafeEmail().fake()).unwrap()
+    }
+}
@@ -0,0 +1,176 @@
+use crate::email_client::{Email, EmailTransport, SendEmailError};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Tokio1Executor};
+use secrecy::{ExposeSecret, SecretString};
+use std::time::Duration;
+
+pub struct SmtpTransport {
+    mailer: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl SmtpTransport {
+    pub fn new(
+        host: &str,
+        port: u16,
+        credentials: Option<(String, SecretString)>,
+        require_tls: bool,
+        timeout: Duration,
+    ) -> Result<Self, anyhow::Error> {
+        let mut builder = if require_tls {
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?
+        } else {
+            AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host)
+        };
+
+        builder = builder.port(port).timeout(Some(timeout));
+
+        if let Some((username, password)) = credentials {
+            builder = builder.credentials(Credentials::new(
+                username,
+                password.expose_secret().to_owned(),
+            ));
+        }
+
+        Ok(Self {
+            mailer: builder.build(),
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailTransport for SmtpTransport {
+    async fn send(&self, email: &Email<'_>) -> Result<Option<String>, SendEmailError> {
+        let message = email.to_mime_message()?;
+
+        self.mailer
+            .send(message)
+            .await
+            .map_err(classify_smtp_error)?;
+
+        // SMTP relays only mention a queue id in free-form reply text, so there is none to keep.
+        Ok(None)
+    }
+}
+
+fn classify_smtp_error(error: lettre::transport::smtp::Error) -> SendEmailError {
+    if error.is_permanent() || error.is_client() {
+        SendEmailError::Permanent(error.into())
+    } else {
+        SendEmailError::Transient(error.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::SubscriberEmail;
+    use crate::email_client::{Email, EmailTransport, SendEmailError, SmtpTransport};
+    use claims::{assert_matches, assert_ok};
+    use fake::Fake;
+    use fake::faker::internet::en::SafeEmail;
+    use std::time::Duration;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+    use tokio::task::JoinHandle;
+
+    // Minimal SMTP sink accepting a single message. Returns the raw DATA section it received.
+    async fn start_smtp_sink(recipient_reply: &'static str) -> (u16, JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let handle = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            let mut data = String::new();
+            let mut reading_data = false;
+
+            writer.write_all(b"220 localhost ESMTP\r\n").await.unwrap();
+
+            while let Ok(Some(line)) = lines.next_line().await {
+                if reading_data {
+                    if line == "." {
+                        reading_data = false;
+                        writer.write_all(b"250 Queued\r\n").await.unwrap();
+                    } else {
+                        data.push_str(&line);
+                        data.push('\n');
+                    }
+                    continue;
+                }
+
+                let reply = match line.split_whitespace().next().unwrap_or_default() {
+                    "EHLO" | "HELO" => "250 localhost\r\n",
+                    "RCPT" => recipient_reply,
+                    "DATA" => {
+                        reading_data = true;
+                        "354 End data with <CR><LF>.<CR><LF>\r\n"
+                    }
+                    "QUIT" => {
+                        writer.write_all(b"221 Bye\r\n").await.unwrap();
+                        break;
+                    }
+                    _ => "250 OK\r\n",
+                };
+
+                writer.write_all(reply.as_bytes()).await.unwrap();
+            }
+
+            data
+        });
+
+        (port, handle)
+    }
+
+    fn transport(port: u16) -> SmtpTransport {
+        SmtpTransport::new("127.0.0.1", port, None, false, Duration::from_secs(2)).unwrap()
+    }
+
+    fn email() -> SubscriberEmail {
+        SubscriberEmail::parse(SafeEmail().fake()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn then_it_should_deliver_the_message_to_the_smtp_server() {
+        let (port, sink) = start_smtp_sink("250 OK\r\n").await;
+        let sender = email();
+        let recipient = email();
+
+        let result = transport(port)
+            .send(&Email {
+                from: &sender,
+                to: &recipient,
+                subject: "Newsletter title",
+                html_content: "<p>Newsletter HTML content</p>",
+                text_content: "Newsletter plain content",
+                headers: &[],
+            })
+            .await;
+
+        assert_ok!(result);
+
+        let data = sink.await.unwrap();
+        assert!(data.contains("Subject: Newsletter title"));
+        assert!(data.contains(&format!("To: {}", recipient)));
+        assert!(data.contains("Newsletter plain content"));
+    }
+
+    #[tokio::test]
+    async fn given_a_rejected_recipient_then_it_should_return_a_permanent_error() {
+        let (port, _sink) = start_smtp_sink("550 No such user\r\n").await;
+        let sender = email();
+        let recipient = email();
+
+        let result = transport(port)
+            .send(&Email {
+                from: &sender,
+                to: &recipient,
+                subject: "Newsletter title",
+                html_content: "<p>Newsletter HTML content</p>",
+                text_content: "Newsletter plain content",
+                headers: &[],
+            })
+            .await;
+
+        assert_matches!(result, Err(SendEmailError::Permanent(_)));
+    }
+}
@@ -0,0 +1,159 @@
+use crate::idempotency::IdempotencyKey;
+use actix_web::HttpResponse;
+use actix_web::body::to_bytes;
+use actix_web::http::StatusCode;
+use anyhow::Context;
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+#[derive(Debug, sqlx::Type)]
+#[sqlx(type_name = "header_pair")]
+struct HeaderPairRecord {
+    name: String,
+    value: Vec<u8>,
+}
+
+pub enum NextAction {
+    StartProcessing(Transaction<'static, Postgres>),
+    ReturnSavedResponse(HttpResponse),
+    RequestInProgress,
+}
+
+#[tracing::instrument(name = "try_processing", skip(db_connection_pool))]
+pub async fn try_processing(
+    db_connection_pool: &PgPool,
+    idempotency_key: &IdempotencyKey,
+    user_id: Uuid,
+) -> Result<NextAction, anyhow::Error> {
+    let mut transaction = db_connection_pool
+        .begin()
+        .await
+        .context("Failed to get a database connection from the pool")?;
+
+    // A concurrent request with the same key waits here until the first transaction is either
+    // committed (we then replay its response) or rolled back (we then process the request).
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO idempotency (user_id, idempotency_key, created_at)
+        VALUES ($1, $2, now())
+        ON CONFLICT DO NOTHING
+        "#,
+        user_id,
+        idempotency_key.as_ref()
+    );
+
+    let inserted_rows = transaction
+        .execute(query)
+        .await
+        .context("Failed to register the idempotency key")?
+        .rows_affected();
+
+    if inserted_rows > 0 {
+        return Ok(NextAction::StartProcessing(transaction));
+    }
+
+    let saved_response = get_saved_response(db_connection_pool, idempotency_key, user_id).await?;
+
+    Ok(saved_response
+        .map(NextAction::ReturnSavedResponse)
+        .unwrap_or(NextAction::RequestInProgress))
+}
+
+#[tracing::instrument(name = "get_saved_response", skip(db_connection_pool))]
+async fn get_saved_response(
+    db_connection_pool: &PgPool,
+    idempotency_key: &IdempotencyKey,
+    user_id: Uuid,
+) -> Result<Option<HttpResponse>, anyhow::Error> {
+    let saved_response = sqlx::query!(
+        r#"
+        SELECT
+            response_status_code,
+            response_headers as "response_headers: Vec<HeaderPairRecord>",
+            response_body
+        FROM idempotency
+        WHERE user_id = $1 AND idempotency_key = $2
+        "#,
+        user_id,
+        idempotency_key.as_ref()
+    )
+    .fetch_optional(db_connection_pool)
+    .await
+    .context("Failed to retrieve the saved response")?;
+
+    let Some(row) = saved_response else {
+        return Ok(None);
+    };
+
+    let (Some(status_code), Some(headers), Some(body)) = (
+        row.response_status_code,
+        row.response_headers,
+        row.response_body,
+    ) else {
+        return Ok(None);
+    };
+
+    let status_code = StatusCode::from_u16(status_code.try_into()?)?;
+    let mut response = HttpResponse::build(status_code);
+
+    for HeaderPairRecord { name, value } in headers {
+        response.append_header((name, value));
+    }
+
+    Ok(Some(response.body(body)))
+}
+
+#[tracing::instrument(name = "save_response", skip(transaction, http_response))]
+pub async fn save_response(
+    mut transaction: Transaction<'static, Postgres>,
+    idempotency_key: &IdempotencyKey,
+    user_id: Uuid,
+    http_response: HttpResponse,
+) -> Result<HttpResponse, anyhow::Error> {
+    let (response_head, body) = http_response.into_parts();
+
+    let body = to_bytes(body)
+        .await
+        .map_err(|error| anyhow::anyhow!("{}", error))?;
+
+    let status_code = response_head.status().as_u16() as i16;
+
+    let headers: Vec<HeaderPairRecord> = response_head
+        .headers()
+        .iter()
+        .map(|(name, value)| HeaderPairRecord {
+            name: name.as_str().to_owned(),
+            value: value.as_bytes().to_owned(),
+        })
+        .collect();
+
+    let query = sqlx::query_unchecked!(
+        r#"
+        UPDATE idempotency
+        SET
+            response_status_code = $3,
+            response_headers = $4,
+            response_body = $5
+        WHERE user_id = $1 AND idempotency_key = $2
+        "#,
+        user_id,
+        idempotency_key.as_ref(),
+        status_code,
+        headers,
+        body.as_ref()
+    );
+
+    transaction
+        .execute(query)
+        .await
+        .context("Failed to save the response for the idempotency key")?;
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the transaction to save the response")?;
+
+    let http_response = response_head.set_body(body).map_into_boxed_body();
+
+    Ok(http_response)
+}
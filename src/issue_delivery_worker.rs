@@ -0,0 +1,396 @@
+use crate::deliveries::{DeliveryAttempt, DeliveryStatus, record_delivery_attempt};
+use crate::domain::SubscriberEmail;
+use crate::email_client::{EmailClient, OutgoingEmail, SendEmailError};
+use anyhow::Context;
+use sqlx::{Executor, PgPool};
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::Span;
+use uuid::Uuid;
+
+pub enum ExecutionOutcome {
+    TaskCompleted,
+    EmptyQueue,
+}
+
+pub async fn run_worker_until_stopped(
+    db_connection_pool: PgPool,
+    email_client: Arc<EmailClient>,
+    application_base_url: String,
+) -> Result<(), anyhow::Error> {
+    loop {
+        match try_execute_task(&db_connection_pool, &email_client, &application_base_url).await {
+            Ok(ExecutionOutcome::EmptyQueue) => tokio::time::sleep(Duration::from_secs(10)).await,
+            Ok(ExecutionOutcome::TaskCompleted) => {}
+            Err(_) => tokio::time::sleep(Duration::from_secs(1)).await,
+        }
+    }
+}
+
+#[tracing::instrument(
+    name = "try_execute_task",
+    skip_all,
+    fields(n_tasks=tracing::field::Empty),
+    err
+)]
+pub async fn try_execute_task(
+    db_connection_pool: &PgPool,
+    email_client: &EmailClient,
+    application_base_url: &str,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    // Without a batch endpoint, tasks keep being delivered one at a time.
+    let batch_size = email_client.max_batch_size().unwrap_or(1);
+    let tasks = claim_tasks(db_connection_pool, batch_size).await?;
+
+    if tasks.is_empty() {
+        return Ok(ExecutionOutcome::EmptyQueue);
+    }
+
+    Span::current().record("n_tasks", tasks.len());
+
+    let subscriber_emails: Vec<_> = tasks
+        .iter()
+        .map(|task| task.subscriber_email.clone())
+        .collect();
+    let unsubscribe_tokens = get_unsubscribe_tokens(db_connection_pool, &subscriber_emails).await?;
+    let mut issues = HashMap::new();
+    let mut recipients = Vec::new();
+    let mut preparations = Vec::with_capacity(tasks.len());
+
+    for task in &tasks {
+        let unsubscribe_token = unsubscribe_tokens.get(&task.subscriber_email).cloned();
+
+        match (
+            SubscriberEmail::parse(task.subscriber_email.clone()),
+            unsubscribe_token,
+        ) {
+            (Ok(email), Some(unsubscribe_token)) => {
+                if let Entry::Vacant(entry) = issues.entry(task.newsletter_issue_id) {
+                    entry.insert(get_issue(db_connection_pool, task.newsletter_issue_id).await?);
+                }
+
+                let unsubscribe_link = format!(
+                    "<{}/subscriptions/unsubscribe?unsubscribe_token={}>",
+                    application_base_url, unsubscribe_token
+                );
+                recipients.push((task.newsletter_issue_id, email, unsubscribe_link));
+                preparations.push(None);
+            }
+            (Ok(_), None) => {
+                tracing::info!(
+                    subscriber_email = %task.subscriber_email,
+                    "Skipping a subscriber that is no longer confirmed",
+                );
+                preparations.push(Some(TaskOutcome {
+                    status: DeliveryStatus::Skipped,
+                    attempts: 0,
+                    error: None,
+                    provider_message_id: None,
+                }));
+            }
+            (Err(error), _) => {
+                tracing::error!(
+                    subscriber_email = %task.subscriber_email,
+                    error.message = %error,
+                    "Skipping a confirmed subscriber. Their stored contact details are invalid",
+                );
+                preparations.push(Some(TaskOutcome {
+                    status: DeliveryStatus::Failed,
+                    attempts: 0,
+                    error: Some(error),
+                    provider_message_id: None,
+                }));
+            }
+        }
+    }
+
+    let headers: Vec<_> = recipients
+        .iter()
+        .map(|(_, _, unsubscribe_link)| {
+            [
+                ("List-Unsubscribe", unsubscribe_link.as_str()),
+                ("List-Unsubscribe-Post", "List-Unsubscribe=One-Click"),
+            ]
+        })
+        .collect();
+    let emails: Vec<_> = recipients
+        .iter()
+        .zip(&headers)
+        .map(|((newsletter_issue_id, email, _), headers)| {
+            let issue = &issues[newsletter_issue_id];
+
+            OutgoingEmail {
+                to: email,
+                subject: &issue.title,
+                html_content: &issue.html_content,
+                text_content: &issue.text_content,
+                headers,
+            }
+        })
+        .collect();
+
+    // Outcomes come back in the order the emails were given, which is the order of the tasks.
+    let mut sent_outcomes = send_emails(email_client, &emails).await.into_iter();
+    let mut n_unrecorded = 0;
+
+    for (task, preparation) in tasks.iter().zip(preparations) {
+        let outcome = match preparation {
+            Some(outcome) => outcome,
+            None => {
+                let (attempts, result) = sent_outcomes
+                    .next()
+                    .expect("There is an outcome for every email sent");
+
+                match result {
+                    Ok(provider_message_id) => TaskOutcome {
+                        status: DeliveryStatus::Sent,
+                        attempts,
+                        error: None,
+                        provider_message_id,
+                    },
+                    Err(error) => {
+                        tracing::error!(
+                            subscriber_email = %task.subscriber_email,
+                            error.message = %error,
+                            "Failed to deliver issue to a confirmed subscriber. Skipping.",
+                        );
+
+                        TaskOutcome {
+                            status: DeliveryStatus::Failed,
+                            attempts,
+                            error: Some(error),
+                            provider_message_id: None,
+                        }
+                    }
+                }
+            }
+        };
+
+        let attempt = DeliveryAttempt {
+            status: outcome.status,
+            attempts: outcome.attempts,
+            error: outcome.error.as_deref(),
+            provider_message_id: outcome.provider_message_id.as_deref(),
+        };
+        // Each outcome is stored on its own, so that a failure to store one does not get the
+        // emails that were already recorded sent again.
+        if let Err(error) = complete_task(db_connection_pool, task, &attempt).await {
+            tracing::error!(
+                error.cause_chain = ?error,
+                subscriber_email = %task.subscriber_email,
+                "Failed to record the outcome of a delivery. It will be retried once its claim expires",
+            );
+            n_unrecorded += 1;
+        }
+    }
+
+    if n_unrecorded > 0 {
+        anyhow::bail!(
+            "Failed to record the outcome of {} out of {} deliveries",
+            n_unrecorded,
+            tasks.len()
+        );
+    }
+
+    Ok(ExecutionOutcome::TaskCompleted)
+}
+
+struct TaskOutcome {
+    status: DeliveryStatus,
+    attempts: u32,
+    error: Option<String>,
+    provider_message_id: Option<String>,
+}
+
+// Pairs each email with the number of attempts it took and the provider's message id, or a
+// description of the error that stopped it.
+async fn send_emails(
+    email_client: &EmailClient,
+    emails: &[OutgoingEmail<'_>],
+) -> Vec<(u32, Result<Option<String>, String>)> {
+    if emails.is_empty() {
+        return Vec::new();
+    }
+
+    if email_client.max_batch_size().is_none() {
+        let mut outcomes = Vec::with_capacity(emails.len());
+
+        for email in emails {
+            let outcome = email_client
+                .send_email_with_headers(
+                    email.to,
+                    email.subject,
+                    email.html_content,
+                    email.text_content,
+                    email.headers,
+                )
+                .await;
+
+            outcomes.push((outcome.attempts, outcome.result.map_err(describe_error)));
+        }
+
+        return outcomes;
+    }
+
+    let mut outcomes = Vec::with_capacity(emails.len());
+
+    for batch in email_client.split_into_batches(emails) {
+        let outcome = email_client.send_batch(batch).await;
+
+        match outcome.result {
+            Ok(batch_outcomes) => outcomes.extend(
+                batch_outcomes
+                    .into_iter()
+                    .map(|outcome| (outcome.attempts, outcome.result.map_err(describe_error))),
+            ),
+            Err(error) => {
+                let error = describe_error(error);
+
+                outcomes.extend(batch.iter().map(|_| (outcome.attempts, Err(error.clone()))));
+            }
+        }
+    }
+
+    outcomes
+}
+
+fn describe_error(error: SendEmailError) -> String {
+    format!("{:#}", anyhow::Error::new(error))
+}
+
+// Claimed tasks are left alone by other workers until the claim expires, which leaves more than
+// enough time to send a batch and retry it. Tasks claimed by a worker that stopped midway are
+// then picked up again.
+const CLAIM_DURATION: Duration = Duration::from_secs(15 * 60);
+
+#[derive(Debug)]
+struct DeliveryTask {
+    newsletter_issue_id: Uuid,
+    subscriber_email: String,
+}
+
+// The claim is committed straight away, so that no transaction stays open while emails are sent.
+#[tracing::instrument(name = "claim_tasks", skip(db_connection_pool))]
+async fn claim_tasks(
+    db_connection_pool: &PgPool,
+    limit: usize,
+) -> Result<Vec<DeliveryTask>, anyhow::Error> {
+    let tasks = sqlx::query_as!(
+        DeliveryTask,
+        r#"
+        UPDATE issue_delivery_queue
+        SET claimed_until = now() + make_interval(secs => $2)
+        WHERE (newsletter_issue_id, subscriber_email) IN (
+            SELECT newsletter_issue_id, subscriber_email
+            FROM issue_delivery_queue
+            WHERE claimed_until IS NULL OR claimed_until < now()
+            FOR UPDATE
+            SKIP LOCKED
+            LIMIT $1
+        )
+        RETURNING newsletter_issue_id, subscriber_email
+        "#,
+        limit as i64,
+        CLAIM_DURATION.as_secs_f64()
+    )
+    .fetch_all(db_connection_pool)
+    .await
+    .context("Failed to claim delivery tasks")?;
+
+    Ok(tasks)
+}
+
+#[tracing::instrument(name = "complete_task", skip(db_connection_pool, attempt))]
+async fn complete_task(
+    db_connection_pool: &PgPool,
+    task: &DeliveryTask,
+    attempt: &DeliveryAttempt<'_>,
+) -> Result<(), anyhow::Error> {
+    let mut transaction = db_connection_pool
+        .begin()
+        .await
+        .context("Failed to get a database connection from the pool")?;
+
+    record_delivery_attempt(
+        &mut *transaction,
+        task.newsletter_issue_id,
+        &task.subscriber_email,
+        attempt,
+    )
+    .await
+    .context("Failed to record the outcome of a delivery")?;
+
+    let query = sqlx::query!(
+        r#"
+        DELETE FROM issue_delivery_queue
+        WHERE newsletter_issue_id = $1 AND subscriber_email = $2
+        "#,
+        task.newsletter_issue_id,
+        task.subscriber_email
+    );
+
+    transaction
+        .execute(query)
+        .await
+        .context("Failed to delete a completed delivery task")?;
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the transaction to complete a delivery task")?;
+
+    Ok(())
+}
+
+// Subscribers that are no longer confirmed are left out.
+#[tracing::instrument(name = "get_unsubscribe_tokens", skip_all)]
+async fn get_unsubscribe_tokens(
+    db_connection_pool: &PgPool,
+    subscriber_emails: &[String],
+) -> Result<HashMap<String, String>, anyhow::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT email, unsubscribe_token
+        FROM subscriptions
+        WHERE email = ANY($1) AND status = 'confirmed'
+        "#,
+        subscriber_emails
+    )
+    .fetch_all(db_connection_pool)
+    .await
+    .context("Failed to retrieve the subscribers' unsubscribe tokens")?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| (row.email, row.unsubscribe_token))
+        .collect())
+}
+
+struct NewsletterIssue {
+    title: String,
+    text_content: String,
+    html_content: String,
+}
+
+#[tracing::instrument(name = "get_issue", skip(db_connection_pool))]
+async fn get_issue(
+    db_connection_pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<NewsletterIssue, anyhow::Error> {
+    let issue = sqlx::query_as!(
+        NewsletterIssue,
+        r#"
+        SELECT title, text_content, html_content
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_one(db_connection_pool)
+    .await
+    .context("Failed to retrieve the newsletter issue")?;
+
+    Ok(issue)
+}
@@ -0,0 +1,22 @@
+use crate::newsletter_issues::promote_due_issues;
+use sqlx::PgPool;
+use std::time::Duration;
+
+// Scheduled issues go out within one tick of their time, which is precise enough for a newsletter.
+const TICK: Duration = Duration::from_secs(10);
+
+pub async fn run_scheduler_until_stopped(db_connection_pool: PgPool) -> Result<(), anyhow::Error> {
+    loop {
+        match promote_due_issues(&db_connection_pool).await {
+            Ok(0) => {}
+            Ok(promoted) => tracing::info!(promoted, "Scheduled newsletter issues are now sending"),
+            Err(error) => tracing::error!(
+                error.cause_chain = ?error,
+                error.message = %error,
+                "Failed to promote the scheduled newsletter issues",
+            ),
+        }
+
+        tokio::time::sleep(TICK).await;
+    }
+}
@@ -1,10 +1,17 @@
 pub mod application;
+pub mod audit;
 pub mod configuration;
+pub mod csrf;
 pub mod routes;
 pub mod telemetry;
 
+pub mod deliveries;
 pub mod domain;
 pub mod email_client;
+pub mod idempotency;
+pub mod issue_delivery_worker;
+pub mod issue_scheduler;
+pub mod newsletter_issues;
 
 pub mod utils;
 
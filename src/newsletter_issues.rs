@@ -0,0 +1,540 @@
+use crate::audit::{AuditAction, AuditEvent, AuditOutcome, record_audit_event};
+use crate::deliveries::create_pending_deliveries;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IssueStatus {
+    Draft,
+    Scheduled,
+    Sending,
+}
+
+impl IssueStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IssueStatus::Draft => "draft",
+            IssueStatus::Scheduled => "scheduled",
+            IssueStatus::Sending => "sending",
+        }
+    }
+}
+
+impl TryFrom<&str> for IssueStatus {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "draft" => Ok(IssueStatus::Draft),
+            "scheduled" => Ok(IssueStatus::Scheduled),
+            "sending" => Ok(IssueStatus::Sending),
+            other => Err(format!("{} is not a supported issue status", other)),
+        }
+    }
+}
+
+pub struct IssueContent<'a> {
+    pub title: &'a str,
+    pub text_content: &'a str,
+    pub html_content: &'a str,
+}
+
+// Whoever publishes or schedules an issue is recorded in the audit log along with it. A scheduled
+// issue keeps track of who scheduled it, so that they are credited with its publication as well.
+pub struct Publisher<'a> {
+    pub user_id: Option<Uuid>,
+    pub username: &'a str,
+    pub ip_address: Option<&'a str>,
+}
+
+impl Publisher<'static> {
+    pub const SCHEDULER: Publisher<'static> = Publisher {
+        user_id: None,
+        username: "scheduler",
+        ip_address: None,
+    };
+}
+
+pub struct NewsletterIssue {
+    pub newsletter_issue_id: Uuid,
+    pub title: String,
+    pub text_content: String,
+    pub html_content: String,
+    pub status: IssueStatus,
+    pub created_at: DateTime<Utc>,
+    pub scheduled_at: Option<DateTime<Utc>>,
+    pub published_at: Option<DateTime<Utc>>,
+}
+
+pub struct NewsletterIssueSummary {
+    pub newsletter_issue_id: Uuid,
+    pub title: String,
+    pub status: String,
+    pub created_at: DateTime<Utc>,
+    pub scheduled_at: Option<DateTime<Utc>>,
+    pub published_at: Option<DateTime<Utc>>,
+}
+
+#[tracing::instrument(name = "create_draft", skip_all)]
+pub async fn create_draft(
+    transaction: &mut Transaction<'_, Postgres>,
+    content: &IssueContent<'_>,
+) -> Result<Uuid, anyhow::Error> {
+    insert_newsletter_issue(transaction, content, IssueStatus::Draft, None, None)
+        .await
+        .context("Failed to store the draft")
+}
+
+// Only drafts can be edited: an issue that is being sent must match what subscribers receive.
+#[tracing::instrument(name = "update_draft", skip(db_connection_pool, content))]
+pub async fn update_draft(
+    db_connection_pool: &PgPool,
+    newsletter_issue_id: Uuid,
+    content: &IssueContent<'_>,
+) -> Result<bool, anyhow::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET title = $2, text_content = $3, html_content = $4
+        WHERE newsletter_issue_id = $1 AND status = 'draft'
+        "#,
+        newsletter_issue_id,
+        content.title,
+        content.text_content,
+        content.html_content
+    )
+    .execute(db_connection_pool)
+    .await
+    .context("Failed to update the draft")?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+#[tracing::instrument(name = "get_newsletter_issue", skip(db_connection_pool))]
+pub async fn get_newsletter_issue(
+    db_connection_pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<Option<NewsletterIssue>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT newsletter_issue_id, title, text_content, html_content, status, created_at,
+            scheduled_at, published_at
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_optional(db_connection_pool)
+    .await
+    .context("Failed to retrieve the newsletter issue")?;
+
+    row.map(|row| {
+        Ok(NewsletterIssue {
+            newsletter_issue_id: row.newsletter_issue_id,
+            title: row.title,
+            text_content: row.text_content,
+            html_content: row.html_content,
+            status: IssueStatus::try_from(row.status.as_str())
+                .map_err(|error| anyhow::anyhow!(error))?,
+            created_at: row.created_at,
+            scheduled_at: row.scheduled_at,
+            published_at: row.published_at,
+        })
+    })
+    .transpose()
+}
+
+#[tracing::instrument(name = "list_newsletter_issues", skip(db_connection_pool))]
+pub async fn list_newsletter_issues(
+    db_connection_pool: &PgPool,
+) -> Result<Vec<NewsletterIssueSummary>, anyhow::Error> {
+    let issues = sqlx::query_as!(
+        NewsletterIssueSummary,
+        r#"
+        SELECT newsletter_issue_id, title, status, created_at, scheduled_at, published_at
+        FROM newsletter_issues
+        ORDER BY created_at DESC
+        "#
+    )
+    .fetch_all(db_connection_pool)
+    .await
+    .context("Failed to list the newsletter issues")?;
+
+    Ok(issues)
+}
+
+// Used by the API, which publishes an issue as soon as it is submitted.
+#[tracing::instrument(name = "publish_issue", skip(transaction, content, publisher))]
+pub async fn publish_issue(
+    transaction: &mut Transaction<'_, Postgres>,
+    content: &IssueContent<'_>,
+    publisher: &Publisher<'_>,
+) -> Result<Uuid, anyhow::Error> {
+    let newsletter_issue_id =
+        insert_newsletter_issue(transaction, content, IssueStatus::Sending, None, None)
+            .await
+            .context("Failed to store newsletter issue details")?;
+
+    start_delivery(transaction, newsletter_issue_id, content.title, publisher).await?;
+
+    Ok(newsletter_issue_id)
+}
+
+#[tracing::instrument(name = "schedule_new_issue", skip(transaction, content, scheduler))]
+pub async fn schedule_new_issue(
+    transaction: &mut Transaction<'_, Postgres>,
+    content: &IssueContent<'_>,
+    scheduled_at: DateTime<Utc>,
+    scheduler: &Publisher<'_>,
+) -> Result<Uuid, anyhow::Error> {
+    let newsletter_issue_id = insert_newsletter_issue(
+        transaction,
+        content,
+        IssueStatus::Scheduled,
+        Some(scheduled_at),
+        Some(scheduler),
+    )
+    .await
+    .context("Failed to store the scheduled newsletter issue")?;
+
+    record_issue_event(
+        transaction,
+        AuditAction::ScheduleNewsletter,
+        newsletter_issue_id,
+        content.title,
+        scheduler,
+    )
+    .await?;
+
+    Ok(newsletter_issue_id)
+}
+
+// Schedules a draft, or moves an issue that is already scheduled to another time.
+#[tracing::instrument(name = "schedule_issue", skip(db_connection_pool, scheduler))]
+pub async fn schedule_issue(
+    db_connection_pool: &PgPool,
+    newsletter_issue_id: Uuid,
+    scheduled_at: DateTime<Utc>,
+    scheduler: &Publisher<'_>,
+) -> Result<bool, anyhow::Error> {
+    let mut transaction = db_connection_pool
+        .begin()
+        .await
+        .context("Failed to get a database connection from the pool")?;
+
+    // The row stays locked until the end of the transaction, so a publication that commits in the
+    // meantime is seen here rather than overwritten.
+    let issue = sqlx::query!(
+        r#"
+        SELECT title, status
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1
+        FOR UPDATE
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to lock the newsletter issue")?;
+
+    let Some(issue) = issue.filter(|issue| {
+        issue.status == IssueStatus::Draft.as_str()
+            || issue.status == IssueStatus::Scheduled.as_str()
+    }) else {
+        return Ok(false);
+    };
+
+    sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET status = 'scheduled', scheduled_at = $2, scheduled_by_user_id = $3, scheduled_by = $4
+        WHERE newsletter_issue_id = $1 AND status IN ('draft', 'scheduled')
+        "#,
+        newsletter_issue_id,
+        scheduled_at,
+        scheduler.user_id,
+        scheduler.username
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to schedule the newsletter issue")?;
+
+    let action = if issue.status == IssueStatus::Scheduled.as_str() {
+        AuditAction::RescheduleNewsletter
+    } else {
+        AuditAction::ScheduleNewsletter
+    };
+    record_issue_event(
+        &mut transaction,
+        action,
+        newsletter_issue_id,
+        &issue.title,
+        scheduler,
+    )
+    .await?;
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the transaction to schedule a newsletter issue")?;
+
+    Ok(true)
+}
+
+// A cancelled issue goes back to being a draft, so that it can be edited and scheduled again.
+#[tracing::instrument(name = "cancel_scheduled_issue", skip(db_connection_pool, canceller))]
+pub async fn cancel_scheduled_issue(
+    db_connection_pool: &PgPool,
+    newsletter_issue_id: Uuid,
+    canceller: &Publisher<'_>,
+) -> Result<bool, anyhow::Error> {
+    let mut transaction = db_connection_pool
+        .begin()
+        .await
+        .context("Failed to get a database connection from the pool")?;
+
+    let row = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET status = 'draft', scheduled_at = NULL, scheduled_by_user_id = NULL, scheduled_by = NULL
+        WHERE newsletter_issue_id = $1 AND status = 'scheduled'
+        RETURNING title
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to cancel the scheduled newsletter issue")?;
+
+    let Some(row) = row else {
+        return Ok(false);
+    };
+
+    record_issue_event(
+        &mut transaction,
+        AuditAction::CancelNewsletterSchedule,
+        newsletter_issue_id,
+        &row.title,
+        canceller,
+    )
+    .await?;
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the transaction to cancel a newsletter schedule")?;
+
+    Ok(true)
+}
+
+// Due issues are locked while they are promoted, so that several instances of the scheduler
+// never enqueue the same issue twice.
+#[tracing::instrument(name = "promote_due_issues", skip(db_connection_pool))]
+pub async fn promote_due_issues(db_connection_pool: &PgPool) -> Result<usize, anyhow::Error> {
+    let mut transaction = db_connection_pool
+        .begin()
+        .await
+        .context("Failed to get a database connection from the pool")?;
+
+    let due_issues = sqlx::query!(
+        r#"
+        SELECT newsletter_issue_id, title, scheduled_by_user_id, scheduled_by
+        FROM newsletter_issues
+        WHERE status = 'scheduled' AND scheduled_at <= now()
+        FOR UPDATE SKIP LOCKED
+        "#
+    )
+    .fetch_all(&mut *transaction)
+    .await
+    .context("Failed to retrieve the due newsletter issues")?;
+
+    for issue in &due_issues {
+        sqlx::query!(
+            r#"
+            UPDATE newsletter_issues
+            SET status = 'sending', published_at = now()
+            WHERE newsletter_issue_id = $1
+            "#,
+            issue.newsletter_issue_id
+        )
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to mark the scheduled issue as published")?;
+
+        // Issues scheduled before their scheduler was recorded are credited to the scheduler itself.
+        let publisher = match &issue.scheduled_by {
+            Some(scheduled_by) => Publisher {
+                user_id: issue.scheduled_by_user_id,
+                username: scheduled_by,
+                ip_address: None,
+            },
+            None => Publisher::SCHEDULER,
+        };
+
+        start_delivery(
+            &mut transaction,
+            issue.newsletter_issue_id,
+            &issue.title,
+            &publisher,
+        )
+        .await?;
+    }
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the transaction to promote due issues")?;
+
+    Ok(due_issues.len())
+}
+
+// The status check makes publishing happen at most once, however many times it is requested. A
+// scheduled issue can also be published straight away.
+#[tracing::instrument(name = "publish_draft", skip(transaction, publisher))]
+pub async fn publish_draft(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+    publisher: &Publisher<'_>,
+) -> Result<bool, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET status = 'sending', published_at = now()
+        WHERE newsletter_issue_id = $1 AND status IN ('draft', 'scheduled')
+        RETURNING title
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_optional(&mut **transaction)
+    .await
+    .context("Failed to mark the draft as published")?;
+
+    let Some(row) = row else {
+        return Ok(false);
+    };
+
+    start_delivery(transaction, newsletter_issue_id, &row.title, publisher).await?;
+
+    Ok(true)
+}
+
+async fn start_delivery(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+    title: &str,
+    publisher: &Publisher<'_>,
+) -> Result<(), anyhow::Error> {
+    enqueue_delivery_tasks(transaction, newsletter_issue_id)
+        .await
+        .context("Failed to enqueue delivery tasks")?;
+    create_pending_deliveries(transaction, newsletter_issue_id)
+        .await
+        .context("Failed to track the deliveries of the issue")?;
+
+    record_issue_event(
+        transaction,
+        AuditAction::PublishNewsletter,
+        newsletter_issue_id,
+        title,
+        publisher,
+    )
+    .await
+}
+
+async fn record_issue_event(
+    transaction: &mut Transaction<'_, Postgres>,
+    action: AuditAction,
+    newsletter_issue_id: Uuid,
+    title: &str,
+    actor: &Publisher<'_>,
+) -> Result<(), anyhow::Error> {
+    let target = format!("{} ({})", title, newsletter_issue_id);
+    let event = AuditEvent {
+        actor_user_id: actor.user_id,
+        actor: actor.username,
+        action,
+        target: Some(&target),
+        ip_address: actor.ip_address,
+        outcome: AuditOutcome::Success,
+    };
+
+    record_audit_event(&mut **transaction, &event).await
+}
+
+#[tracing::instrument(name = "insert_newsletter_issue", skip_all)]
+async fn insert_newsletter_issue(
+    transaction: &mut Transaction<'_, Postgres>,
+    content: &IssueContent<'_>,
+    status: IssueStatus,
+    scheduled_at: Option<DateTime<Utc>>,
+    scheduler: Option<&Publisher<'_>>,
+) -> Result<Uuid, sqlx::Error> {
+    let newsletter_issue_id = Uuid::new_v4();
+    let now = Utc::now();
+    let published_at = (status == IssueStatus::Sending).then_some(now);
+
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO newsletter_issues (
+            newsletter_issue_id, title, text_content, html_content, status, created_at,
+            scheduled_at, published_at, scheduled_by_user_id, scheduled_by
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        "#,
+        newsletter_issue_id,
+        content.title,
+        content.text_content,
+        content.html_content,
+        status.as_str(),
+        now,
+        scheduled_at,
+        published_at,
+        scheduler.and_then(|scheduler| scheduler.user_id),
+        scheduler.map(|scheduler| scheduler.username)
+    );
+
+    transaction.execute(query).await?;
+
+    Ok(newsletter_issue_id)
+}
+
+#[tracing::instrument(name = "enqueue_delivery_tasks", skip(transaction))]
+async fn enqueue_delivery_tasks(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO issue_delivery_queue (newsletter_issue_id, subscriber_email)
+        SELECT $1, email
+        FROM subscriptions
+        WHERE status = 'confirmed'
+        "#,
+        newsletter_issue_id,
+    );
+
+    transaction.execute(query).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::newsletter_issues::IssueStatus;
+    use claims::assert_err;
+
+    #[test]
+    fn then_statuses_should_round_trip_through_their_names() {
+        for status in [
+            IssueStatus::Draft,
+            IssueStatus::Scheduled,
+            IssueStatus::Sending,
+        ] {
+            assert_eq!(IssueStatus::try_from(status.as_str()), Ok(status));
+        }
+
+        assert_err!(IssueStatus::try_from("archived"));
+    }
+}
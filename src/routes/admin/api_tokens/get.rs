@@ -0,0 +1,116 @@
+use crate::authentication::{ApiTokenScope, UserId, list_api_tokens};
+use crate::csrf::csrf_token_field;
+use crate::session_state::TypedSession;
+use actix_web::error::ErrorInternalServerError;
+use actix_web::http::header::ContentType;
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::IncomingFlashMessages;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+pub async fn api_tokens_page(
+    flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+    user_id: web::ReqData<UserId>,
+    db_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_token_field = csrf_token_field(&session)?;
+    let mut messages_html = String::new();
+
+    for flash_message in flash_messages.iter() {
+        writeln!(messages_html, "<p><i>{}</i></p>", flash_message.content()).unwrap();
+    }
+
+    let api_tokens = list_api_tokens(&db_pool, **user_id)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    let mut api_tokens_html = String::new();
+
+    for api_token in api_tokens {
+        writeln!(
+            api_tokens_html,
+            r#"<tr>
+                <td>{name}</td>
+                <td>{scopes}</td>
+                <td>{created_at}</td>
+                <td>{expires_at}</td>
+                <td>{last_used_at}</td>
+                <td>
+                   <form action="/admin/api-tokens/{api_token_id}/revoke" method="post">
+                      {csrf_token_field}
+                      <button type="submit">Revoke</button>
+                   </form>
+                </td>
+             </tr>"#,
+            name = api_token.name,
+            scopes = api_token.scopes.join(", "),
+            created_at = api_token.created_at.format("%Y-%m-%d %H:%M UTC"),
+            expires_at = api_token
+                .expires_at
+                .map(|expires_at| expires_at.format("%Y-%m-%d %H:%M UTC").to_string())
+                .unwrap_or_else(|| "Never".into()),
+            last_used_at = api_token
+                .last_used_at
+                .map(|last_used_at| last_used_at.format("%Y-%m-%d %H:%M UTC").to_string())
+                .unwrap_or_else(|| "Never".into()),
+            api_token_id = api_token.api_token_id,
+        )
+        .unwrap();
+    }
+
+    let mut scopes_html = String::new();
+
+    for scope in ApiTokenScope::ALL {
+        writeln!(
+            scopes_html,
+            r#"<label><input type="checkbox" name="scope" value="{scope}" /> {scope}</label><br />"#,
+            scope = scope.as_str()
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"
+        <!DOCTYPE html>
+        <html lang="en">
+           <head>
+              <meta http-equiv="content-type" content="text/html; charset=utf-8" />
+              <title>API tokens</title>
+           </head>
+           <body>
+              {messages_html}
+              <table>
+                 <tr>
+                    <th>Name</th>
+                    <th>Scopes</th>
+                    <th>Created</th>
+                    <th>Expires</th>
+                    <th>Last used</th>
+                    <th></th>
+                 </tr>
+                 {api_tokens_html}
+              </table>
+              <form action="/admin/api-tokens" method="post">
+                 {csrf_token_field}
+                 <label
+                    >Name
+                    <input type="text" placeholder="Enter a name for the token" name="name" />
+                 </label>
+                 <br />
+                 {scopes_html}
+                 <label
+                    >Expires in (days)
+                    <input type="number" min="1" placeholder="Never" name="expires_in_days" />
+                 </label>
+                 <br />
+                 <button type="submit">Create API token</button>
+              </form>
+              <p><a href="/admin/dashboard">&lt;- Back</a></p>
+           </body>
+        </html>
+        "#
+        )))
+}
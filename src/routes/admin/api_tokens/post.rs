@@ -0,0 +1,185 @@
+use crate::authentication::{ApiTokenScope, UserId, create_api_token, revoke_api_token};
+use crate::utils::see_other;
+use actix_web::error::ErrorInternalServerError;
+use actix_web::http::header::ContentType;
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use chrono::Utc;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+const MAX_NAME_LENGTH: usize = 64;
+
+#[derive(Debug)]
+struct NewApiToken {
+    name: String,
+    scopes: Vec<ApiTokenScope>,
+    expires_in_days: Option<i64>,
+}
+
+// Checkboxes repeat the `scope` field, so the form is read as a list of key-value pairs.
+impl TryFrom<Vec<(String, String)>> for NewApiToken {
+    type Error = String;
+
+    fn try_from(fields: Vec<(String, String)>) -> Result<Self, Self::Error> {
+        let mut name = String::new();
+        let mut scopes = Vec::new();
+        let mut expires_in_days = None;
+
+        for (key, value) in fields {
+            match key.as_str() {
+                "name" => name = value.trim().to_owned(),
+                "scope" => scopes.push(ApiTokenScope::try_from(value.as_str())?),
+                "expires_in_days" if !value.trim().is_empty() => {
+                    let days = value
+                        .trim()
+                        .parse::<i64>()
+                        .ok()
+                        .filter(|days| (1..=3650).contains(days))
+                        .ok_or("The expiry must be a number of days between 1 and 3650.")?;
+
+                    expires_in_days = Some(days);
+                }
+                _ => {}
+            }
+        }
+
+        let is_valid_name = name.chars().count() <= MAX_NAME_LENGTH
+            && !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_alphanumeric() || matches!(c, ' ' | '-' | '_' | '.'));
+
+        if !is_valid_name {
+            return Err(format!(
+                "The name must be 1 to {} letters, digits, spaces, dashes, underscores or dots.",
+                MAX_NAME_LENGTH
+            ));
+        }
+
+        if scopes.is_empty() {
+            return Err("Select at least one scope.".into());
+        }
+
+        Ok(Self {
+            name,
+            scopes,
+            expires_in_days,
+        })
+    }
+}
+
+#[tracing::instrument(
+    name = "create_api_token",
+    skip(form, user_id, db_pool),
+    fields(user_id=%*user_id)
+)]
+pub async fn create_new_api_token(
+    form: web::Form<Vec<(String, String)>>,
+    user_id: web::ReqData<UserId>,
+    db_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let new_api_token = match NewApiToken::try_from(form.0) {
+        Ok(new_api_token) => new_api_token,
+        Err(error) => {
+            FlashMessage::error(error).send();
+
+            return Ok(see_other("/admin/api-tokens"));
+        }
+    };
+
+    let expires_at = new_api_token
+        .expires_in_days
+        .map(|days| Utc::now() + chrono::Duration::days(days));
+
+    let token = create_api_token(
+        &db_pool,
+        **user_id,
+        &new_api_token.name,
+        &new_api_token.scopes,
+        expires_at,
+    )
+    .await
+    .map_err(ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"
+        <!DOCTYPE html>
+        <html lang="en">
+           <head>
+              <meta http-equiv="content-type" content="text/html; charset=utf-8" />
+              <title>API tokens</title>
+           </head>
+           <body>
+              <p>The API token {name} has been created.</p>
+              <p>Copy it now, it will not be shown again:</p>
+              <p><code>{token}</code></p>
+              <p><a href="/admin/api-tokens">&lt;- Back</a></p>
+           </body>
+        </html>
+        "#,
+            name = new_api_token.name
+        )))
+}
+
+#[tracing::instrument(
+    name = "revoke_api_token",
+    skip(api_token_id, user_id, db_pool),
+    fields(user_id=%*user_id)
+)]
+pub async fn revoke_existing_api_token(
+    api_token_id: web::Path<Uuid>,
+    user_id: web::ReqData<UserId>,
+    db_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    revoke_api_token(&db_pool, **user_id, api_token_id.into_inner())
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    FlashMessage::info("The API token has been revoked.").send();
+
+    Ok(see_other("/admin/api-tokens"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::authentication::ApiTokenScope;
+    use crate::routes::admin::api_tokens::post::NewApiToken;
+    use claims::{assert_err, assert_ok};
+
+    fn fields(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn given_repeated_scopes_then_it_should_collect_all_of_them() {
+        let new_api_token = assert_ok!(NewApiToken::try_from(fields(&[
+            ("name", "CI"),
+            ("scope", "newsletters:publish"),
+            ("scope", "subscribers:read"),
+            ("scope", "deliveries:read"),
+            ("expires_in_days", ""),
+        ])));
+
+        assert_eq!(new_api_token.scopes, ApiTokenScope::ALL);
+        assert_eq!(new_api_token.expires_in_days, None);
+    }
+
+    #[test]
+    fn given_no_scope_then_it_should_be_rejected() {
+        assert_err!(NewApiToken::try_from(fields(&[("name", "CI")])));
+    }
+
+    #[test]
+    fn given_a_name_with_markup_then_it_should_be_rejected() {
+        assert_err!(NewApiToken::try_from(fields(&[
+            ("name", "<script>"),
+            ("scope", "newsletters:publish"),
+        ])));
+    }
+}
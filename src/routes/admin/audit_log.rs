@@ -0,0 +1,224 @@
+use crate::audit::{AuditAction, AuditEventFilter, AuditOutcome, list_audit_events};
+use crate::utils::escape_html;
+use actix_web::error::{ErrorBadRequest, ErrorInternalServerError};
+use actix_web::http::header::ContentType;
+use actix_web::{HttpResponse, web};
+use sqlx::PgPool;
+use std::fmt::Write;
+
+const PAGE_SIZE: i64 = 50;
+
+// Filters come from a GET form, which submits empty strings for the fields left blank.
+#[derive(serde::Deserialize)]
+pub struct AuditLogQuery {
+    action: Option<String>,
+    outcome: Option<String>,
+    actor: Option<String>,
+    page: Option<i64>,
+}
+
+impl TryFrom<&AuditLogQuery> for AuditEventFilter {
+    type Error = String;
+
+    fn try_from(query: &AuditLogQuery) -> Result<Self, Self::Error> {
+        let non_empty = |value: &Option<String>| {
+            value
+                .as_deref()
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(str::to_owned)
+        };
+
+        Ok(Self {
+            action: non_empty(&query.action)
+                .map(|action| AuditAction::try_from(action.as_str()))
+                .transpose()?,
+            outcome: non_empty(&query.outcome)
+                .map(|outcome| AuditOutcome::try_from(outcome.as_str()))
+                .transpose()?,
+            actor: non_empty(&query.actor),
+        })
+    }
+}
+
+pub async fn audit_log_page(
+    query: web::Query<AuditLogQuery>,
+    db_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let filter = AuditEventFilter::try_from(&query.0).map_err(ErrorBadRequest)?;
+    let page = query.page.unwrap_or(1).max(1);
+    let offset = (page - 1)
+        .checked_mul(PAGE_SIZE)
+        .ok_or_else(|| ErrorBadRequest("The page number is too large"))?;
+
+    // One extra row is fetched to know whether there is a next page.
+    let mut events = list_audit_events(&db_pool, &filter, PAGE_SIZE + 1, offset)
+        .await
+        .map_err(ErrorInternalServerError)?;
+    let has_next_page = events.len() as i64 > PAGE_SIZE;
+    events.truncate(PAGE_SIZE as usize);
+
+    let mut events_html = String::new();
+
+    for event in events {
+        writeln!(
+            events_html,
+            r#"<tr>
+                <td>{occurred_at}</td>
+                <td>{actor}</td>
+                <td>{action}</td>
+                <td>{target}</td>
+                <td>{ip_address}</td>
+                <td>{outcome}</td>
+             </tr>"#,
+            occurred_at = event.occurred_at.format("%Y-%m-%d %H:%M:%S UTC"),
+            actor = escape_html(&event.actor),
+            action = event.action,
+            target = escape_html(event.target.as_deref().unwrap_or("")),
+            ip_address = escape_html(event.ip_address.as_deref().unwrap_or("Unknown")),
+            outcome = event.outcome,
+        )
+        .unwrap();
+    }
+
+    let mut action_options_html = String::from(r#"<option value="">Any</option>"#);
+
+    for action in AuditAction::ALL {
+        writeln!(
+            action_options_html,
+            r#"<option value="{value}"{selected}>{value}</option>"#,
+            value = action.as_str(),
+            selected = selected(filter.action == Some(action)),
+        )
+        .unwrap();
+    }
+
+    let mut outcome_options_html = String::from(r#"<option value="">Any</option>"#);
+
+    for outcome in AuditOutcome::ALL {
+        writeln!(
+            outcome_options_html,
+            r#"<option value="{value}"{selected}>{value}</option>"#,
+            value = outcome.as_str(),
+            selected = selected(filter.outcome == Some(outcome)),
+        )
+        .unwrap();
+    }
+
+    let actor = escape_html(filter.actor.as_deref().unwrap_or(""));
+    let action = filter.action.map(|action| action.as_str()).unwrap_or("");
+    let outcome = filter.outcome.map(|outcome| outcome.as_str()).unwrap_or("");
+
+    // Pagination keeps the current filters by submitting them along as hidden fields.
+    let page_link = |target_page: i64, label: &str| {
+        format!(
+            r#"<form action="/admin/audit-log" method="get">
+                  <input type="hidden" name="action" value="{action}" />
+                  <input type="hidden" name="outcome" value="{outcome}" />
+                  <input type="hidden" name="actor" value="{actor}" />
+                  <input type="hidden" name="page" value="{target_page}" />
+                  <button type="submit">{label}</button>
+               </form>"#
+        )
+    };
+
+    let mut pagination_html = String::new();
+
+    if page > 1 {
+        pagination_html.push_str(&page_link(page - 1, "Previous page"));
+    }
+
+    if has_next_page {
+        pagination_html.push_str(&page_link(page + 1, "Next page"));
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"
+        <!DOCTYPE html>
+        <html lang="en">
+           <head>
+              <meta http-equiv="content-type" content="text/html; charset=utf-8" />
+              <title>Audit log</title>
+           </head>
+           <body>
+              <form action="/admin/audit-log" method="get">
+                 <label
+                    >Action
+                    <select name="action">{action_options_html}</select>
+                 </label>
+                 <label
+                    >Outcome
+                    <select name="outcome">{outcome_options_html}</select>
+                 </label>
+                 <label
+                    >Actor
+                    <input type="text" placeholder="Username" name="actor" value="{actor}" />
+                 </label>
+                 <button type="submit">Filter</button>
+              </form>
+              <table>
+                 <tr>
+                    <th>Time</th>
+                    <th>Actor</th>
+                    <th>Action</th>
+                    <th>Target</th>
+                    <th>IP address</th>
+                    <th>Outcome</th>
+                 </tr>
+                 {events_html}
+              </table>
+              <p>Page {page}</p>
+              {pagination_html}
+              <p><a href="/admin/dashboard">&lt;- Back</a></p>
+           </body>
+        </html>
+        "#
+        )))
+}
+
+fn selected(is_selected: bool) -> &'static str {
+    if is_selected { " selected" } else { "" }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audit::{AuditAction, AuditEventFilter, AuditOutcome};
+    use crate::routes::admin::audit_log::AuditLogQuery;
+    use claims::{assert_err, assert_ok};
+
+    fn query(action: &str, outcome: &str, actor: &str) -> AuditLogQuery {
+        AuditLogQuery {
+            action: Some(action.into()),
+            outcome: Some(outcome.into()),
+            actor: Some(actor.into()),
+            page: None,
+        }
+    }
+
+    #[test]
+    fn given_blank_fields_then_it_should_not_filter_on_them() {
+        let filter = assert_ok!(AuditEventFilter::try_from(&query("", "", "  ")));
+
+        assert_eq!(filter.action, None);
+        assert_eq!(filter.outcome, None);
+        assert_eq!(filter.actor, None);
+    }
+
+    #[test]
+    fn given_valid_fields_then_it_should_filter_on_them() {
+        let filter = assert_ok!(AuditEventFilter::try_from(&query(
+            "login", "failure", "ursula"
+        )));
+
+        assert_eq!(filter.action, Some(AuditAction::Login));
+        assert_eq!(filter.outcome, Some(AuditOutcome::Failure));
+        assert_eq!(filter.actor.as_deref(), Some("ursula"));
+    }
+
+    #[test]
+    fn given_an_unknown_outcome_then_it_should_be_rejected() {
+        assert_err!(AuditEventFilter::try_from(&query("", "maybe", "")));
+    }
+}
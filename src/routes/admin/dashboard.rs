@@ -1,27 +1,36 @@
+use crate::authentication::{Role, UserId};
+use crate::csrf::csrf_token_field;
 use crate::session_state::TypedSession;
 use actix_web::error::ErrorInternalServerError;
-use actix_web::http::header::{ContentType, LOCATION};
-use actix_web::{web, HttpResponse};
+use actix_web::http::header::ContentType;
+use actix_web::{HttpResponse, web};
 use anyhow::Context;
 use sqlx::PgPool;
 use uuid::Uuid;
 
 pub async fn admin_dashboard(
+    user_id: web::ReqData<UserId>,
+    role: web::ReqData<Role>,
     session: TypedSession,
     db_pool: web::Data<PgPool>,
 ) -> Result<HttpResponse, actix_web::Error> {
-    let username = if let Ok(Some(user_id)) = session
-        .get_user_id()
-        .map_err(|error| ErrorInternalServerError(error))
-    {
-        get_username(user_id, &db_pool)
-            .await
-            .map_err(|error| ErrorInternalServerError(error))?
-    } else {
-        return Ok(HttpResponse::SeeOther()
-            .insert_header((LOCATION, "/login"))
-            .finish());
-    };
+    let csrf_token_field = csrf_token_field(&session)?;
+    let username = get_username(**user_id, &db_pool)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    let mut role_actions_html = String::new();
+
+    if *role >= Role::Editor {
+        role_actions_html
+            .push_str(r#"<li><a href="/admin/newsletters">Newsletter issues</a></li>"#);
+        role_actions_html.push_str(r#"<li><a href="/admin/api-tokens">API tokens</a></li>"#);
+    }
+
+    if *role >= Role::Owner {
+        role_actions_html.push_str(r#"<li><a href="/admin/users">Users</a></li>"#);
+        role_actions_html.push_str(r#"<li><a href="/admin/audit-log">Audit log</a></li>"#);
+    }
 
     Ok(HttpResponse::Ok()
         .content_type(ContentType::html())
@@ -37,6 +46,16 @@ pub async fn admin_dashboard(
                 <p>Available actions:</p>
                     <ol>
                         <li><a href="/admin/password">Change password</a></li>
+                        <li><a href="/admin/email">Change email address</a></li>
+                        <li><a href="/admin/two-factor">Two-factor authentication</a></li>
+                        <li><a href="/admin/sessions">Active sessions</a></li>
+                        {role_actions_html}
+                        <li>
+                            <form name="logoutForm" action="/admin/logout" method="post">
+                               {csrf_token_field}
+                                <input type="submit" value="Logout">
+                            </form>
+                        </li>
                     </ol>
                 </body>
                 </html>
@@ -44,7 +63,7 @@ pub async fn admin_dashboard(
         )))
 }
 
-async fn get_username(user_id: Uuid, db_pool: &PgPool) -> Result<String, anyhow::Error> {
+pub async fn get_username(user_id: Uuid, db_pool: &PgPool) -> Result<String, anyhow::Error> {
     let row = sqlx::query!(r#"SELECT username FROM users WHERE user_id = $1"#, user_id)
         .fetch_one(db_pool)
         .await
@@ -0,0 +1,72 @@
+use crate::authentication::{UserId, get_user_email_address};
+use crate::csrf::csrf_token_field;
+use crate::session_state::TypedSession;
+use actix_web::error::ErrorInternalServerError;
+use actix_web::http::header::ContentType;
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::IncomingFlashMessages;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+pub async fn change_email_form(
+    user_id: web::ReqData<UserId>,
+    session: TypedSession,
+    flash_messages: IncomingFlashMessages,
+    db_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_token_field = csrf_token_field(&session)?;
+    let mut messages_html = String::new();
+
+    for flash_message in flash_messages.iter() {
+        writeln!(messages_html, "<p><i>{}</i></p>", flash_message.content()).unwrap();
+    }
+
+    let current_email_html = match get_user_email_address(&db_pool, **user_id)
+        .await
+        .map_err(ErrorInternalServerError)?
+    {
+        Some(email) => format!("<p>Your current email address is {email}.</p>"),
+        None => "<p>You have not set an email address yet, so you cannot reset your password.</p>"
+            .to_string(),
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"
+        <!DOCTYPE html>
+        <html lang="en">
+           <head>
+              <meta http-equiv="content-type" content="text/html; charset=utf-8" />
+              <title>Change Email Address</title>
+           </head>
+           <body>
+              {messages_html}
+              {current_email_html}
+              <form action="/admin/email" method="post">
+                 {csrf_token_field}
+                 <label
+                    >New email address
+                    <input
+                       type="email"
+                       placeholder="Enter new email address"
+                       name="email"
+                    />
+                 </label>
+                 <br />
+                 <label
+                    >Current password<input
+                       type="password"
+                       placeholder="Enter current password"
+                       name="current_password"
+                    />
+                 </label>
+                 <br />
+                 <button type="submit">Change email address</button>
+              </form>
+              <p><a href="/admin/dashboard">&lt;- Back</a></p>
+           </body>
+        </html>
+        "#
+        )))
+}
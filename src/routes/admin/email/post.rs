@@ -0,0 +1,109 @@
+use crate::audit::{AuditAction, AuditEvent, AuditOutcome, record_audit_event};
+use crate::authentication::{
+    AuthError, Credentials, PasswordHashing, UserId, change_user_email, validate_credentials,
+};
+use crate::domain::SubscriberEmail;
+use crate::routes::admin::dashboard::get_username;
+use crate::utils::{client_ip, see_other};
+use actix_web::error::ErrorInternalServerError;
+use actix_web::web::Form;
+use actix_web::{HttpRequest, HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use secrecy::SecretString;
+use serde::Deserialize;
+use sqlx::PgPool;
+
+#[derive(Deserialize)]
+pub struct FormData {
+    email: String,
+    current_password: SecretString,
+}
+
+// Password reset links are sent to this address, so changing it asks for the current password
+// just like changing the password does.
+#[tracing::instrument(
+    name = "change_email",
+    skip(form, http_request, user_id, db_pool, password_hashing),
+    fields(user_id=%*user_id)
+)]
+pub async fn change_email(
+    form: Form<FormData>,
+    http_request: HttpRequest,
+    user_id: web::ReqData<UserId>,
+    db_pool: web::Data<PgPool>,
+    password_hashing: web::Data<PasswordHashing>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+    let form = form.into_inner();
+
+    let Ok(email) = SubscriberEmail::parse(form.email.trim().to_owned()) else {
+        FlashMessage::error("The email address is invalid.").send();
+
+        return Ok(see_other("/admin/email"));
+    };
+
+    let username = get_username(*user_id, &db_pool)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    let ip_address = client_ip(&http_request);
+    let credentials = Credentials {
+        username: username.clone(),
+        password: form.current_password,
+    };
+
+    if let Err(error) = validate_credentials(credentials, &password_hashing, &db_pool).await {
+        return match error {
+            AuthError::InvalidCredentials(_) => {
+                let event = AuditEvent {
+                    actor_user_id: Some(*user_id),
+                    actor: &username,
+                    action: AuditAction::ChangeEmail,
+                    target: None,
+                    ip_address: ip_address.as_deref(),
+                    outcome: AuditOutcome::Failure,
+                };
+                record_audit_event(db_pool.get_ref(), &event)
+                    .await
+                    .map_err(ErrorInternalServerError)?;
+
+                FlashMessage::error("The current password is incorrect.").send();
+
+                Ok(see_other("/admin/email"))
+            }
+            _ => Err(ErrorInternalServerError(error)),
+        };
+    }
+
+    let mut transaction = db_pool.begin().await.map_err(ErrorInternalServerError)?;
+
+    if !change_user_email(&mut *transaction, *user_id, email.as_ref())
+        .await
+        .map_err(ErrorInternalServerError)?
+    {
+        FlashMessage::error("Another user already has this email address.").send();
+
+        return Ok(see_other("/admin/email"));
+    }
+
+    let event = AuditEvent {
+        actor_user_id: Some(*user_id),
+        actor: &username,
+        action: AuditAction::ChangeEmail,
+        target: Some(email.as_ref()),
+        ip_address: ip_address.as_deref(),
+        outcome: AuditOutcome::Success,
+    };
+    record_audit_event(&mut *transaction, &event)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    transaction
+        .commit()
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    FlashMessage::info("Your email address has been changed.").send();
+
+    Ok(see_other("/admin/email"))
+}
@@ -0,0 +1,24 @@
+use crate::authentication::{UserId, revoke_session};
+use crate::session_state::TypedSession;
+use crate::utils::see_other;
+use actix_web::error::ErrorInternalServerError;
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+
+pub async fn log_out(
+    session: TypedSession,
+    user_id: web::ReqData<UserId>,
+    db_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if let Some(session_id) = session.get_session_id().map_err(ErrorInternalServerError)? {
+        revoke_session(db_pool.get_ref(), **user_id, session_id)
+            .await
+            .map_err(ErrorInternalServerError)?;
+    }
+
+    session.log_out();
+    FlashMessage::info("You have successfully logged out.").send();
+
+    Ok(see_other("/login"))
+}
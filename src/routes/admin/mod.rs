@@ -1,2 +1,10 @@
+pub mod api_tokens;
+pub mod audit_log;
 pub mod dashboard;
+pub mod email;
+pub mod logout;
+pub mod newsletters;
 pub mod password;
+pub mod sessions;
+pub mod two_factor;
+pub mod users;
@@ -0,0 +1,432 @@
+use crate::application::AudienceTimezone;
+use crate::csrf::csrf_token_field;
+use crate::deliveries::{get_delivery_counts, list_failed_deliveries};
+use crate::newsletter_issues::{
+    IssueStatus, NewsletterIssue, get_newsletter_issue, list_newsletter_issues,
+};
+use crate::session_state::TypedSession;
+use crate::utils::escape_html;
+use actix_web::error::{ErrorInternalServerError, ErrorNotFound};
+use actix_web::http::header::{CONTENT_SECURITY_POLICY, ContentType};
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::IncomingFlashMessages;
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+// The preview renders the issue's own markup, which may style itself inline and load remote
+// images. Scripts stay blocked, both by this policy and by the sandboxed frame.
+const PREVIEW_CONTENT_SECURITY_POLICY: &str = "default-src 'none'; style-src 'unsafe-inline'; \
+    img-src https: data:; frame-ancestors 'none'; base-uri 'none'; form-action 'none'";
+
+pub async fn newsletter_issues_page(
+    flash_messages: IncomingFlashMessages,
+    db_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let messages_html = messages_html(&flash_messages);
+    let issues = list_newsletter_issues(&db_pool)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    let mut issues_html = String::new();
+
+    for issue in issues {
+        writeln!(
+            issues_html,
+            r#"<tr>
+                <td><a href="/admin/newsletters/{newsletter_issue_id}">{title}</a></td>
+                <td>{status}</td>
+                <td>{created_at}</td>
+                <td>{scheduled_at}</td>
+                <td>{published_at}</td>
+             </tr>"#,
+            newsletter_issue_id = issue.newsletter_issue_id,
+            title = escape_html(&issue.title),
+            status = issue.status,
+            created_at = issue.created_at.format("%Y-%m-%d %H:%M UTC"),
+            scheduled_at = issue
+                .scheduled_at
+                .map(|scheduled_at| scheduled_at.format("%Y-%m-%d %H:%M UTC").to_string())
+                .unwrap_or_default(),
+            published_at = issue
+                .published_at
+                .map(|published_at| published_at.format("%Y-%m-%d %H:%M UTC").to_string())
+                .unwrap_or_else(|| "Not published".into()),
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"
+        <!DOCTYPE html>
+        <html lang="en">
+           <head>
+              <meta http-equiv="content-type" content="text/html; charset=utf-8" />
+              <title>Newsletter issues</title>
+           </head>
+           <body>
+              {messages_html}
+              <p><a href="/admin/newsletters/new">Write a new issue</a></p>
+              <table>
+                 <tr>
+                    <th>Title</th>
+                    <th>Status</th>
+                    <th>Created</th>
+                    <th>Scheduled</th>
+                    <th>Published</th>
+                 </tr>
+                 {issues_html}
+              </table>
+              <p><a href="/admin/dashboard">&lt;- Back</a></p>
+           </body>
+        </html>
+        "#
+        )))
+}
+
+pub async fn new_newsletter_issue_form(
+    session: TypedSession,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_token_field = csrf_token_field(&session)?;
+    let messages_html = messages_html(&flash_messages);
+
+    // A fresh key per rendering, so that resubmitting the same form does not store it twice.
+    let idempotency_key = Uuid::new_v4();
+    let form_html = issue_form_html(
+        "/admin/newsletters",
+        &format!(
+            r#"{csrf_token_field}
+                 <input type="hidden" name="idempotency_key" value="{idempotency_key}" />"#
+        ),
+        None,
+    );
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"
+        <!DOCTYPE html>
+        <html lang="en">
+           <head>
+              <meta http-equiv="content-type" content="text/html; charset=utf-8" />
+              <title>Write a newsletter issue</title>
+           </head>
+           <body>
+              {messages_html}
+              {form_html}
+              <p><a href="/admin/newsletters">&lt;- Back</a></p>
+           </body>
+        </html>
+        "#
+        )))
+}
+
+pub async fn newsletter_issue_page(
+    newsletter_issue_id: web::Path<Uuid>,
+    session: TypedSession,
+    flash_messages: IncomingFlashMessages,
+    db_pool: web::Data<PgPool>,
+    audience_timezone: web::Data<AudienceTimezone>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_token_field = csrf_token_field(&session)?;
+    let messages_html = messages_html(&flash_messages);
+    let issue = get_newsletter_issue(&db_pool, newsletter_issue_id.into_inner())
+        .await
+        .map_err(ErrorInternalServerError)?
+        .ok_or_else(|| ErrorNotFound("The newsletter issue does not exist"))?;
+    let issue_path = format!("/admin/newsletters/{}", issue.newsletter_issue_id);
+
+    let timezone = audience_timezone.0;
+    let publish_form_html = format!(
+        r#"<form action="{issue_path}/publish" method="post">
+                 {csrf_token_field}
+                 <p>Publishing sends the issue to every confirmed subscriber.</p>
+                 <button type="submit">Publish now</button>
+              </form>"#
+    );
+
+    let (content_html, publish_html) = match issue.status {
+        IssueStatus::Draft => (
+            issue_form_html(&issue_path, &csrf_token_field, Some(&issue)),
+            format!(
+                "{schedule_form_html}\n{publish_form_html}",
+                schedule_form_html =
+                    schedule_form_html(&issue_path, &csrf_token_field, None, timezone),
+            ),
+        ),
+        IssueStatus::Scheduled => (
+            format!(
+                r#"<h1>{title}</h1>
+              <p>Scheduled for {scheduled_for}. Cancel the schedule to edit the issue.</p>"#,
+                title = escape_html(&issue.title),
+                scheduled_for = issue
+                    .scheduled_at
+                    .map(|scheduled_at| {
+                        let local = scheduled_at.with_timezone(&timezone);
+                        format!("{} ({})", local.format("%Y-%m-%d %H:%M"), timezone)
+                    })
+                    .unwrap_or_default(),
+            ),
+            format!(
+                r#"{schedule_form_html}
+              <form action="{issue_path}/cancel-schedule" method="post">
+                 {csrf_token_field}
+                 <button type="submit">Cancel schedule</button>
+              </form>
+              {publish_form_html}"#,
+                schedule_form_html = schedule_form_html(
+                    &issue_path,
+                    &csrf_token_field,
+                    issue.scheduled_at,
+                    timezone
+                ),
+            ),
+        ),
+        IssueStatus::Sending => (
+            format!(
+                r#"<h1>{title}</h1>
+              <p>Published on {published_at}. The issue can no longer be edited.</p>
+              <p><a href="{issue_path}/report">Delivery report</a></p>"#,
+                title = escape_html(&issue.title),
+                published_at = issue
+                    .published_at
+                    .map(|published_at| published_at.format("%Y-%m-%d %H:%M UTC").to_string())
+                    .unwrap_or_default(),
+            ),
+            String::new(),
+        ),
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"
+        <!DOCTYPE html>
+        <html lang="en">
+           <head>
+              <meta http-equiv="content-type" content="text/html; charset=utf-8" />
+              <title>Newsletter issue</title>
+           </head>
+           <body>
+              {messages_html}
+              <p>Status: {status}</p>
+              {content_html}
+              <p><a href="{issue_path}/preview">Preview</a></p>
+              <form action="{issue_path}/test" method="post">
+                 {csrf_token_field}
+                 <label
+                    >Send a test to
+                    <input type="email" placeholder="Enter an email address" name="email" />
+                 </label>
+                 <button type="submit">Send test email</button>
+              </form>
+              {publish_html}
+              <p><a href="/admin/newsletters">&lt;- Back</a></p>
+           </body>
+        </html>
+        "#,
+            status = issue.status.as_str(),
+        )))
+}
+
+const FAILED_DELIVERIES_SHOWN: i64 = 100;
+
+pub async fn newsletter_issue_report(
+    newsletter_issue_id: web::Path<Uuid>,
+    db_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue = get_newsletter_issue(&db_pool, newsletter_issue_id.into_inner())
+        .await
+        .map_err(ErrorInternalServerError)?
+        .ok_or_else(|| ErrorNotFound("The newsletter issue does not exist"))?;
+    let counts = get_delivery_counts(&db_pool, issue.newsletter_issue_id)
+        .await
+        .map_err(ErrorInternalServerError)?;
+    let failed_deliveries =
+        list_failed_deliveries(&db_pool, issue.newsletter_issue_id, FAILED_DELIVERIES_SHOWN)
+            .await
+            .map_err(ErrorInternalServerError)?;
+
+    let mut failures_html = String::new();
+
+    for delivery in failed_deliveries {
+        writeln!(
+            failures_html,
+            r#"<tr>
+                <td>{subscriber_email}</td>
+                <td>{n_attempts}</td>
+                <td>{last_error}</td>
+                <td>{updated_at}</td>
+             </tr>"#,
+            subscriber_email = escape_html(&delivery.subscriber_email),
+            n_attempts = delivery.n_attempts,
+            last_error = escape_html(delivery.last_error.as_deref().unwrap_or("")),
+            updated_at = delivery.updated_at.format("%Y-%m-%d %H:%M UTC"),
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"
+        <!DOCTYPE html>
+        <html lang="en">
+           <head>
+              <meta http-equiv="content-type" content="text/html; charset=utf-8" />
+              <title>Delivery report</title>
+           </head>
+           <body>
+              <h1>{title}</h1>
+              <table>
+                 <tr>
+                    <th>Pending</th>
+                    <th>Sent</th>
+                    <th>Failed</th>
+                    <th>Skipped</th>
+                 </tr>
+                 <tr>
+                    <td>{pending}</td>
+                    <td>{sent}</td>
+                    <td>{failed}</td>
+                    <td>{skipped}</td>
+                 </tr>
+              </table>
+              <h2>Failed deliveries</h2>
+              <table>
+                 <tr>
+                    <th>Subscriber</th>
+                    <th>Attempts</th>
+                    <th>Last error</th>
+                    <th>Updated</th>
+                 </tr>
+                 {failures_html}
+              </table>
+              <p><a href="/admin/newsletters/{newsletter_issue_id}">&lt;- Back</a></p>
+           </body>
+        </html>
+        "#,
+            title = escape_html(&issue.title),
+            pending = counts.pending,
+            sent = counts.sent,
+            failed = counts.failed,
+            skipped = counts.skipped,
+            newsletter_issue_id = issue.newsletter_issue_id,
+        )))
+}
+
+pub async fn newsletter_issue_preview(
+    newsletter_issue_id: web::Path<Uuid>,
+    db_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue = get_newsletter_issue(&db_pool, newsletter_issue_id.into_inner())
+        .await
+        .map_err(ErrorInternalServerError)?
+        .ok_or_else(|| ErrorNotFound("The newsletter issue does not exist"))?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .insert_header((CONTENT_SECURITY_POLICY, PREVIEW_CONTENT_SECURITY_POLICY))
+        .body(format!(
+            r#"
+        <!DOCTYPE html>
+        <html lang="en">
+           <head>
+              <meta http-equiv="content-type" content="text/html; charset=utf-8" />
+              <title>Preview</title>
+           </head>
+           <body>
+              <h1>{title}</h1>
+              <h2>HTML</h2>
+              <iframe sandbox="" srcdoc="{html_content}" width="100%" height="600"></iframe>
+              <h2>Plain text</h2>
+              <pre>{text_content}</pre>
+              <p><a href="/admin/newsletters/{newsletter_issue_id}">&lt;- Back</a></p>
+           </body>
+        </html>
+        "#,
+            title = escape_html(&issue.title),
+            html_content = escape_html(&issue.html_content),
+            text_content = escape_html(&issue.text_content),
+            newsletter_issue_id = issue.newsletter_issue_id,
+        )))
+}
+
+fn messages_html(flash_messages: &IncomingFlashMessages) -> String {
+    let mut messages_html = String::new();
+
+    for flash_message in flash_messages.iter() {
+        writeln!(messages_html, "<p><i>{}</i></p>", flash_message.content()).unwrap();
+    }
+
+    messages_html
+}
+
+fn issue_form_html(action: &str, hidden_fields: &str, issue: Option<&NewsletterIssue>) -> String {
+    let title = escape_html(issue.map(|issue| issue.title.as_str()).unwrap_or(""));
+    let html_content = escape_html(issue.map(|issue| issue.html_content.as_str()).unwrap_or(""));
+    let text_content = escape_html(issue.map(|issue| issue.text_content.as_str()).unwrap_or(""));
+
+    format!(
+        r#"<form action="{action}" method="post">
+                 {hidden_fields}
+                 <label
+                    >Title
+                    <input type="text" placeholder="Enter the issue title" name="title" value="{title}" />
+                 </label>
+                 <br />
+                 <label
+                    >HTML content
+                    <textarea placeholder="Enter the content in HTML format" name="html_content" rows="20" cols="50">{html_content}</textarea>
+                 </label>
+                 <br />
+                 <label
+                    >Plain text content
+                    <textarea placeholder="Enter the content in plain text" name="text_content" rows="20" cols="50">{text_content}</textarea>
+                 </label>
+                 <br />
+                 <button type="submit">Save draft</button>
+              </form>"#
+    )
+}
+
+fn schedule_form_html(
+    issue_path: &str,
+    csrf_token_field: &str,
+    scheduled_at: Option<DateTime<Utc>>,
+    timezone: Tz,
+) -> String {
+    let scheduled_for = scheduled_at
+        .map(|scheduled_at| {
+            scheduled_at
+                .with_timezone(&timezone)
+                .format("%Y-%m-%dT%H:%M")
+                .to_string()
+        })
+        .unwrap_or_default();
+    let button = if scheduled_at.is_some() {
+        "Reschedule"
+    } else {
+        "Schedule"
+    };
+
+    format!(
+        r#"<form action="{issue_path}/schedule" method="post">
+                 {csrf_token_field}
+                 <label
+                    >Send at
+                    <input type="datetime-local" name="scheduled_for" value="{scheduled_for}" />
+                 </label>
+                 <label
+                    >Time zone
+                    <input type="text" name="timezone" value="{timezone}" />
+                 </label>
+                 <button type="submit">{button}</button>
+              </form>"#
+    )
+}
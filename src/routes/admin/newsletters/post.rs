@@ -0,0 +1,456 @@
+use crate::authentication::{AuthError, RateLimit, UserId, enforce_rate_limit};
+use crate::domain::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::idempotency::{IdempotencyKey, NextAction, save_response, try_processing};
+use crate::newsletter_issues::{
+    IssueContent, Publisher, cancel_scheduled_issue, create_draft, get_newsletter_issue,
+    publish_draft, schedule_issue, update_draft,
+};
+use crate::routes::admin::dashboard::get_username;
+use crate::utils::{client_ip, see_other};
+use actix_web::error::{ErrorBadRequest, ErrorInternalServerError, ErrorNotFound};
+use actix_web::{HttpRequest, HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use chrono::{DateTime, LocalResult, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(serde::Deserialize)]
+pub struct IssueFormData {
+    title: String,
+    html_content: String,
+    text_content: String,
+}
+
+impl IssueFormData {
+    fn content(&self) -> Result<IssueContent<'_>, &'static str> {
+        if [&self.title, &self.html_content, &self.text_content]
+            .iter()
+            .any(|field| field.trim().is_empty())
+        {
+            return Err("The title, HTML content and plain text content are all required.");
+        }
+
+        Ok(IssueContent {
+            title: &self.title,
+            text_content: &self.text_content,
+            html_content: &self.html_content,
+        })
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct NewIssueFormData {
+    #[serde(flatten)]
+    issue: IssueFormData,
+    idempotency_key: String,
+}
+
+#[tracing::instrument(
+    name = "create_newsletter_draft",
+    skip(form, user_id, db_pool),
+    fields(user_id=%*user_id)
+)]
+pub async fn create_newsletter_draft(
+    form: web::Form<NewIssueFormData>,
+    user_id: web::ReqData<UserId>,
+    db_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = **user_id;
+    let NewIssueFormData {
+        issue,
+        idempotency_key,
+    } = form.0;
+
+    let content = match issue.content() {
+        Ok(content) => content,
+        Err(error_message) => {
+            FlashMessage::error(error_message).send();
+
+            return Ok(see_other("/admin/newsletters/new"));
+        }
+    };
+
+    let idempotency_key = IdempotencyKey::try_from(idempotency_key).map_err(ErrorBadRequest)?;
+
+    let mut transaction = match try_processing(&db_pool, &idempotency_key, user_id)
+        .await
+        .map_err(ErrorInternalServerError)?
+    {
+        NextAction::StartProcessing(transaction) => transaction,
+        NextAction::ReturnSavedResponse(saved_response) => return Ok(saved_response),
+        NextAction::RequestInProgress => {
+            FlashMessage::error("This draft is still being saved.").send();
+
+            return Ok(see_other("/admin/newsletters/new"));
+        }
+    };
+
+    let newsletter_issue_id = create_draft(&mut transaction, &content)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    let response = see_other(&format!("/admin/newsletters/{}", newsletter_issue_id));
+    let response = save_response(transaction, &idempotency_key, user_id, response)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    FlashMessage::info("The draft has been saved.").send();
+
+    Ok(response)
+}
+
+#[tracing::instrument(name = "save_newsletter_draft", skip(form, db_pool))]
+pub async fn save_newsletter_draft(
+    newsletter_issue_id: web::Path<Uuid>,
+    form: web::Form<IssueFormData>,
+    db_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let newsletter_issue_id = newsletter_issue_id.into_inner();
+    let issue_path = format!("/admin/newsletters/{}", newsletter_issue_id);
+
+    let content = match form.content() {
+        Ok(content) => content,
+        Err(error_message) => {
+            FlashMessage::error(error_message).send();
+
+            return Ok(see_other(&issue_path));
+        }
+    };
+
+    let updated = update_draft(&db_pool, newsletter_issue_id, &content)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    if updated {
+        FlashMessage::info("The draft has been saved.").send();
+    } else {
+        FlashMessage::error("Only drafts can be edited.").send();
+    }
+
+    Ok(see_other(&issue_path))
+}
+
+#[derive(serde::Deserialize)]
+pub struct TestEmailFormData {
+    email: String,
+}
+
+// Test emails can go to any address, so they are limited to keep the composer from being used to
+// send unsolicited email.
+const TEST_EMAIL_RATE_LIMIT: RateLimit = RateLimit {
+    max_attempts: 10,
+    window: chrono::Duration::hours(1),
+};
+
+#[tracing::instrument(
+    name = "send_newsletter_test",
+    skip(form, user_id, db_pool, email_client),
+    fields(user_id=%*user_id)
+)]
+pub async fn send_newsletter_test(
+    newsletter_issue_id: web::Path<Uuid>,
+    form: web::Form<TestEmailFormData>,
+    user_id: web::ReqData<UserId>,
+    db_pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let newsletter_issue_id = newsletter_issue_id.into_inner();
+    let issue_path = format!("/admin/newsletters/{}", newsletter_issue_id);
+
+    let Ok(recipient) = SubscriberEmail::parse(form.0.email.trim().to_owned()) else {
+        FlashMessage::error("Enter a valid email address to send the test to.").send();
+
+        return Ok(see_other(&issue_path));
+    };
+
+    let issue = get_newsletter_issue(&db_pool, newsletter_issue_id)
+        .await
+        .map_err(ErrorInternalServerError)?
+        .ok_or_else(|| ErrorNotFound("The newsletter issue does not exist"))?;
+
+    match enforce_rate_limit(
+        &format!("test-email:{}", **user_id),
+        &TEST_EMAIL_RATE_LIMIT,
+        &db_pool,
+    )
+    .await
+    {
+        Ok(()) => {}
+        Err(AuthError::TooManyAttempts { retry_after }) => {
+            FlashMessage::error(format!(
+                "Too many test emails have been sent. Try again in {} minutes.",
+                retry_after.as_secs().div_ceil(60).max(1)
+            ))
+            .send();
+
+            return Ok(see_other(&issue_path));
+        }
+        Err(error) => return Err(ErrorInternalServerError(error)),
+    }
+
+    let subject = format!("[Test] {}", issue.title);
+
+    match email_client
+        .send_email(
+            &recipient,
+            &subject,
+            &issue.html_content,
+            &issue.text_content,
+        )
+        .await
+    {
+        Ok(()) => {
+            FlashMessage::info(format!("A test email has been sent to {}.", recipient)).send();
+        }
+        Err(error) => {
+            tracing::error!(
+                error.cause_chain = ?error,
+                error.message = %error,
+                "Failed to send a test email",
+            );
+            FlashMessage::error("The test email could not be sent.").send();
+        }
+    }
+
+    Ok(see_other(&issue_path))
+}
+
+#[tracing::instrument(
+    name = "publish_newsletter_draft",
+    skip(http_request, user_id, db_pool),
+    fields(user_id=%*user_id)
+)]
+pub async fn publish_newsletter_draft(
+    newsletter_issue_id: web::Path<Uuid>,
+    http_request: HttpRequest,
+    user_id: web::ReqData<UserId>,
+    db_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let newsletter_issue_id = newsletter_issue_id.into_inner();
+    let user_id = **user_id;
+
+    get_newsletter_issue(&db_pool, newsletter_issue_id)
+        .await
+        .map_err(ErrorInternalServerError)?
+        .ok_or_else(|| ErrorNotFound("The newsletter issue does not exist"))?;
+
+    let username = get_username(user_id, &db_pool)
+        .await
+        .map_err(ErrorInternalServerError)?;
+    let ip_address = client_ip(&http_request);
+    let publisher = Publisher {
+        user_id: Some(user_id),
+        username: &username,
+        ip_address: ip_address.as_deref(),
+    };
+
+    let mut transaction = db_pool
+        .begin()
+        .await
+        .context("Failed to get a database connection from the pool")
+        .map_err(ErrorInternalServerError)?;
+
+    let published = publish_draft(&mut transaction, newsletter_issue_id, &publisher)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the transaction to publish a draft")
+        .map_err(ErrorInternalServerError)?;
+
+    if published {
+        FlashMessage::info("The newsletter issue has been accepted - emails will go out shortly.")
+            .send();
+    } else {
+        FlashMessage::error("This newsletter issue has already been published.").send();
+    }
+
+    Ok(see_other(&format!(
+        "/admin/newsletters/{}",
+        newsletter_issue_id
+    )))
+}
+
+#[derive(serde::Deserialize)]
+pub struct ScheduleFormData {
+    scheduled_for: String,
+    timezone: String,
+}
+
+#[tracing::instrument(
+    name = "schedule_newsletter_issue",
+    skip(form, http_request, user_id, db_pool),
+    fields(user_id=%*user_id)
+)]
+pub async fn schedule_newsletter_issue(
+    newsletter_issue_id: web::Path<Uuid>,
+    form: web::Form<ScheduleFormData>,
+    http_request: HttpRequest,
+    user_id: web::ReqData<UserId>,
+    db_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let newsletter_issue_id = newsletter_issue_id.into_inner();
+    let user_id = **user_id;
+    let issue_path = format!("/admin/newsletters/{}", newsletter_issue_id);
+
+    get_newsletter_issue(&db_pool, newsletter_issue_id)
+        .await
+        .map_err(ErrorInternalServerError)?
+        .ok_or_else(|| ErrorNotFound("The newsletter issue does not exist"))?;
+
+    let scheduled_at = match parse_schedule(&form.scheduled_for, &form.timezone, Utc::now()) {
+        Ok(scheduled_at) => scheduled_at,
+        Err(error_message) => {
+            FlashMessage::error(error_message).send();
+
+            return Ok(see_other(&issue_path));
+        }
+    };
+
+    let username = get_username(user_id, &db_pool)
+        .await
+        .map_err(ErrorInternalServerError)?;
+    let ip_address = client_ip(&http_request);
+    let scheduler = Publisher {
+        user_id: Some(user_id),
+        username: &username,
+        ip_address: ip_address.as_deref(),
+    };
+
+    let scheduled = schedule_issue(&db_pool, newsletter_issue_id, scheduled_at, &scheduler)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    if scheduled {
+        FlashMessage::info(format!(
+            "The newsletter issue will be sent on {}.",
+            scheduled_at.format("%Y-%m-%d %H:%M UTC")
+        ))
+        .send();
+    } else {
+        FlashMessage::error("This newsletter issue has already been published.").send();
+    }
+
+    Ok(see_other(&issue_path))
+}
+
+#[tracing::instrument(
+    name = "cancel_newsletter_issue_schedule",
+    skip(http_request, user_id, db_pool),
+    fields(user_id=%*user_id)
+)]
+pub async fn cancel_newsletter_issue_schedule(
+    newsletter_issue_id: web::Path<Uuid>,
+    http_request: HttpRequest,
+    user_id: web::ReqData<UserId>,
+    db_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let newsletter_issue_id = newsletter_issue_id.into_inner();
+    let user_id = **user_id;
+
+    get_newsletter_issue(&db_pool, newsletter_issue_id)
+        .await
+        .map_err(ErrorInternalServerError)?
+        .ok_or_else(|| ErrorNotFound("The newsletter issue does not exist"))?;
+
+    let username = get_username(user_id, &db_pool)
+        .await
+        .map_err(ErrorInternalServerError)?;
+    let ip_address = client_ip(&http_request);
+    let canceller = Publisher {
+        user_id: Some(user_id),
+        username: &username,
+        ip_address: ip_address.as_deref(),
+    };
+
+    let cancelled = cancel_scheduled_issue(&db_pool, newsletter_issue_id, &canceller)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    if cancelled {
+        FlashMessage::info("The schedule has been cancelled. The issue is a draft again.").send();
+    } else {
+        FlashMessage::error("This newsletter issue is not scheduled.").send();
+    }
+
+    Ok(see_other(&format!(
+        "/admin/newsletters/{}",
+        newsletter_issue_id
+    )))
+}
+
+// The form submits a wall-clock time without an offset. It is read in the chosen time zone; a
+// time repeated by a daylight saving change resolves to its first occurrence.
+fn parse_schedule(
+    scheduled_for: &str,
+    timezone: &str,
+    now: DateTime<Utc>,
+) -> Result<DateTime<Utc>, String> {
+    let timezone: Tz = timezone
+        .trim()
+        .parse()
+        .map_err(|_| format!("{} is not a known time zone.", timezone.trim()))?;
+    let local = NaiveDateTime::parse_from_str(scheduled_for.trim(), "%Y-%m-%dT%H:%M")
+        .map_err(|_| "Enter the date and time to send the issue at.".to_string())?;
+
+    let scheduled_at = match timezone.from_local_datetime(&local) {
+        LocalResult::Single(scheduled_at) => scheduled_at,
+        LocalResult::Ambiguous(earliest, _) => earliest,
+        LocalResult::None => {
+            return Err(format!("{} does not exist in {}.", local, timezone));
+        }
+    }
+    .with_timezone(&Utc);
+
+    if scheduled_at <= now {
+        return Err("The scheduled time must be in the future.".into());
+    }
+
+    Ok(scheduled_at)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_schedule;
+    use chrono::{TimeZone, Utc};
+    use claims::{assert_err, assert_ok_eq};
+
+    #[test]
+    fn given_a_local_time_then_it_should_convert_it_to_utc() {
+        let now = Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap();
+
+        assert_ok_eq!(
+            parse_schedule("2026-07-01T09:30", "Europe/Rome", now),
+            Utc.with_ymd_and_hms(2026, 7, 1, 7, 30, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn given_an_ambiguous_local_time_then_it_should_use_the_first_occurrence() {
+        let now = Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap();
+
+        assert_ok_eq!(
+            parse_schedule("2026-10-25T02:30", "Europe/Rome", now),
+            Utc.with_ymd_and_hms(2026, 10, 25, 0, 30, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn given_an_invalid_schedule_then_it_should_be_rejected() {
+        let now = Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap();
+
+        for (scheduled_for, timezone) in [
+            ("2026-07-01T09:30", "Mars/Olympus_Mons"),
+            ("tomorrow", "UTC"),
+            ("2026-03-29T02:30", "Europe/Rome"),
+            ("2025-12-31T23:59", "UTC"),
+        ] {
+            assert_err!(parse_schedule(scheduled_for, timezone, now));
+        }
+    }
+}
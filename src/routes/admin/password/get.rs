@@ -1,15 +1,25 @@
+use crate::csrf::csrf_token_field;
 use crate::session_state::TypedSession;
-use crate::utils::get_redirect_if_session_without_user_id;
-use actix_web::http::header::ContentType;
 use actix_web::HttpResponse;
+use actix_web::http::header::ContentType;
+use actix_web_flash_messages::IncomingFlashMessages;
+use std::fmt::Write;
+
+pub async fn change_password_form(
+    session: TypedSession,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_token_field = csrf_token_field(&session)?;
+    let mut messages_html = String::new();
 
-pub async fn change_password_form(session: TypedSession) -> Result<HttpResponse, actix_web::Error> {
-    if let Some(redirect_response) = get_redirect_if_session_without_user_id(&session)? {
-        return Ok(redirect_response);
+    for flash_message in flash_messages.iter() {
+        writeln!(messages_html, "<p><i>{}</i></p>", flash_message.content()).unwrap();
     }
 
-    Ok(HttpResponse::Ok().content_type(ContentType::html()).body(
-        r#"
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"
         <!DOCTYPE html>
         <html lang="en">
            <head>
@@ -17,7 +27,9 @@ pub async fn change_password_form(session: TypedSession) -> Result<HttpResponse,
               <title>Change Password</title>
            </head>
            <body>
+              {messages_html}
               <form action="/admin/password" method="post">
+                 {csrf_token_field}
                  <label
                     >Current password<input
                        type="password"
@@ -49,6 +61,6 @@ pub async fn change_password_form(session: TypedSession) -> Result<HttpResponse,
               <p><a href="/admin/dashboard">&lt;- Back</a></p>
            </body>
         </html>
-        "#,
-    ))
+        "#
+        )))
 }
@@ -1,9 +1,18 @@
+use crate::audit::{AuditAction, AuditEvent, AuditOutcome, record_audit_event};
+use crate::authentication::{
+    AuthError, Credentials, PasswordHashing, UserId, revoke_other_sessions, validate_credentials,
+    validate_new_password,
+};
+use crate::routes::admin::dashboard::get_username;
 use crate::session_state::TypedSession;
-use crate::utils::get_redirect_if_session_without_user_id;
+use crate::utils::{client_ip, see_other};
+use actix_web::error::ErrorInternalServerError;
 use actix_web::web::Form;
-use actix_web::HttpResponse;
+use actix_web::{HttpRequest, HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
 use secrecy::SecretString;
 use serde::Deserialize;
+use sqlx::PgPool;
 
 #[derive(Deserialize)]
 pub struct FormData {
@@ -12,13 +21,101 @@ pub struct FormData {
     new_password_check: SecretString,
 }
 
+#[tracing::instrument(
+    name = "change_password",
+    skip(form, http_request, user_id, session, db_pool, password_hashing),
+    fields(user_id=%*user_id)
+)]
 pub async fn change_password(
     form: Form<FormData>,
+    http_request: HttpRequest,
+    user_id: web::ReqData<UserId>,
     session: TypedSession,
+    db_pool: web::Data<PgPool>,
+    password_hashing: web::Data<PasswordHashing>,
 ) -> Result<HttpResponse, actix_web::Error> {
-    if let Some(redirect_response) = get_redirect_if_session_without_user_id(&session)? {
-        return Ok(redirect_response);
+    let user_id = user_id.into_inner();
+
+    if let Err(error_message) = validate_new_password(&form.new_password, &form.new_password_check)
+    {
+        FlashMessage::error(error_message).send();
+
+        return Ok(see_other("/admin/password"));
+    }
+
+    let username = get_username(*user_id, &db_pool)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    let ip_address = client_ip(&http_request);
+    let credentials = Credentials {
+        username: username.clone(),
+        password: form.0.current_password,
+    };
+
+    if let Err(error) = validate_credentials(credentials, &password_hashing, &db_pool).await {
+        return match error {
+            AuthError::InvalidCredentials(_) => {
+                let event = AuditEvent {
+                    actor_user_id: Some(*user_id),
+                    actor: &username,
+                    action: AuditAction::ChangePassword,
+                    target: None,
+                    ip_address: ip_address.as_deref(),
+                    outcome: AuditOutcome::Failure,
+                };
+                record_audit_event(db_pool.get_ref(), &event)
+                    .await
+                    .map_err(ErrorInternalServerError)?;
+
+                FlashMessage::error("The current password is incorrect.").send();
+
+                Ok(see_other("/admin/password"))
+            }
+            _ => Err(ErrorInternalServerError(error)),
+        };
     }
 
-    todo!()
+    let current_session_id = session
+        .get_session_id()
+        .map_err(ErrorInternalServerError)?
+        .ok_or_else(|| ErrorInternalServerError("The session id is not available"))?;
+
+    // Whoever knew the previous password may still be logged in elsewhere, so only the session
+    // that made the change is kept.
+    let mut transaction = db_pool.begin().await.map_err(ErrorInternalServerError)?;
+
+    crate::authentication::change_password(
+        *user_id,
+        form.0.new_password,
+        &password_hashing,
+        &mut *transaction,
+    )
+    .await
+    .map_err(ErrorInternalServerError)?;
+
+    revoke_other_sessions(&mut *transaction, *user_id, current_session_id)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    let event = AuditEvent {
+        actor_user_id: Some(*user_id),
+        actor: &username,
+        action: AuditAction::ChangePassword,
+        target: None,
+        ip_address: ip_address.as_deref(),
+        outcome: AuditOutcome::Success,
+    };
+    record_audit_event(&mut *transaction, &event)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    transaction
+        .commit()
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    FlashMessage::info("Your password has been changed.").send();
+
+    Ok(see_other("/admin/password"))
 }
@@ -0,0 +1,93 @@
+use crate::authentication::{UserId, list_active_sessions};
+use crate::csrf::csrf_token_field;
+use crate::session_state::TypedSession;
+use crate::utils::escape_html;
+use actix_web::error::ErrorInternalServerError;
+use actix_web::http::header::ContentType;
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::IncomingFlashMessages;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+pub async fn sessions_page(
+    flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+    user_id: web::ReqData<UserId>,
+    db_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_token_field = csrf_token_field(&session)?;
+    let current_session_id = session.get_session_id().map_err(ErrorInternalServerError)?;
+    let mut messages_html = String::new();
+
+    for flash_message in flash_messages.iter() {
+        writeln!(messages_html, "<p><i>{}</i></p>", flash_message.content()).unwrap();
+    }
+
+    let active_sessions = list_active_sessions(&db_pool, **user_id)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    let mut sessions_html = String::new();
+
+    for active_session in active_sessions {
+        let action_html = if Some(active_session.session_id) == current_session_id {
+            "This session".to_owned()
+        } else {
+            format!(
+                r#"<form action="/admin/sessions/{session_id}/revoke" method="post">
+                      {csrf_token_field}
+                      <button type="submit">Revoke</button>
+                   </form>"#,
+                session_id = active_session.session_id
+            )
+        };
+
+        writeln!(
+            sessions_html,
+            r#"<tr>
+                <td>{created_at}</td>
+                <td>{last_seen_at}</td>
+                <td>{ip_address}</td>
+                <td>{user_agent}</td>
+                <td>{action_html}</td>
+             </tr>"#,
+            created_at = active_session.created_at.format("%Y-%m-%d %H:%M UTC"),
+            last_seen_at = active_session.last_seen_at.format("%Y-%m-%d %H:%M UTC"),
+            ip_address = escape_html(active_session.ip_address.as_deref().unwrap_or("Unknown")),
+            user_agent = escape_html(active_session.user_agent.as_deref().unwrap_or("Unknown")),
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"
+        <!DOCTYPE html>
+        <html lang="en">
+           <head>
+              <meta http-equiv="content-type" content="text/html; charset=utf-8" />
+              <title>Active sessions</title>
+           </head>
+           <body>
+              {messages_html}
+              <table>
+                 <tr>
+                    <th>Signed in</th>
+                    <th>Last seen</th>
+                    <th>IP address</th>
+                    <th>Device</th>
+                    <th></th>
+                 </tr>
+                 {sessions_html}
+              </table>
+              <form action="/admin/sessions/revoke-others" method="post">
+                 {csrf_token_field}
+                 <button type="submit">Sign out all other sessions</button>
+              </form>
+              <p><a href="/admin/dashboard">&lt;- Back</a></p>
+           </body>
+        </html>
+        "#
+        )))
+}
@@ -0,0 +1,66 @@
+use crate::authentication::{UserId, revoke_other_sessions, revoke_session};
+use crate::session_state::TypedSession;
+use crate::utils::see_other;
+use actix_web::error::ErrorInternalServerError;
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[tracing::instrument(
+    name = "revoke_user_session",
+    skip(session_id, user_id, session, db_pool),
+    fields(user_id=%*user_id)
+)]
+pub async fn revoke_user_session(
+    session_id: web::Path<Uuid>,
+    user_id: web::ReqData<UserId>,
+    session: TypedSession,
+    db_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let session_id = session_id.into_inner();
+    let current_session_id = session.get_session_id().map_err(ErrorInternalServerError)?;
+
+    // Logging out is the way to end the current session; it also clears the cookie.
+    if Some(session_id) == current_session_id {
+        FlashMessage::error("Use the logout button to end the current session.").send();
+
+        return Ok(see_other("/admin/sessions"));
+    }
+
+    let revoked = revoke_session(db_pool.get_ref(), **user_id, session_id)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    if revoked {
+        FlashMessage::info("The session has been revoked.").send();
+    } else {
+        FlashMessage::error("The session does not exist or has already ended.").send();
+    }
+
+    Ok(see_other("/admin/sessions"))
+}
+
+#[tracing::instrument(
+    name = "revoke_all_other_sessions",
+    skip(user_id, session, db_pool),
+    fields(user_id=%*user_id)
+)]
+pub async fn revoke_all_other_sessions(
+    user_id: web::ReqData<UserId>,
+    session: TypedSession,
+    db_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let current_session_id = session
+        .get_session_id()
+        .map_err(ErrorInternalServerError)?
+        .ok_or_else(|| ErrorInternalServerError("The session id is not available"))?;
+
+    let revoked = revoke_other_sessions(db_pool.get_ref(), **user_id, current_session_id)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    FlashMessage::info(format!("{} other session(s) have been revoked.", revoked)).send();
+
+    Ok(see_other("/admin/sessions"))
+}
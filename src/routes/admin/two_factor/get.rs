@@ -0,0 +1,99 @@
+use crate::authentication::{TotpSecretCipher, TwoFactorStatus, UserId, get_two_factor_status};
+use crate::csrf::csrf_token_field;
+use crate::session_state::TypedSession;
+use actix_web::error::ErrorInternalServerError;
+use actix_web::http::header::ContentType;
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::IncomingFlashMessages;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+pub async fn two_factor_form(
+    flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+    user_id: web::ReqData<UserId>,
+    db_pool: web::Data<PgPool>,
+    totp_secret_cipher: web::Data<TotpSecretCipher>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_token_field = csrf_token_field(&session)?;
+    let mut messages_html = String::new();
+
+    for flash_message in flash_messages.iter() {
+        writeln!(messages_html, "<p><i>{}</i></p>", flash_message.content()).unwrap();
+    }
+
+    let status = get_two_factor_status(&db_pool, &totp_secret_cipher, **user_id)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    let status_html = match status {
+        TwoFactorStatus::Disabled => format!(
+            r#"
+              <p>Two-factor authentication is disabled.</p>
+              <form action="/admin/two-factor/setup" method="post">
+                 {csrf_token_field}
+                 <button type="submit">Set up two-factor authentication</button>
+              </form>
+            "#
+        ),
+        TwoFactorStatus::Pending(totp) => format!(
+            r#"
+              <p>Scan this provisioning URI with your authenticator app:</p>
+              <p><a href="{provisioning_uri}">{provisioning_uri}</a></p>
+              <p>Or enter this secret manually: <code>{secret}</code></p>
+              <form action="/admin/two-factor/confirm" method="post">
+                 {csrf_token_field}
+                 <label
+                    >Code
+                    <input
+                       type="text"
+                       placeholder="Enter the code shown by the app"
+                       name="code"
+                    />
+                 </label>
+                 <br />
+                 <button type="submit">Enable two-factor authentication</button>
+              </form>
+            "#,
+            provisioning_uri = totp.get_url(),
+            secret = totp.get_secret_base32()
+        ),
+        TwoFactorStatus::Enabled(_) => format!(
+            r#"
+              <p>Two-factor authentication is enabled.</p>
+              <form action="/admin/two-factor/disable" method="post">
+                 {csrf_token_field}
+                 <label
+                    >Code
+                    <input
+                       type="text"
+                       placeholder="Enter a code or a recovery code"
+                       name="code"
+                    />
+                 </label>
+                 <br />
+                 <button type="submit">Disable two-factor authentication</button>
+              </form>
+            "#
+        ),
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"
+        <!DOCTYPE html>
+        <html lang="en">
+           <head>
+              <meta http-equiv="content-type" content="text/html; charset=utf-8" />
+              <title>Two-factor authentication</title>
+           </head>
+           <body>
+              {messages_html}
+              {status_html}
+              <p><a href="/admin/dashboard">&lt;- Back</a></p>
+           </body>
+        </html>
+        "#
+        )))
+}
@@ -0,0 +1,143 @@
+use crate::authentication::{
+    TotpSecretCipher, TwoFactorStatus, UserId, disable_two_factor, enable_two_factor,
+    get_two_factor_status, start_two_factor_enrollment, verify_second_factor,
+};
+use crate::utils::see_other;
+use actix_web::error::ErrorInternalServerError;
+use actix_web::http::header::ContentType;
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    code: String,
+}
+
+#[tracing::instrument(
+    name = "start_two_factor_setup",
+    skip(user_id, db_pool, totp_secret_cipher),
+    fields(user_id=%*user_id)
+)]
+pub async fn start_two_factor_setup(
+    user_id: web::ReqData<UserId>,
+    db_pool: web::Data<PgPool>,
+    totp_secret_cipher: web::Data<TotpSecretCipher>,
+) -> Result<HttpResponse, actix_web::Error> {
+    start_two_factor_enrollment(&db_pool, &totp_secret_cipher, **user_id)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    Ok(see_other("/admin/two-factor"))
+}
+
+#[tracing::instrument(
+    name = "confirm_two_factor_setup",
+    skip(form, user_id, db_pool, totp_secret_cipher),
+    fields(user_id=%*user_id)
+)]
+pub async fn confirm_two_factor_setup(
+    form: web::Form<FormData>,
+    user_id: web::ReqData<UserId>,
+    db_pool: web::Data<PgPool>,
+    totp_secret_cipher: web::Data<TotpSecretCipher>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = **user_id;
+
+    let TwoFactorStatus::Pending(totp) =
+        get_two_factor_status(&db_pool, &totp_secret_cipher, user_id)
+            .await
+            .map_err(ErrorInternalServerError)?
+    else {
+        return Ok(see_other("/admin/two-factor"));
+    };
+
+    if !verify_second_factor(&db_pool, &totp, user_id, &form.code)
+        .await
+        .map_err(ErrorInternalServerError)?
+    {
+        FlashMessage::error("The authentication code is invalid.").send();
+
+        return Ok(see_other("/admin/two-factor"));
+    }
+
+    let recovery_codes = enable_two_factor(&db_pool, user_id)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    let mut recovery_codes_html = String::new();
+
+    for recovery_code in recovery_codes {
+        writeln!(
+            recovery_codes_html,
+            "<li><code>{}</code></li>",
+            recovery_code
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"
+        <!DOCTYPE html>
+        <html lang="en">
+           <head>
+              <meta http-equiv="content-type" content="text/html; charset=utf-8" />
+              <title>Two-factor authentication</title>
+           </head>
+           <body>
+              <p>Two-factor authentication is now enabled.</p>
+              <p>
+                 Store these recovery codes somewhere safe. Each of them can be used once to log in
+                 if you lose access to your authenticator app. They will not be shown again.
+              </p>
+              <ul>
+                 {recovery_codes_html}
+              </ul>
+              <p><a href="/admin/dashboard">&lt;- Back</a></p>
+           </body>
+        </html>
+        "#
+        )))
+}
+
+#[tracing::instrument(
+    name = "turn_off_two_factor",
+    skip(form, user_id, db_pool, totp_secret_cipher),
+    fields(user_id=%*user_id)
+)]
+pub async fn turn_off_two_factor(
+    form: web::Form<FormData>,
+    user_id: web::ReqData<UserId>,
+    db_pool: web::Data<PgPool>,
+    totp_secret_cipher: web::Data<TotpSecretCipher>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = **user_id;
+
+    let TwoFactorStatus::Enabled(totp) =
+        get_two_factor_status(&db_pool, &totp_secret_cipher, user_id)
+            .await
+            .map_err(ErrorInternalServerError)?
+    else {
+        return Ok(see_other("/admin/two-factor"));
+    };
+
+    if !verify_second_factor(&db_pool, &totp, user_id, &form.code)
+        .await
+        .map_err(ErrorInternalServerError)?
+    {
+        FlashMessage::error("The authentication code is invalid.").send();
+
+        return Ok(see_other("/admin/two-factor"));
+    }
+
+    disable_two_factor(&db_pool, user_id)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    FlashMessage::info("Two-factor authentication has been disabled.").send();
+
+    Ok(see_other("/admin/two-factor"))
+}
@@ -0,0 +1,146 @@
+use crate::authentication::{Role, UserId, list_users};
+use crate::csrf::csrf_token_field;
+use crate::session_state::TypedSession;
+use actix_web::error::ErrorInternalServerError;
+use actix_web::http::header::ContentType;
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::IncomingFlashMessages;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+pub async fn users_page(
+    flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+    user_id: web::ReqData<UserId>,
+    db_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_token_field = csrf_token_field(&session)?;
+    let mut messages_html = String::new();
+
+    for flash_message in flash_messages.iter() {
+        writeln!(messages_html, "<p><i>{}</i></p>", flash_message.content()).unwrap();
+    }
+
+    let users = list_users(&db_pool)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    let mut users_html = String::new();
+
+    for user in users {
+        let status = if user.disabled { "Disabled" } else { "Active" };
+
+        // Owners cannot lock themselves out, which guarantees there is always an owner left.
+        let actions_html = if user.user_id == **user_id {
+            "(you)".to_string()
+        } else {
+            let (toggle_action, toggle_label) = if user.disabled {
+                ("enable", "Enable")
+            } else {
+                ("disable", "Disable")
+            };
+
+            format!(
+                r#"<form action="/admin/users/{user_id}/role" method="post">
+                      {csrf_token_field}
+                      <select name="role">{role_options}</select>
+                      <button type="submit">Change role</button>
+                   </form>
+                   <form action="/admin/users/{user_id}/{toggle_action}" method="post">
+                      {csrf_token_field}
+                      <button type="submit">{toggle_label}</button>
+                   </form>
+                   <form action="/admin/users/{user_id}/delete" method="post">
+                      {csrf_token_field}
+                      <button type="submit">Delete</button>
+                   </form>"#,
+                user_id = user.user_id,
+                role_options = role_options_html(Some(user.role)),
+            )
+        };
+
+        writeln!(
+            users_html,
+            r#"<tr>
+                <td>{username}</td>
+                <td>{email}</td>
+                <td>{role}</td>
+                <td>{status}</td>
+                <td>{actions_html}</td>
+             </tr>"#,
+            username = user.username,
+            email = user.email.unwrap_or_default(),
+            role = user.role.as_str(),
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"
+        <!DOCTYPE html>
+        <html lang="en">
+           <head>
+              <meta http-equiv="content-type" content="text/html; charset=utf-8" />
+              <title>Users</title>
+           </head>
+           <body>
+              {messages_html}
+              <table>
+                 <tr>
+                    <th>Username</th>
+                    <th>Email</th>
+                    <th>Role</th>
+                    <th>Status</th>
+                    <th></th>
+                 </tr>
+                 {users_html}
+              </table>
+              <form action="/admin/users" method="post">
+                 {csrf_token_field}
+                 <label
+                    >Username
+                    <input type="text" placeholder="Enter a username" name="username" />
+                 </label>
+                 <br />
+                 <label
+                    >Email
+                    <input type="email" placeholder="Enter an email address" name="email" />
+                 </label>
+                 <br />
+                 <label
+                    >Role
+                    <select name="role">{role_options}</select>
+                 </label>
+                 <br />
+                 <button type="submit">Invite user</button>
+              </form>
+              <p><a href="/admin/dashboard">&lt;- Back</a></p>
+           </body>
+        </html>
+        "#,
+            role_options = role_options_html(Some(Role::Editor)),
+        )))
+}
+
+fn role_options_html(selected: Option<Role>) -> String {
+    let mut options_html = String::new();
+
+    for role in Role::ALL {
+        let selected_attribute = if Some(role) == selected {
+            " selected"
+        } else {
+            ""
+        };
+
+        write!(
+            options_html,
+            r#"<option value="{role}"{selected_attribute}>{role}</option>"#,
+            role = role.as_str()
+        )
+        .unwrap();
+    }
+
+    options_html
+}
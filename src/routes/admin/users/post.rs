@@ -0,0 +1,345 @@
+use crate::application::{ApplicationBaseUrl, InvitationTokenTtl};
+use crate::authentication::{
+    PasswordHashing, Role, UserId, change_user_role, create_user, delete_user, disable_user,
+    enable_user, issue_password_reset_token,
+};
+use crate::domain::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::utils::see_other;
+use actix_web::error::ErrorInternalServerError;
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+const MAX_USERNAME_LENGTH: usize = 64;
+
+#[derive(serde::Deserialize)]
+pub struct InviteFormData {
+    username: String,
+    email: String,
+    role: String,
+}
+
+#[derive(Debug)]
+struct NewUser {
+    username: String,
+    email: SubscriberEmail,
+    role: Role,
+}
+
+impl TryFrom<InviteFormData> for NewUser {
+    type Error = String;
+
+    fn try_from(form: InviteFormData) -> Result<Self, Self::Error> {
+        let username = form.username.trim().to_owned();
+
+        let is_valid_username = username.chars().count() <= MAX_USERNAME_LENGTH
+            && !username.is_empty()
+            && username
+                .chars()
+                .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.'));
+
+        if !is_valid_username {
+            return Err(format!(
+                "The username must be 1 to {} letters, digits, dashes, underscores or dots.",
+                MAX_USERNAME_LENGTH
+            ));
+        }
+
+        let email = SubscriberEmail::parse(form.email.trim().to_owned())
+            .map_err(|_| "The email address is invalid.".to_string())?;
+        let role = Role::try_from(form.role.as_str())?;
+
+        Ok(Self {
+            username,
+            email,
+            role,
+        })
+    }
+}
+
+#[tracing::instrument(
+    name = "invite_user",
+    skip(
+        form,
+        db_pool,
+        password_hashing,
+        email_client,
+        application_base_url,
+        invitation_token_ttl
+    ),
+    fields(username = %form.username)
+)]
+pub async fn invite_user(
+    form: web::Form<InviteFormData>,
+    db_pool: web::Data<PgPool>,
+    password_hashing: web::Data<PasswordHashing>,
+    email_client: web::Data<EmailClient>,
+    application_base_url: web::Data<ApplicationBaseUrl>,
+    invitation_token_ttl: web::Data<InvitationTokenTtl>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let new_user = match NewUser::try_from(form.0) {
+        Ok(new_user) => new_user,
+        Err(error) => {
+            FlashMessage::error(error).send();
+
+            return Ok(see_other("/admin/users"));
+        }
+    };
+
+    let Some(user_id) = create_user(
+        &db_pool,
+        &new_user.username,
+        new_user.email.as_ref(),
+        new_user.role,
+        &password_hashing,
+    )
+    .await
+    .map_err(ErrorInternalServerError)?
+    else {
+        FlashMessage::error("A user with this username or email address already exists.").send();
+
+        return Ok(see_other("/admin/users"));
+    };
+
+    match send_invitation_email(
+        &db_pool,
+        &email_client,
+        &application_base_url,
+        user_id,
+        &new_user.email,
+        invitation_token_ttl.0,
+    )
+    .await
+    {
+        Ok(()) => {
+            FlashMessage::info(format!(
+                "{} has been invited. They will receive an email to choose a password.",
+                new_user.username
+            ))
+            .send();
+        }
+        Err(error) => {
+            tracing::error!(
+                error.cause_chain = ?error,
+                error.message = %error,
+                "Failed to send an invitation email",
+            );
+
+            FlashMessage::error(format!(
+                "{} has been created, but the invitation email could not be sent. \
+                They can use the password reset form to choose a password.",
+                new_user.username
+            ))
+            .send();
+        }
+    }
+
+    Ok(see_other("/admin/users"))
+}
+
+#[derive(serde::Deserialize)]
+pub struct RoleFormData {
+    role: String,
+}
+
+#[tracing::instrument(
+    name = "change_role",
+    skip(form, target_user_id, user_id, db_pool),
+    fields(user_id=%*user_id)
+)]
+pub async fn change_role(
+    form: web::Form<RoleFormData>,
+    target_user_id: web::Path<Uuid>,
+    user_id: web::ReqData<UserId>,
+    db_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let target_user_id = target_user_id.into_inner();
+
+    if let Some(response) = reject_self_management(target_user_id, **user_id) {
+        return Ok(response);
+    }
+
+    let role = match Role::try_from(form.role.as_str()) {
+        Ok(role) => role,
+        Err(error) => {
+            FlashMessage::error(error).send();
+
+            return Ok(see_other("/admin/users"));
+        }
+    };
+
+    change_user_role(&db_pool, target_user_id, role)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    FlashMessage::info("The user's role has been changed.").send();
+
+    Ok(see_other("/admin/users"))
+}
+
+#[tracing::instrument(
+    name = "disable_account",
+    skip(target_user_id, user_id, db_pool),
+    fields(user_id=%*user_id)
+)]
+pub async fn disable_account(
+    target_user_id: web::Path<Uuid>,
+    user_id: web::ReqData<UserId>,
+    db_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let target_user_id = target_user_id.into_inner();
+
+    if let Some(response) = reject_self_management(target_user_id, **user_id) {
+        return Ok(response);
+    }
+
+    disable_user(&db_pool, target_user_id)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    FlashMessage::info("The user has been disabled.").send();
+
+    Ok(see_other("/admin/users"))
+}
+
+#[tracing::instrument(
+    name = "enable_account",
+    skip(target_user_id, user_id, db_pool),
+    fields(user_id=%*user_id)
+)]
+pub async fn enable_account(
+    target_user_id: web::Path<Uuid>,
+    user_id: web::ReqData<UserId>,
+    db_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    enable_user(&db_pool, target_user_id.into_inner())
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    FlashMessage::info("The user has been enabled.").send();
+
+    Ok(see_other("/admin/users"))
+}
+
+#[tracing::instrument(
+    name = "delete_account",
+    skip(target_user_id, user_id, db_pool),
+    fields(user_id=%*user_id)
+)]
+pub async fn delete_account(
+    target_user_id: web::Path<Uuid>,
+    user_id: web::ReqData<UserId>,
+    db_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let target_user_id = target_user_id.into_inner();
+
+    if let Some(response) = reject_self_management(target_user_id, **user_id) {
+        return Ok(response);
+    }
+
+    delete_user(&db_pool, target_user_id)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    FlashMessage::info("The user has been deleted.").send();
+
+    Ok(see_other("/admin/users"))
+}
+
+// Owners cannot demote, disable or delete themselves, so that there is always an owner left.
+fn reject_self_management(target_user_id: Uuid, user_id: Uuid) -> Option<HttpResponse> {
+    if target_user_id != user_id {
+        return None;
+    }
+
+    FlashMessage::error("You cannot change your own account from this page.").send();
+
+    Some(see_other("/admin/users"))
+}
+
+#[tracing::instrument(
+    name = "send_invitation_email",
+    skip(db_pool, email_client, application_base_url, recipient)
+)]
+async fn send_invitation_email(
+    db_pool: &PgPool,
+    email_client: &EmailClient,
+    application_base_url: &ApplicationBaseUrl,
+    user_id: Uuid,
+    recipient: &SubscriberEmail,
+    ttl: chrono::Duration,
+) -> Result<(), anyhow::Error> {
+    let token = issue_password_reset_token(db_pool, user_id, ttl).await?;
+
+    let invitation_link = format!(
+        "{}/password-reset/confirm?token={}",
+        application_base_url.0, token
+    );
+
+    let html_content = format!(
+        "You have been invited to manage our newsletter.<br />\
+        Click <a href=\"{}\">here</a> to choose your password.",
+        invitation_link
+    );
+
+    let text_content = format!(
+        "You have been invited to manage our newsletter.\nVisit {} to choose your password.",
+        invitation_link
+    );
+
+    email_client
+        .send_email(
+            recipient,
+            "You have been invited to the newsletter admin",
+            &html_content,
+            &text_content,
+        )
+        .await
+        .context("Failed to send the invitation email")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::routes::admin::users::post::{InviteFormData, NewUser};
+    use claims::{assert_err, assert_ok};
+
+    fn form(username: &str, email: &str, role: &str) -> InviteFormData {
+        InviteFormData {
+            username: username.into(),
+            email: email.into(),
+            role: role.into(),
+        }
+    }
+
+    #[test]
+    fn given_valid_fields_then_it_should_accept_the_new_user() {
+        assert_ok!(NewUser::try_from(form(
+            "ursula",
+            "ursula_le_guin@gmail.com",
+            "editor"
+        )));
+    }
+
+    #[test]
+    fn given_a_username_with_markup_then_it_should_be_rejected() {
+        assert_err!(NewUser::try_from(form(
+            "<b>ursula</b>",
+            "ursula_le_guin@gmail.com",
+            "editor"
+        )));
+    }
+
+    #[test]
+    fn given_an_unknown_role_then_it_should_be_rejected() {
+        assert_err!(NewUser::try_from(form(
+            "ursula",
+            "ursula_le_guin@gmail.com",
+            "administrator"
+        )));
+    }
+}
@@ -1,77 +1,258 @@
-use actix_web::{HttpResponse, web};
-use sqlx::PgPool;
+use crate::application::{ApplicationBaseUrl, SubscriptionTokenTtl};
+use crate::domain::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::routes::subscriptions::{
+    generate_subscription_token, reset_pending_subscription, send_confirmation_email, store_token,
+};
+use crate::utils::error_chain_fmt;
+use actix_web::http::StatusCode;
+use actix_web::http::header::ContentType;
+use actix_web::{HttpResponse, ResponseError, web};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use std::fmt::Debug;
 use uuid::Uuid;
 
+#[derive(thiserror::Error)]
+pub enum ConfirmSubscriptionError {
+    #[error("The subscription token is unknown or has already been used")]
+    InvalidToken,
+    #[error("The subscription token has expired")]
+    ExpiredToken(String),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl Debug for ConfirmSubscriptionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for ConfirmSubscriptionError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ConfirmSubscriptionError::InvalidToken => StatusCode::UNAUTHORIZED,
+            ConfirmSubscriptionError::ExpiredToken(_) => StatusCode::GONE,
+            ConfirmSubscriptionError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        match self {
+            ConfirmSubscriptionError::ExpiredToken(subscription_token) => {
+                HttpResponse::build(self.status_code())
+                    .content_type(ContentType::html())
+                    .body(format!(
+                        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Confirmation link expired</title>
+</head>
+<body>
+    <p>This confirmation link has expired.</p>
+    <form action="/subscriptions/confirm/resend" method="post">
+        <input type="hidden" name="subscription_token" value="{}">
+        <button type="submit">Send me a new link</button>
+    </form>
+</body>
+</html>"#,
+                        subscription_token
+                    ))
+            }
+            _ => HttpResponse::new(self.status_code()),
+        }
+    }
+}
+
 #[derive(serde::Deserialize)]
 pub struct Parameters {
     subscription_token: String,
 }
 
-#[tracing::instrument(name = "confirm_subscription", skip(parameters, db_connection_pool))]
+#[tracing::instrument(
+    name = "confirm_subscription",
+    skip(parameters, db_connection_pool, subscription_token_ttl)
+)]
 pub async fn confirm_subscription(
     parameters: web::Query<Parameters>,
     db_connection_pool: web::Data<PgPool>,
-) -> HttpResponse {
-    let subscriber_id =
-        match get_subscriber_id_from_token(&db_connection_pool, &parameters.subscription_token)
-            .await
-        {
-            Ok(subscriber_id) => subscriber_id,
-            Err(_) => return HttpResponse::InternalServerError().finish(),
-        };
-
-    match subscriber_id {
-        None => HttpResponse::Unauthorized().finish(),
-        Some(subscriber_id) => {
-            if confirm_subscriber(&db_connection_pool, subscriber_id)
-                .await
-                .is_err()
-            {
-                return HttpResponse::InternalServerError().finish();
-            }
+    subscription_token_ttl: web::Data<SubscriptionTokenTtl>,
+) -> Result<HttpResponse, ConfirmSubscriptionError> {
+    let mut transaction = db_connection_pool
+        .begin()
+        .await
+        .context("Failed to get a database connection from the pool")?;
 
-            HttpResponse::Ok().finish()
-        }
+    let token = get_subscription_token(&mut transaction, &parameters.subscription_token)
+        .await
+        .context("Failed to retrieve the subscription token")?
+        .ok_or(ConfirmSubscriptionError::InvalidToken)?;
+
+    if token.consumed_at.is_some() {
+        return Err(ConfirmSubscriptionError::InvalidToken);
+    }
+
+    if token.created_at + subscription_token_ttl.0 < Utc::now() {
+        return Err(ConfirmSubscriptionError::ExpiredToken(
+            parameters.0.subscription_token,
+        ));
     }
+
+    consume_token(&mut transaction, &parameters.subscription_token)
+        .await
+        .context("Failed to mark the subscription token as consumed")?;
+
+    confirm_subscriber(&mut transaction, token.subscriber_id)
+        .await
+        .context("Failed to update the subscription status")?;
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the transaction to confirm a subscriber")?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[derive(serde::Deserialize)]
+pub struct ResendFormData {
+    subscription_token: String,
 }
 
 #[tracing::instrument(
-    name = "get_subscriber_id_from_token",
-    skip(db_connection_pool, subscription_token)
+    name = "resend_confirmation",
+    skip(form, db_connection_pool, email_client, application_base_url)
 )]
-async fn get_subscriber_id_from_token(
-    db_connection_pool: &PgPool,
+pub async fn resend_confirmation(
+    form: web::Form<ResendFormData>,
+    db_connection_pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    application_base_url: web::Data<ApplicationBaseUrl>,
+) -> Result<HttpResponse, ConfirmSubscriptionError> {
+    let mut transaction = db_connection_pool
+        .begin()
+        .await
+        .context("Failed to get a database connection from the pool")?;
+
+    let token = get_subscription_token(&mut transaction, &form.subscription_token)
+        .await
+        .context("Failed to retrieve the subscription token")?
+        .filter(|token| token.consumed_at.is_none())
+        .ok_or(ConfirmSubscriptionError::InvalidToken)?;
+
+    let subscriber = get_pending_subscriber(&mut transaction, token.subscriber_id)
+        .await
+        .context("Failed to retrieve the pending subscriber")?
+        .ok_or(ConfirmSubscriptionError::InvalidToken)?;
+    let subscriber_email =
+        SubscriberEmail::parse(subscriber.email).map_err(|error| anyhow::anyhow!(error))?;
+
+    reset_pending_subscription(&mut transaction, token.subscriber_id)
+        .await
+        .context("Failed to discard the previous subscription tokens")?;
+
+    let subscription_token = generate_subscription_token();
+
+    store_token(&mut transaction, token.subscriber_id, &subscription_token)
+        .await
+        .context("Failed to store the new confirmation token")?;
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the transaction to renew a subscription token")?;
+
+    send_confirmation_email(
+        &email_client,
+        &subscriber_email,
+        &application_base_url,
+        &subscription_token,
+    )
+    .await
+    .context("Failed to send a confirmation email")?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+struct SubscriptionToken {
+    subscriber_id: Uuid,
+    created_at: DateTime<Utc>,
+    consumed_at: Option<DateTime<Utc>>,
+}
+
+// The row stays locked until the transaction ends, so two concurrent confirmations of the same
+// token cannot both observe it as unused.
+#[tracing::instrument(name = "get_subscription_token", skip(transaction, subscription_token))]
+async fn get_subscription_token(
+    transaction: &mut Transaction<'_, Postgres>,
     subscription_token: &str,
-) -> Result<Option<Uuid>, sqlx::Error> {
-    let record = sqlx::query!(
-        "SELECT subscriber_id FROM subscriptions_tokens WHERE subscription_token = $1",
+) -> Result<Option<SubscriptionToken>, sqlx::Error> {
+    sqlx::query_as!(
+        SubscriptionToken,
+        r#"
+        SELECT subscriber_id, created_at, consumed_at
+        FROM subscriptions_tokens
+        WHERE subscription_token = $1
+        FOR UPDATE
+        "#,
         subscription_token
     )
-    .fetch_optional(db_connection_pool)
+    .fetch_optional(&mut **transaction)
     .await
-    .map_err(|error| {
-        tracing::error!("Failed to fetch subscriber_id from token: {}", error);
-        error
-    })?;
+}
 
-    Ok(record.map(|record| record.subscriber_id))
+struct PendingSubscriber {
+    email: String,
 }
 
-#[tracing::instrument(name = "confirm_subscriber", skip(db_connection_pool, subscriber_id))]
+#[tracing::instrument(name = "get_pending_subscriber", skip(transaction))]
+async fn get_pending_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<Option<PendingSubscriber>, sqlx::Error> {
+    sqlx::query_as!(
+        PendingSubscriber,
+        r#"
+        SELECT email
+        FROM subscriptions
+        WHERE id = $1 AND status = 'pending_confirmation'
+        FOR UPDATE
+        "#,
+        subscriber_id
+    )
+    .fetch_optional(&mut **transaction)
+    .await
+}
+
+#[tracing::instrument(name = "consume_token", skip(transaction, subscription_token))]
+async fn consume_token(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscription_token: &str,
+) -> Result<(), sqlx::Error> {
+    let query = sqlx::query!(
+        "UPDATE subscriptions_tokens SET consumed_at = now() WHERE subscription_token = $1",
+        subscription_token
+    );
+
+    transaction.execute(query).await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(name = "confirm_subscriber", skip(transaction, subscriber_id))]
 async fn confirm_subscriber(
-    db_connection_pool: &PgPool,
+    transaction: &mut Transaction<'_, Postgres>,
     subscriber_id: Uuid,
 ) -> Result<(), sqlx::Error> {
-    sqlx::query!(
+    let query = sqlx::query!(
         "UPDATE subscriptions SET status = 'confirmed' WHERE id = $1",
         subscriber_id
-    )
-    .execute(db_connection_pool)
-    .await
-    .map_err(|error| {
-        tracing::error!("Failed to update subscription status: {}", error);
-        error
-    })?;
+    );
+
+    transaction.execute(query).await?;
 
     Ok(())
 }
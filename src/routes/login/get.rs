@@ -1,20 +1,23 @@
+use crate::csrf::csrf_token_field;
+use crate::session_state::TypedSession;
 use actix_web::HttpResponse;
 use actix_web::http::header::ContentType;
-use actix_web_flash_messages::{IncomingFlashMessages, Level};
+use actix_web_flash_messages::IncomingFlashMessages;
 use std::fmt::Write;
 
-pub async fn login_form(flash_messages: IncomingFlashMessages) -> HttpResponse {
-    let mut error_html = String::new();
+pub async fn login_form(
+    session: TypedSession,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut messages_html = String::new();
 
-    let error_messages = flash_messages
-        .iter()
-        .filter(|message| message.level() == Level::Error);
-
-    for flash_message in error_messages {
-        writeln!(error_html, "<p><i>{}</i></p>", flash_message.content()).unwrap()
+    for flash_message in flash_messages.iter() {
+        writeln!(messages_html, "<p><i>{}</i></p>", flash_message.content()).unwrap()
     }
 
-    HttpResponse::Ok()
+    let csrf_token_field = csrf_token_field(&session)?;
+
+    Ok(HttpResponse::Ok()
         .content_type(ContentType::html())
         .body(format!(
             r#"
@@ -26,8 +29,9 @@ pub async fn login_form(flash_messages: IncomingFlashMessages) -> HttpResponse {
             </head>
             <body>
             <main>
-                {error_html}
+                {messages_html}
                 <form action="/login" method="post">
+                   {csrf_token_field}
                     <label>
                         Username
                         <input type="text" placeholder="Enter username" name="username"/>
@@ -40,9 +44,10 @@ pub async fn login_form(flash_messages: IncomingFlashMessages) -> HttpResponse {
 
                     <button type="submit">Login</button>
                 </form>
+                <p><a href="/password-reset">Forgot your password?</a></p>
             </main>
             </body>
             </html>
         "#
-        ))
+        )))
 }
@@ -1,13 +1,18 @@
-use crate::authentication::{AuthError, Credentials, validate_credentials};
+use crate::audit::{AuditAction, AuditEvent, AuditOutcome, record_audit_event};
+use crate::authentication::{
+    AuthError, Credentials, LoginThrottle, PasswordHashing, SessionMetadata, is_two_factor_enabled,
+    register_session, validate_credentials_with_throttling,
+};
 use crate::session_state::TypedSession;
-use crate::utils::error_chain_fmt;
+use crate::utils::{client_ip, error_chain_fmt};
 use actix_web::error::InternalError;
 use actix_web::http::StatusCode;
-use actix_web::http::header::LOCATION;
-use actix_web::{HttpResponse, ResponseError, web};
+use actix_web::http::header::{LOCATION, RETRY_AFTER};
+use actix_web::{HttpRequest, HttpResponse, ResponseError, web};
 use actix_web_flash_messages::FlashMessage;
 use secrecy::SecretString;
 use sqlx::PgPool;
+use uuid::Uuid;
 
 #[derive(serde::Deserialize)]
 pub struct FormData {
@@ -17,12 +22,15 @@ pub struct FormData {
 
 #[tracing::instrument(
     name = "login",
-    skip(request_body, db_pool, session),
+    skip(request_body, http_request, db_pool, login_throttle, password_hashing, session),
     fields(username=tracing::field::Empty, user_id=tracing::field::Empty)
 )]
 pub async fn login(
     request_body: web::Form<FormData>,
+    http_request: HttpRequest,
     db_pool: web::Data<PgPool>,
+    login_throttle: web::Data<LoginThrottle>,
+    password_hashing: web::Data<PasswordHashing>,
     session: TypedSession,
 ) -> Result<HttpResponse, InternalError<LoginError>> {
     let credentials = Credentials {
@@ -30,26 +38,94 @@ pub async fn login(
         password: request_body.0.password,
     };
 
-    tracing::Span::current().record("username", &tracing::field::display(&credentials.username));
+    tracing::Span::current().record("username", tracing::field::display(&credentials.username));
 
-    let result = validate_credentials(credentials, &db_pool).await;
+    let username = credentials.username.clone();
+
+    let client_ip = client_ip(&http_request);
+
+    let result = validate_credentials_with_throttling(
+        credentials,
+        client_ip,
+        &login_throttle,
+        &password_hashing,
+        &db_pool,
+    )
+    .await;
 
     match result {
         Ok(user_id) => {
-            tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
+            tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+
+            let two_factor_enabled = is_two_factor_enabled(&db_pool, user_id)
+                .await
+                .map_err(|error| login_redirect(LoginError::UnexpectedError(error)))?;
+
+            if two_factor_enabled {
+                session.renew();
+                session
+                    .insert_pending_two_factor_user_id(user_id)
+                    .map_err(|error| login_redirect(LoginError::UnexpectedError(error.into())))?;
+
+                return Ok(HttpResponse::SeeOther()
+                    .insert_header((LOCATION, "/login/two-factor"))
+                    .finish());
+            }
 
-            session.renew();
-            session
-                .insert_user_id(user_id)
-                .map_err(|error| login_redirect(LoginError::UnexpectedError(error.into())))?;
+            record_login(
+                &db_pool,
+                &http_request,
+                Some(user_id),
+                &username,
+                AuditOutcome::Success,
+            )
+            .await
+            .map_err(|error| login_redirect(LoginError::UnexpectedError(error)))?;
+
+            start_session(&session, &db_pool, user_id, &http_request)
+                .await
+                .map_err(|error| login_redirect(LoginError::UnexpectedError(error)))?;
 
             Ok(HttpResponse::SeeOther()
                 .insert_header((LOCATION, "/admin/dashboard"))
                 .finish())
         }
+        Err(AuthError::TooManyAttempts { retry_after }) => {
+            record_login(
+                &db_pool,
+                &http_request,
+                None,
+                &username,
+                AuditOutcome::Failure,
+            )
+            .await
+            .map_err(|error| login_redirect(LoginError::UnexpectedError(error)))?;
+
+            let response = HttpResponse::TooManyRequests()
+                .insert_header((RETRY_AFTER, retry_after.as_secs().max(1)))
+                .finish();
+
+            Err(InternalError::from_response(
+                LoginError::TooManyAttempts,
+                response,
+            ))
+        }
         Err(error) => {
+            if let AuthError::InvalidCredentials(_) = error {
+                record_login(
+                    &db_pool,
+                    &http_request,
+                    None,
+                    &username,
+                    AuditOutcome::Failure,
+                )
+                .await
+                .map_err(|error| login_redirect(LoginError::UnexpectedError(error)))?;
+            }
+
             let error_mapped = match error {
                 AuthError::InvalidCredentials(error) => LoginError::AuthError(error),
+                AuthError::TooManyAttempts { .. } => LoginError::TooManyAttempts,
                 AuthError::UnexpectedError(error) => LoginError::UnexpectedError(error),
             };
 
@@ -58,6 +134,46 @@ pub async fn login(
     }
 }
 
+pub async fn start_session(
+    session: &TypedSession,
+    db_pool: &PgPool,
+    user_id: Uuid,
+    http_request: &HttpRequest,
+) -> Result<(), anyhow::Error> {
+    let metadata = SessionMetadata::from_request(http_request);
+    let session_id = register_session(db_pool, user_id, &metadata).await?;
+
+    session.renew();
+    session.insert_user_id(user_id)?;
+    session.insert_session_id(session_id)?;
+
+    Ok(())
+}
+
+// Failed attempts are recorded under the username that was submitted, which may not exist.
+pub async fn record_login(
+    db_pool: &PgPool,
+    http_request: &HttpRequest,
+    user_id: Option<Uuid>,
+    username: &str,
+    outcome: AuditOutcome,
+) -> Result<(), anyhow::Error> {
+    let ip_address = client_ip(http_request);
+
+    record_audit_event(
+        db_pool,
+        &AuditEvent {
+            actor_user_id: user_id,
+            actor: username,
+            action: AuditAction::Login,
+            target: None,
+            ip_address: ip_address.as_deref(),
+            outcome,
+        },
+    )
+    .await
+}
+
 fn login_redirect(error: LoginError) -> InternalError<LoginError> {
     FlashMessage::error(error.to_string()).send();
 
@@ -72,6 +188,8 @@ fn login_redirect(error: LoginError) -> InternalError<LoginError> {
 pub enum LoginError {
     #[error("Authentication failed")]
     AuthError(#[source] anyhow::Error),
+    #[error("Too many failed login attempts. Try again later.")]
+    TooManyAttempts,
     #[error("Something went wrong")]
     UnexpectedError(#[from] anyhow::Error),
 }
@@ -87,6 +205,7 @@ impl ResponseError for LoginError {
         match self {
             LoginError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             LoginError::AuthError(_) => StatusCode::UNAUTHORIZED,
+            LoginError::TooManyAttempts => StatusCode::TOO_MANY_REQUESTS,
         }
     }
 }
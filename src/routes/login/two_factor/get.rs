@@ -0,0 +1,57 @@
+use crate::csrf::csrf_token_field;
+use crate::session_state::TypedSession;
+use crate::utils::see_other;
+use actix_web::HttpResponse;
+use actix_web::error::ErrorInternalServerError;
+use actix_web::http::header::ContentType;
+use actix_web_flash_messages::IncomingFlashMessages;
+use std::fmt::Write;
+
+pub async fn two_factor_login_form(
+    session: TypedSession,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    if session
+        .get_pending_two_factor_user_id()
+        .map_err(ErrorInternalServerError)?
+        .is_none()
+    {
+        return Ok(see_other("/login"));
+    }
+
+    let csrf_token_field = csrf_token_field(&session)?;
+    let mut messages_html = String::new();
+
+    for flash_message in flash_messages.iter() {
+        writeln!(messages_html, "<p><i>{}</i></p>", flash_message.content()).unwrap()
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"
+            <!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Two-factor authentication</title>
+            </head>
+            <body>
+            <main>
+                {messages_html}
+                <p>Enter the code from your authenticator app, or one of your recovery codes.</p>
+                <form action="/login/two-factor" method="post">
+                   {csrf_token_field}
+                    <label>
+                        Code
+                        <input type="text" placeholder="Enter code" name="code" autocomplete="one-time-code"/>
+                    </label>
+
+                    <button type="submit">Verify</button>
+                </form>
+            </main>
+            </body>
+            </html>
+        "#
+        )))
+}
@@ -0,0 +1,108 @@
+use crate::audit::AuditOutcome;
+use crate::authentication::{
+    AuthError, LoginThrottle, TotpSecretCipher, TwoFactorStatus, get_two_factor_status,
+    verify_second_factor_with_throttling,
+};
+use crate::routes::admin::dashboard::get_username;
+use crate::routes::login::post::{record_login, start_session};
+use crate::session_state::TypedSession;
+use crate::utils::{client_ip, see_other};
+use actix_web::error::ErrorInternalServerError;
+use actix_web::http::header::RETRY_AFTER;
+use actix_web::{HttpRequest, HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    code: String,
+}
+
+#[tracing::instrument(
+    name = "two_factor_login",
+    skip(form, http_request, session, db_pool, totp_secret_cipher, login_throttle),
+    fields(user_id=tracing::field::Empty)
+)]
+pub async fn two_factor_login(
+    form: web::Form<FormData>,
+    http_request: HttpRequest,
+    session: TypedSession,
+    db_pool: web::Data<PgPool>,
+    totp_secret_cipher: web::Data<TotpSecretCipher>,
+    login_throttle: web::Data<LoginThrottle>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(user_id) = session
+        .get_pending_two_factor_user_id()
+        .map_err(ErrorInternalServerError)?
+    else {
+        return Ok(see_other("/login"));
+    };
+
+    tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+
+    let TwoFactorStatus::Enabled(totp) =
+        get_two_factor_status(&db_pool, &totp_secret_cipher, user_id)
+            .await
+            .map_err(ErrorInternalServerError)?
+    else {
+        session.log_out();
+        return Ok(see_other("/login"));
+    };
+
+    let result = verify_second_factor_with_throttling(
+        &totp,
+        user_id,
+        &form.code,
+        client_ip(&http_request),
+        &login_throttle,
+        &db_pool,
+    )
+    .await;
+
+    let username = get_username(user_id, &db_pool)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    if let Err(error) = result {
+        let response = match error {
+            AuthError::InvalidCredentials(_) => {
+                FlashMessage::error("The authentication code is invalid.").send();
+
+                see_other("/login/two-factor")
+            }
+            AuthError::TooManyAttempts { retry_after } => HttpResponse::TooManyRequests()
+                .insert_header((RETRY_AFTER, retry_after.as_secs().max(1)))
+                .finish(),
+            AuthError::UnexpectedError(error) => return Err(ErrorInternalServerError(error)),
+        };
+
+        record_login(
+            &db_pool,
+            &http_request,
+            Some(user_id),
+            &username,
+            AuditOutcome::Failure,
+        )
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+        return Ok(response);
+    }
+
+    record_login(
+        &db_pool,
+        &http_request,
+        Some(user_id),
+        &username,
+        AuditOutcome::Success,
+    )
+    .await
+    .map_err(ErrorInternalServerError)?;
+
+    session.remove_pending_two_factor_user_id();
+    start_session(&session, &db_pool, user_id, &http_request)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    Ok(see_other("/admin/dashboard"))
+}
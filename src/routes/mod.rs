@@ -4,4 +4,6 @@ pub mod health_check;
 pub mod home;
 pub mod login;
 pub mod newsletters;
+pub mod password_reset;
 pub mod subscriptions;
+pub mod unsubscribe;
@@ -1,21 +1,55 @@
-use crate::authentication::{AuthError, Credentials, validate_credentials};
-use crate::domain::SubscriberEmail;
-use crate::email_client::EmailClient;
-use crate::utils::error_chain_fmt;
+use crate::audit::{AuditAction, AuditEvent, AuditOutcome, record_audit_event};
+use crate::authentication::{
+    ApiTokenGrant, ApiTokenScope, AuthError, Credentials, LoginThrottle, PasswordHashing, Role,
+    get_active_user_role, is_two_factor_enabled, validate_api_token,
+    validate_credentials_with_throttling,
+};
+use crate::deliveries::{DeliveryCounts, get_delivery_counts};
+use crate::idempotency::{IdempotencyKey, NextAction, save_response, try_processing};
+use crate::newsletter_issues::{
+    IssueContent, IssueStatus, Publisher, cancel_scheduled_issue, get_newsletter_issue,
+    publish_issue, schedule_issue, schedule_new_issue,
+};
+use crate::routes::admin::dashboard::get_username;
+use crate::utils::{client_ip, error_chain_fmt};
 use actix_web::http::header::{HeaderMap, HeaderValue};
 use actix_web::http::{StatusCode, header};
 use actix_web::{HttpRequest, HttpResponse, ResponseError, web};
 use anyhow::Context;
 use base64::Engine;
+use chrono::{DateTime, Utc};
 use secrecy::SecretString;
 use serde::Deserialize;
 use sqlx::PgPool;
 use std::fmt::Debug;
+use uuid::Uuid;
 
 #[derive(Debug, Deserialize)]
 pub struct PublishNewsletterRequestBody {
     title: String,
     content: PublishNewsletterRequestBodyContent,
+    // RFC 3339, so that the time can be given in the audience's own offset.
+    scheduled_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScheduleRequestBody {
+    scheduled_at: DateTime<Utc>,
+}
+
+#[derive(serde::Serialize)]
+struct IssueStatusResponse {
+    newsletter_issue_id: Uuid,
+    status: &'static str,
+    scheduled_at: Option<DateTime<Utc>>,
+}
+
+#[derive(serde::Serialize)]
+struct DeliveryReportResponse {
+    newsletter_issue_id: Uuid,
+    status: &'static str,
+    #[serde(flatten)]
+    counts: DeliveryCounts,
 }
 
 #[derive(Debug, Deserialize)]
@@ -28,6 +62,22 @@ pub struct PublishNewsletterRequestBodyContent {
 pub enum PublishNewsletterError {
     #[error("Authentication failed")]
     AuthError(#[source] anyhow::Error),
+    #[error("Too many failed authentication attempts")]
+    TooManyAttempts { retry_after: std::time::Duration },
+    #[error("Accounts with two-factor authentication must use an API token")]
+    TwoFactorRequired,
+    #[error("The API token does not grant the {} scope", .0.as_str())]
+    MissingScope(ApiTokenScope),
+    #[error("This action requires the {} role", .0.as_str())]
+    InsufficientRole(Role),
+    #[error("{0}")]
+    ValidationError(String),
+    #[error("A request with the same idempotency key is still being processed")]
+    Conflict,
+    #[error("The newsletter issue does not exist")]
+    IssueNotFound,
+    #[error("{0}")]
+    InvalidIssueState(&'static str),
     #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
 }
@@ -43,14 +93,28 @@ impl ResponseError for PublishNewsletterError {
         match self {
             PublishNewsletterError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             PublishNewsletterError::AuthError(_) => StatusCode::UNAUTHORIZED,
+            PublishNewsletterError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            PublishNewsletterError::Conflict | PublishNewsletterError::InvalidIssueState(_) => {
+                StatusCode::CONFLICT
+            }
+            PublishNewsletterError::IssueNotFound => StatusCode::NOT_FOUND,
+            PublishNewsletterError::TooManyAttempts { .. } => StatusCode::TOO_MANY_REQUESTS,
+            PublishNewsletterError::MissingScope(_)
+            | PublishNewsletterError::InsufficientRole(_)
+            | PublishNewsletterError::TwoFactorRequired => StatusCode::FORBIDDEN,
         }
     }
 
     fn error_response(&self) -> HttpResponse {
         match self {
-            PublishNewsletterError::UnexpectedError(_) => {
-                HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR)
-            }
+            PublishNewsletterError::UnexpectedError(_)
+            | PublishNewsletterError::ValidationError(_)
+            | PublishNewsletterError::MissingScope(_)
+            | PublishNewsletterError::InsufficientRole(_)
+            | PublishNewsletterError::TwoFactorRequired
+            | PublishNewsletterError::Conflict
+            | PublishNewsletterError::IssueNotFound
+            | PublishNewsletterError::InvalidIssueState(_) => HttpResponse::new(self.status_code()),
             PublishNewsletterError::AuthError(_) => {
                 let mut response = HttpResponse::new(StatusCode::UNAUTHORIZED);
 
@@ -63,57 +127,469 @@ impl ResponseError for PublishNewsletterError {
 
                 response
             }
+            PublishNewsletterError::TooManyAttempts { retry_after } => {
+                HttpResponse::build(self.status_code())
+                    .insert_header((header::RETRY_AFTER, retry_after.as_secs().max(1)))
+                    .finish()
+            }
         }
     }
 }
 
 #[tracing::instrument(
     name = "publish_newsletter",
-    skip(db_connection_pool, email_client, http_request)
+    skip(db_connection_pool, login_throttle, password_hashing, http_request),
+    fields(user_id=tracing::field::Empty)
 )]
 pub async fn publish_newsletter(
     request_body: web::Json<PublishNewsletterRequestBody>,
     db_connection_pool: web::Data<PgPool>,
-    email_client: web::Data<EmailClient>,
+    login_throttle: web::Data<LoginThrottle>,
+    password_hashing: web::Data<PasswordHashing>,
+    http_request: HttpRequest,
+) -> Result<HttpResponse, PublishNewsletterError> {
+    let ip_address = client_ip(&http_request);
+    let (user_id, username) = authenticate_and_authorize(
+        &http_request,
+        &db_connection_pool,
+        &login_throttle,
+        &password_hashing,
+        ip_address.as_deref(),
+        Access::Publish,
+    )
+    .await?;
+
+    let scheduled_at = request_body.scheduled_at;
+
+    if scheduled_at.is_some_and(|scheduled_at| scheduled_at <= Utc::now()) {
+        return Err(PublishNewsletterError::ValidationError(
+            "The scheduled time must be in the future".into(),
+        ));
+    }
+
+    let idempotency_key = get_idempotency_key(http_request.headers())
+        .map_err(PublishNewsletterError::ValidationError)?;
+
+    let mut transaction = match &idempotency_key {
+        Some(idempotency_key) => {
+            match try_processing(&db_connection_pool, idempotency_key, user_id).await? {
+                NextAction::StartProcessing(transaction) => transaction,
+                NextAction::ReturnSavedResponse(saved_response) => return Ok(saved_response),
+                NextAction::RequestInProgress => return Err(PublishNewsletterError::Conflict),
+            }
+        }
+        None => db_connection_pool
+            .begin()
+            .await
+            .context("Failed to get a database connection from the pool")?,
+    };
+
+    let content = IssueContent {
+        title: &request_body.title,
+        text_content: &request_body.content.text,
+        html_content: &request_body.content.html,
+    };
+    let publisher = Publisher {
+        user_id: Some(user_id),
+        username: &username,
+        ip_address: ip_address.as_deref(),
+    };
+
+    let (newsletter_issue_id, status) = match scheduled_at {
+        Some(scheduled_at) => (
+            schedule_new_issue(&mut transaction, &content, scheduled_at, &publisher).await?,
+            IssueStatus::Scheduled,
+        ),
+        None => (
+            publish_issue(&mut transaction, &content, &publisher).await?,
+            IssueStatus::Sending,
+        ),
+    };
+
+    let response = HttpResponse::Accepted().json(IssueStatusResponse {
+        newsletter_issue_id,
+        status: status.as_str(),
+        scheduled_at,
+    });
+
+    match idempotency_key {
+        Some(idempotency_key) => {
+            let response = save_response(transaction, &idempotency_key, user_id, response).await?;
+
+            Ok(response)
+        }
+        None => {
+            transaction
+                .commit()
+                .await
+                .context("Failed to commit the transaction to publish a newsletter issue")?;
+
+            Ok(response)
+        }
+    }
+}
+
+#[tracing::instrument(
+    name = "reschedule_newsletter",
+    skip(request_body, db_connection_pool, login_throttle, password_hashing, http_request),
+    fields(user_id=tracing::field::Empty)
+)]
+pub async fn reschedule_newsletter(
+    newsletter_issue_id: web::Path<Uuid>,
+    request_body: web::Json<ScheduleRequestBody>,
+    db_connection_pool: web::Data<PgPool>,
+    login_throttle: web::Data<LoginThrottle>,
+    password_hashing: web::Data<PasswordHashing>,
     http_request: HttpRequest,
 ) -> Result<HttpResponse, PublishNewsletterError> {
-    let basic_credentials =
-        get_basic_credentials(http_request.headers()).map_err(PublishNewsletterError::AuthError)?;
+    let newsletter_issue_id = newsletter_issue_id.into_inner();
+    let ip_address = client_ip(&http_request);
+    let (user_id, username) = authenticate_and_authorize(
+        &http_request,
+        &db_connection_pool,
+        &login_throttle,
+        &password_hashing,
+        ip_address.as_deref(),
+        Access::Publish,
+    )
+    .await?;
 
-    validate_credentials(basic_credentials, db_connection_pool.as_ref())
+    let scheduled_at = request_body.scheduled_at;
+
+    if scheduled_at <= Utc::now() {
+        return Err(PublishNewsletterError::ValidationError(
+            "The scheduled time must be in the future".into(),
+        ));
+    }
+
+    get_newsletter_issue(&db_connection_pool, newsletter_issue_id)
+        .await?
+        .ok_or(PublishNewsletterError::IssueNotFound)?;
+
+    let scheduler = Publisher {
+        user_id: Some(user_id),
+        username: &username,
+        ip_address: ip_address.as_deref(),
+    };
+
+    if !schedule_issue(
+        &db_connection_pool,
+        newsletter_issue_id,
+        scheduled_at,
+        &scheduler,
+    )
+    .await?
+    {
+        return Err(PublishNewsletterError::InvalidIssueState(
+            "The newsletter issue has already been published",
+        ));
+    }
+
+    Ok(HttpResponse::Ok().json(IssueStatusResponse {
+        newsletter_issue_id,
+        status: IssueStatus::Scheduled.as_str(),
+        scheduled_at: Some(scheduled_at),
+    }))
+}
+
+#[tracing::instrument(
+    name = "cancel_newsletter_schedule",
+    skip(db_connection_pool, login_throttle, password_hashing, http_request),
+    fields(user_id=tracing::field::Empty)
+)]
+pub async fn cancel_newsletter_schedule(
+    newsletter_issue_id: web::Path<Uuid>,
+    db_connection_pool: web::Data<PgPool>,
+    login_throttle: web::Data<LoginThrottle>,
+    password_hashing: web::Data<PasswordHashing>,
+    http_request: HttpRequest,
+) -> Result<HttpResponse, PublishNewsletterError> {
+    let newsletter_issue_id = newsletter_issue_id.into_inner();
+    let ip_address = client_ip(&http_request);
+    let (user_id, username) = authenticate_and_authorize(
+        &http_request,
+        &db_connection_pool,
+        &login_throttle,
+        &password_hashing,
+        ip_address.as_deref(),
+        Access::Publish,
+    )
+    .await?;
+
+    get_newsletter_issue(&db_connection_pool, newsletter_issue_id)
+        .await?
+        .ok_or(PublishNewsletterError::IssueNotFound)?;
+
+    let canceller = Publisher {
+        user_id: Some(user_id),
+        username: &username,
+        ip_address: ip_address.as_deref(),
+    };
+
+    if !cancel_scheduled_issue(&db_connection_pool, newsletter_issue_id, &canceller).await? {
+        return Err(PublishNewsletterError::InvalidIssueState(
+            "The newsletter issue is not scheduled",
+        ));
+    }
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[tracing::instrument(
+    name = "get_delivery_report",
+    skip(db_connection_pool, login_throttle, password_hashing, http_request),
+    fields(user_id=tracing::field::Empty)
+)]
+pub async fn get_delivery_report(
+    newsletter_issue_id: web::Path<Uuid>,
+    db_connection_pool: web::Data<PgPool>,
+    login_throttle: web::Data<LoginThrottle>,
+    password_hashing: web::Data<PasswordHashing>,
+    http_request: HttpRequest,
+) -> Result<HttpResponse, PublishNewsletterError> {
+    let newsletter_issue_id = newsletter_issue_id.into_inner();
+    let ip_address = client_ip(&http_request);
+    authenticate_and_authorize(
+        &http_request,
+        &db_connection_pool,
+        &login_throttle,
+        &password_hashing,
+        ip_address.as_deref(),
+        Access::ReadDeliveries,
+    )
+    .await?;
+
+    let issue = get_newsletter_issue(&db_connection_pool, newsletter_issue_id)
+        .await?
+        .ok_or(PublishNewsletterError::IssueNotFound)?;
+    let counts = get_delivery_counts(&db_connection_pool, newsletter_issue_id)
         .await
-        .map_err(|error| match error {
-            AuthError::InvalidCredentials(error) => PublishNewsletterError::AuthError(error),
-            AuthError::UnexpectedError(error) => PublishNewsletterError::UnexpectedError(error),
-        })?;
-
-    let subscribers = get_confirmed_subscribers(&db_connection_pool).await?;
-
-    for subscriber_result in subscribers {
-        match subscriber_result {
-            Ok(subscriber) => {
-                email_client
-                    .send_email(
-                        &subscriber.email,
-                        &request_body.title,
-                        &request_body.content.html,
-                        &request_body.content.text,
-                    )
-                    .await
-                    .with_context(|| {
-                        format!("Failed to send newsletter to {}", subscriber.email)
-                    })?;
+        .context("Failed to count the deliveries of the newsletter issue")?;
+
+    Ok(HttpResponse::Ok().json(DeliveryReportResponse {
+        newsletter_issue_id,
+        status: issue.status.as_str(),
+        counts,
+    }))
+}
+
+// What an endpoint requires of the caller. Reading a delivery report changes nothing, so viewers
+// may do it and failed attempts are not audited as publications.
+#[derive(Clone, Copy)]
+enum Access {
+    Publish,
+    ReadDeliveries,
+}
+
+impl Access {
+    fn required_scope(self) -> ApiTokenScope {
+        match self {
+            Access::Publish => ApiTokenScope::PublishNewsletters,
+            Access::ReadDeliveries => ApiTokenScope::ReadDeliveries,
+        }
+    }
+
+    fn required_role(self) -> Role {
+        match self {
+            Access::Publish => Role::Editor,
+            Access::ReadDeliveries => Role::Viewer,
+        }
+    }
+
+    fn audited_action(self) -> Option<AuditAction> {
+        match self {
+            Access::Publish => Some(AuditAction::PublishNewsletter),
+            Access::ReadDeliveries => None,
+        }
+    }
+}
+
+async fn authenticate_and_authorize(
+    http_request: &HttpRequest,
+    db_connection_pool: &PgPool,
+    login_throttle: &LoginThrottle,
+    password_hashing: &PasswordHashing,
+    ip_address: Option<&str>,
+    access: Access,
+) -> Result<(Uuid, String), PublishNewsletterError> {
+    let authenticated_publisher = match authenticate_publisher(
+        http_request,
+        db_connection_pool,
+        login_throttle,
+        password_hashing,
+        ip_address.map(str::to_owned),
+    )
+    .await
+    {
+        Ok(publisher) => publisher,
+        Err(error) => {
+            if let PublishNewsletterError::AuthError(_)
+            | PublishNewsletterError::TooManyAttempts { .. }
+            | PublishNewsletterError::TwoFactorRequired = error
+                && let Some(action) = access.audited_action()
+            {
+                let event = AuditEvent {
+                    actor_user_id: None,
+                    actor: &requested_actor(http_request.headers()),
+                    action,
+                    target: None,
+                    ip_address,
+                    outcome: AuditOutcome::Failure,
+                };
+                record_audit_event(db_connection_pool, &event).await?;
             }
-            Err(error) => {
-                tracing::warn!(
-                    error.cause_chain = ?error,
-                    "Skipping a confirmed subscriber. Their stored contact details are invalid",
-                )
+
+            return Err(error);
+        }
+    };
+
+    let user_id = authenticated_publisher.user_id();
+
+    tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+
+    let username = get_username(user_id, db_connection_pool).await?;
+
+    if let Err(error) = authorize(&authenticated_publisher, access, db_connection_pool).await {
+        if let PublishNewsletterError::MissingScope(_) | PublishNewsletterError::InsufficientRole(_) =
+            error
+            && let Some(action) = access.audited_action()
+        {
+            let event = AuditEvent {
+                actor_user_id: Some(user_id),
+                actor: &username,
+                action,
+                target: None,
+                ip_address,
+                outcome: AuditOutcome::Failure,
+            };
+            record_audit_event(db_connection_pool, &event).await?;
+        }
+
+        return Err(error);
+    }
+
+    Ok((user_id, username))
+}
+
+enum AuthenticatedPublisher {
+    ApiToken(ApiTokenGrant),
+    User(Uuid),
+}
+
+impl AuthenticatedPublisher {
+    fn user_id(&self) -> Uuid {
+        match self {
+            AuthenticatedPublisher::ApiToken(grant) => grant.user_id,
+            AuthenticatedPublisher::User(user_id) => *user_id,
+        }
+    }
+}
+
+async fn authenticate_publisher(
+    http_request: &HttpRequest,
+    db_connection_pool: &PgPool,
+    login_throttle: &LoginThrottle,
+    password_hashing: &PasswordHashing,
+    client_ip: Option<String>,
+) -> Result<AuthenticatedPublisher, PublishNewsletterError> {
+    match get_bearer_token(http_request.headers()) {
+        Some(token) => {
+            let grant = validate_api_token(token, db_connection_pool)
+                .await
+                .map_err(map_auth_error)?;
+
+            Ok(AuthenticatedPublisher::ApiToken(grant))
+        }
+        None => {
+            let basic_credentials = get_basic_credentials(http_request.headers())
+                .map_err(PublishNewsletterError::AuthError)?;
+
+            let user_id = validate_credentials_with_throttling(
+                basic_credentials,
+                client_ip,
+                login_throttle,
+                password_hashing,
+                db_connection_pool,
+            )
+            .await
+            .map_err(map_auth_error)?;
+
+            // A password alone would otherwise get around the second factor.
+            if is_two_factor_enabled(db_connection_pool, user_id).await? {
+                return Err(PublishNewsletterError::TwoFactorRequired);
             }
+
+            Ok(AuthenticatedPublisher::User(user_id))
         }
     }
+}
+
+async fn authorize(
+    publisher: &AuthenticatedPublisher,
+    access: Access,
+    db_connection_pool: &PgPool,
+) -> Result<(), PublishNewsletterError> {
+    if let AuthenticatedPublisher::ApiToken(grant) = publisher
+        && !grant.has_scope(access.required_scope())
+    {
+        return Err(PublishNewsletterError::MissingScope(
+            access.required_scope(),
+        ));
+    }
 
-    Ok(HttpResponse::Ok().finish())
+    let role = get_active_user_role(db_connection_pool, publisher.user_id()).await?;
+
+    if role.is_none_or(|role| role < access.required_role()) {
+        return Err(PublishNewsletterError::InsufficientRole(
+            access.required_role(),
+        ));
+    }
+
+    Ok(())
+}
+
+// Rejected requests are recorded under the identity they claimed. API tokens are never stored,
+// not even in part.
+fn requested_actor(headers: &HeaderMap) -> String {
+    if get_bearer_token(headers).is_some() {
+        return "API token".into();
+    }
+
+    get_basic_credentials(headers)
+        .map(|credentials| credentials.username)
+        .unwrap_or_else(|_| "anonymous".into())
+}
+
+fn map_auth_error(error: AuthError) -> PublishNewsletterError {
+    match error {
+        AuthError::InvalidCredentials(error) => PublishNewsletterError::AuthError(error),
+        AuthError::TooManyAttempts { retry_after } => {
+            PublishNewsletterError::TooManyAttempts { retry_after }
+        }
+        AuthError::UnexpectedError(error) => PublishNewsletterError::UnexpectedError(error),
+    }
+}
+
+#[tracing::instrument(name = "get_idempotency_key", skip(headers))]
+fn get_idempotency_key(headers: &HeaderMap) -> Result<Option<IdempotencyKey>, String> {
+    let Some(header_value) = headers.get("Idempotency-Key") else {
+        return Ok(None);
+    };
+
+    let idempotency_key = header_value
+        .to_str()
+        .map_err(|_| "The Idempotency-Key header is not a valid UTF-8 string".to_string())?;
+
+    IdempotencyKey::try_from(idempotency_key.to_owned()).map(Some)
+}
+
+// Basic credentials remain accepted for clients that have not moved to API tokens yet.
+fn get_bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get("Authorization")?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
 }
 
 #[tracing::instrument(name = "get_basic_credentials", skip(headers))]
@@ -151,26 +627,3 @@ fn get_basic_credentials(headers: &HeaderMap) -> Result<Credentials, anyhow::Err
         password: SecretString::from(password),
     })
 }
-
-struct ConfirmedSubscriber {
-    email: SubscriberEmail,
-}
-
-#[tracing::instrument(name = "get_confirmed_subscribers", skip(db_connection_pool))]
-async fn get_confirmed_subscribers(
-    db_connection_pool: &PgPool,
-) -> Result<Vec<Result<ConfirmedSubscriber, anyhow::Error>>, anyhow::Error> {
-    let rows = sqlx::query!("SELECT email FROM subscriptions WHERE status = 'confirmed'")
-        .fetch_all(db_connection_pool)
-        .await?;
-
-    let rows_mapped = rows
-        .into_iter()
-        .map(|row| match SubscriberEmail::parse(row.email) {
-            Ok(email) => Ok(ConfirmedSubscriber { email }),
-            Err(error) => Err(anyhow::anyhow!(error)),
-        })
-        .collect();
-
-    Ok(rows_mapped)
-}
@@ -0,0 +1,116 @@
+use crate::authentication::find_password_reset_token_owner;
+use crate::utils::see_other;
+use actix_web::error::ErrorInternalServerError;
+use actix_web::http::header::ContentType;
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use sqlx::PgPool;
+use std::fmt::Write;
+
+pub async fn forgot_password_form(flash_messages: IncomingFlashMessages) -> HttpResponse {
+    let mut messages_html = String::new();
+
+    for flash_message in flash_messages.iter() {
+        writeln!(messages_html, "<p><i>{}</i></p>", flash_message.content()).unwrap()
+    }
+
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"
+            <!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Forgot password</title>
+            </head>
+            <body>
+            <main>
+                {messages_html}
+                <p>Enter your username and we will email you a link to reset your password.</p>
+                <form action="/password-reset" method="post">
+                    <label>
+                        Username
+                        <input type="text" placeholder="Enter username" name="username"/>
+                    </label>
+
+                    <button type="submit">Send reset link</button>
+                </form>
+                <p><a href="/login">&lt;- Back to login</a></p>
+            </main>
+            </body>
+            </html>
+        "#
+        ))
+}
+
+#[derive(serde::Deserialize)]
+pub struct Parameters {
+    token: String,
+}
+
+#[tracing::instrument(
+    name = "reset_password_form",
+    skip(parameters, flash_messages, db_connection_pool)
+)]
+pub async fn reset_password_form(
+    parameters: web::Query<Parameters>,
+    flash_messages: IncomingFlashMessages,
+    db_connection_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let token_owner = find_password_reset_token_owner(&db_connection_pool, &parameters.token)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    if token_owner.is_none() {
+        FlashMessage::error("The password reset link is invalid or has expired.").send();
+
+        return Ok(see_other("/password-reset"));
+    }
+
+    let mut messages_html = String::new();
+
+    for flash_message in flash_messages.iter() {
+        writeln!(messages_html, "<p><i>{}</i></p>", flash_message.content()).unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"
+        <!DOCTYPE html>
+        <html lang="en">
+           <head>
+              <meta http-equiv="content-type" content="text/html; charset=utf-8" />
+              <title>Reset Password</title>
+           </head>
+           <body>
+              {messages_html}
+              <form action="/password-reset/confirm" method="post">
+                 <input type="hidden" name="token" value="{token}" />
+                 <label
+                    >New password
+                    <input
+                       type="password"
+                       placeholder="Enter new password"
+                       name="new_password"
+                    />
+                 </label>
+                 <br />
+                 <label
+                    >Confirm new password
+                    <input
+                       type="password"
+                       placeholder="Type the new password again"
+                       name="new_password_check"
+                    />
+                 </label>
+                 <br />
+                 <button type="submit">Reset password</button>
+              </form>
+           </body>
+        </html>
+        "#,
+            token = parameters.token
+        )))
+}
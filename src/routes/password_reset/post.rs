@@ -0,0 +1,265 @@
+use crate::application::{ApplicationBaseUrl, PasswordResetTokenTtl};
+use crate::authentication::{
+    AuthError, PasswordHashing, RateLimit, change_password, consume_password_reset_token,
+    enforce_rate_limit, issue_password_reset_token, revoke_all_sessions, validate_new_password,
+};
+use crate::domain::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::utils::{client_ip, see_other};
+use actix_web::error::ErrorInternalServerError;
+use actix_web::{HttpRequest, HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use secrecy::SecretString;
+use sqlx::PgPool;
+use tracing::Instrument;
+use uuid::Uuid;
+
+#[derive(serde::Deserialize)]
+pub struct RequestFormData {
+    username: String,
+}
+
+// Anyone can ask for a reset email to be sent to someone else's address, so requests are limited
+// both for the requester and for the account.
+const PASSWORD_RESET_RATE_LIMIT_PER_IP: RateLimit = RateLimit {
+    max_attempts: 20,
+    window: chrono::Duration::hours(1),
+};
+const PASSWORD_RESET_RATE_LIMIT_PER_ACCOUNT: RateLimit = RateLimit {
+    max_attempts: 3,
+    window: chrono::Duration::hours(1),
+};
+
+#[tracing::instrument(
+    name = "request_password_reset",
+    skip(
+        form,
+        http_request,
+        db_connection_pool,
+        email_client,
+        application_base_url,
+        password_reset_token_ttl
+    ),
+    fields(username = %form.username)
+)]
+pub async fn request_password_reset(
+    form: web::Form<RequestFormData>,
+    http_request: HttpRequest,
+    db_connection_pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    application_base_url: web::Data<ApplicationBaseUrl>,
+    password_reset_token_ttl: web::Data<PasswordResetTokenTtl>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let recipient = get_user_email(&db_connection_pool, &form.username)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    // Unknown usernames are limited just like accounts, so that being limited reveals nothing.
+    let target = match &recipient {
+        Some((_, email)) => email.to_lowercase(),
+        None => form.username.clone(),
+    };
+    let mut rate_limits = Vec::new();
+
+    if let Some(client_ip) = client_ip(&http_request) {
+        rate_limits.push((
+            format!("password-reset-ip:{}", client_ip),
+            &PASSWORD_RESET_RATE_LIMIT_PER_IP,
+        ));
+    }
+
+    rate_limits.push((
+        format!("password-reset:{}", target),
+        &PASSWORD_RESET_RATE_LIMIT_PER_ACCOUNT,
+    ));
+
+    for (throttling_key, rate_limit) in rate_limits {
+        match enforce_rate_limit(&throttling_key, rate_limit, &db_connection_pool).await {
+            Ok(()) => {}
+            Err(AuthError::TooManyAttempts { retry_after }) => {
+                FlashMessage::error(format!(
+                    "Too many password reset requests. Try again in {} minutes.",
+                    retry_after.as_secs().div_ceil(60).max(1)
+                ))
+                .send();
+
+                return Ok(see_other("/password-reset"));
+            }
+            Err(error) => return Err(ErrorInternalServerError(error)),
+        }
+    }
+
+    // The response is the same whether or not the account exists, so that this form cannot be
+    // used to find out which usernames are registered. The email is sent in the background for
+    // the response to take as long either way.
+    if let Some((user_id, email)) = recipient {
+        let db_connection_pool = db_connection_pool.clone();
+        let email_client = email_client.clone();
+        let application_base_url = application_base_url.clone();
+        let password_reset_token_ttl = password_reset_token_ttl.0;
+
+        tokio::spawn(
+            async move {
+                if let Err(error) = send_password_reset_email(
+                    &db_connection_pool,
+                    &email_client,
+                    &application_base_url,
+                    user_id,
+                    email,
+                    password_reset_token_ttl,
+                )
+                .await
+                {
+                    tracing::error!(
+                        error.cause_chain = ?error,
+                        error.message = %error,
+                        "Failed to send a password reset email",
+                    );
+                }
+            }
+            .instrument(tracing::Span::current()),
+        );
+    }
+
+    FlashMessage::info(
+        "If the account exists, a password reset link has been sent to its email address.",
+    )
+    .send();
+
+    Ok(see_other("/login"))
+}
+
+#[derive(serde::Deserialize)]
+pub struct ResetFormData {
+    token: String,
+    new_password: SecretString,
+    new_password_check: SecretString,
+}
+
+#[tracing::instrument(
+    name = "reset_password",
+    skip(form, db_connection_pool, password_hashing),
+    fields(user_id = tracing::field::Empty)
+)]
+pub async fn reset_password(
+    form: web::Form<ResetFormData>,
+    db_connection_pool: web::Data<PgPool>,
+    password_hashing: web::Data<PasswordHashing>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if let Err(error_message) = validate_new_password(&form.new_password, &form.new_password_check)
+    {
+        FlashMessage::error(error_message).send();
+
+        return Ok(see_other(&format!(
+            "/password-reset/confirm?token={}",
+            urlencoding::encode(&form.token)
+        )));
+    }
+
+    let mut transaction = db_connection_pool
+        .begin()
+        .await
+        .context("Failed to get a database connection from the pool")
+        .map_err(ErrorInternalServerError)?;
+
+    let Some(user_id) = consume_password_reset_token(&mut transaction, &form.token)
+        .await
+        .map_err(ErrorInternalServerError)?
+    else {
+        FlashMessage::error("The password reset link is invalid or has expired.").send();
+
+        return Ok(see_other("/password-reset"));
+    };
+
+    tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+
+    change_password(
+        user_id,
+        form.0.new_password,
+        &password_hashing,
+        &mut *transaction,
+    )
+    .await
+    .map_err(ErrorInternalServerError)?;
+
+    revoke_all_sessions(&mut *transaction, user_id)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the transaction to reset a password")
+        .map_err(ErrorInternalServerError)?;
+
+    FlashMessage::info("Your password has been reset. You can now log in with it.").send();
+
+    Ok(see_other("/login"))
+}
+
+#[tracing::instrument(name = "get_user_email", skip(db_connection_pool))]
+async fn get_user_email(
+    db_connection_pool: &PgPool,
+    username: &str,
+) -> Result<Option<(Uuid, String)>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT user_id, email
+        FROM users
+        WHERE username = $1 AND disabled_at IS NULL
+        "#,
+        username
+    )
+    .fetch_optional(db_connection_pool)
+    .await
+    .context("Failed to retrieve the user's email")?;
+
+    Ok(row.and_then(|row| row.email.map(|email| (row.user_id, email))))
+}
+
+#[tracing::instrument(
+    name = "send_password_reset_email",
+    skip(db_connection_pool, email_client, application_base_url, email)
+)]
+async fn send_password_reset_email(
+    db_connection_pool: &PgPool,
+    email_client: &EmailClient,
+    application_base_url: &ApplicationBaseUrl,
+    user_id: Uuid,
+    email: String,
+    ttl: chrono::Duration,
+) -> Result<(), anyhow::Error> {
+    let recipient = SubscriberEmail::parse(email).map_err(|error| anyhow::anyhow!(error))?;
+    let token = issue_password_reset_token(db_connection_pool, user_id, ttl).await?;
+
+    let reset_link = format!(
+        "{}/password-reset/confirm?token={}",
+        application_base_url.0, token
+    );
+
+    let html_content = format!(
+        "We received a request to reset your password.<br />\
+        Click <a href=\"{}\">here</a> to choose a new one. \
+        If you did not request it, you can safely ignore this email.",
+        reset_link
+    );
+
+    let text_content = format!(
+        "We received a request to reset your password.\nVisit {} to choose a new one. \
+        If you did not request it, you can safely ignore this email.",
+        reset_link
+    );
+
+    email_client
+        .send_email(
+            &recipient,
+            "Reset your password",
+            &html_content,
+            &text_content,
+        )
+        .await
+        .context("Failed to send the password reset email")?;
+
+    Ok(())
+}
@@ -1,6 +1,6 @@
 use crate::application::ApplicationBaseUrl;
 use crate::domain::{NewSubscriber, SubscriberEmail, SubscriberName};
-use crate::email_client::EmailClient;
+use crate::email_client::{EmailClient, SendEmailError};
 use crate::utils::error_chain_fmt;
 use actix_web::http::StatusCode;
 use actix_web::{HttpResponse, ResponseError, web};
@@ -94,14 +94,39 @@ pub async fn subscribe_controller(
         .await
         .context("Failed to get a database connection from the pool")?;
 
-    let subscriber_id = insert_subscriber(&mut transaction, &new_subscriber)
+    let existing_subscriber = get_existing_subscriber(&mut transaction, &new_subscriber.email)
         .await
-        .context("Failed to insert subscriber in the database")?;
+        .context("Failed to look up an existing subscriber with the same email")?;
+
+    let subscriber_id = match existing_subscriber {
+        Some(ExistingSubscriber { status, .. }) if status == "confirmed" => {
+            transaction
+                .commit()
+                .await
+                .context("Failed to commit the transaction to look up a subscriber")?;
+
+            send_already_subscribed_email(&email_client, new_subscriber)
+                .await
+                .context("Failed to send an already subscribed notice")?;
+
+            return Ok(HttpResponse::Ok().finish());
+        }
+        Some(ExistingSubscriber { subscriber_id, .. }) => {
+            reset_pending_subscription(&mut transaction, subscriber_id)
+                .await
+                .context("Failed to reset the pending subscription")?;
+
+            subscriber_id
+        }
+        None => insert_subscriber(&mut transaction, &new_subscriber)
+            .await
+            .context("Failed to insert subscriber in the database")?,
+    };
     let subscription_token = generate_subscription_token();
 
     store_token(&mut transaction, subscriber_id, &subscription_token)
         .await
-        .context("Failed to store the confirmation token for the new subscriber")?;
+        .context("Failed to store the confirmation token for the subscriber")?;
 
     transaction
         .commit()
@@ -110,7 +135,7 @@ pub async fn subscribe_controller(
 
     send_confirmation_email(
         &email_client,
-        new_subscriber,
+        &new_subscriber.email,
         &application_base_url,
         &subscription_token,
     )
@@ -120,22 +145,71 @@ pub async fn subscribe_controller(
     Ok(HttpResponse::Ok().finish())
 }
 
+struct ExistingSubscriber {
+    subscriber_id: Uuid,
+    status: String,
+}
+
+#[tracing::instrument(name = "get_existing_subscriber", skip(transaction, email))]
+async fn get_existing_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    email: &SubscriberEmail,
+) -> Result<Option<ExistingSubscriber>, sqlx::Error> {
+    let row = sqlx::query_as!(
+        ExistingSubscriber,
+        r#"
+        SELECT id AS subscriber_id, status
+        FROM subscriptions
+        WHERE email = $1
+        FOR UPDATE
+        "#,
+        email.as_ref()
+    )
+    .fetch_optional(&mut **transaction)
+    .await?;
+
+    Ok(row)
+}
+
+// Previously issued tokens are discarded so that only the link in the latest email is valid.
+#[tracing::instrument(name = "reset_pending_subscription", skip(transaction))]
+pub async fn reset_pending_subscription(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    let query = sqlx::query!(
+        "DELETE FROM subscriptions_tokens WHERE subscriber_id = $1",
+        subscriber_id
+    );
+    transaction.execute(query).await?;
+
+    let query = sqlx::query!(
+        "UPDATE subscriptions SET status = 'pending_confirmation' WHERE id = $1",
+        subscriber_id
+    );
+    transaction.execute(query).await?;
+
+    Ok(())
+}
+
 #[tracing::instrument(name = "insert_subscriber", skip(transaction, new_subscriber))]
 pub async fn insert_subscriber(
     transaction: &mut Transaction<'_, Postgres>,
     new_subscriber: &NewSubscriber,
 ) -> Result<Uuid, sqlx::Error> {
     let subscriber_id = Uuid::new_v4();
+    let unsubscribe_token = generate_subscription_token();
 
     let query = sqlx::query!(
         r#"
-        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
-        VALUES ($1, $2, $3, $4, 'pending_confirmation')
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status, unsubscribe_token)
+        VALUES ($1, $2, $3, $4, 'pending_confirmation', $5)
         "#,
         subscriber_id,
         new_subscriber.email.as_ref(),
         new_subscriber.name.as_ref(),
-        Utc::now()
+        Utc::now(),
+        unsubscribe_token
     );
 
     transaction.execute(query).await?;
@@ -145,14 +219,14 @@ pub async fn insert_subscriber(
 
 #[tracing::instrument(
     name = "send_confirmation_email",
-    skip(email_client, new_subscriber, application_base_url)
+    skip(email_client, recipient, application_base_url)
 )]
-async fn send_confirmation_email(
+pub async fn send_confirmation_email(
     email_client: &EmailClient,
-    new_subscriber: NewSubscriber,
+    recipient: &SubscriberEmail,
     application_base_url: &ApplicationBaseUrl,
     subscription_token: &str,
-) -> Result<(), reqwest::Error> {
+) -> Result<(), SendEmailError> {
     let confirmation_link = format!(
         "{}/subscriptions/confirm?subscription_token={}",
         application_base_url.0, subscription_token
@@ -169,12 +243,31 @@ async fn send_confirmation_email(
         confirmation_link
     );
 
+    email_client
+        .send_email(recipient, "Welcome!", &html_content, &text_content)
+        .await
+}
+
+#[tracing::instrument(
+    name = "send_already_subscribed_email",
+    skip(email_client, new_subscriber)
+)]
+async fn send_already_subscribed_email(
+    email_client: &EmailClient,
+    new_subscriber: NewSubscriber,
+) -> Result<(), SendEmailError> {
+    let html_content = "You are already subscribed to our newsletter.<br />\
+        If you did not request to subscribe again, you can safely ignore this email.";
+
+    let text_content = "You are already subscribed to our newsletter.\n\
+        If you did not request to subscribe again, you can safely ignore this email.";
+
     email_client
         .send_email(
             &new_subscriber.email,
-            "Welcome!",
-            &html_content,
-            &text_content,
+            "You are already subscribed",
+            html_content,
+            text_content,
         )
         .await
 }
@@ -183,7 +276,7 @@ async fn send_confirmation_email(
     name = "store_token",
     skip(transaction, subscriber_id, subscription_token)
 )]
-async fn store_token(
+pub async fn store_token(
     transaction: &mut Transaction<'_, Postgres>,
     subscriber_id: Uuid,
     subscription_token: &str,
@@ -202,7 +295,7 @@ async fn store_token(
     Ok(())
 }
 
-fn generate_subscription_token() -> String {
+pub fn generate_subscription_token() -> String {
     let mut rng = rand::rng();
 
     Alphanumeric.sample_string(&mut rng, 25)
@@ -0,0 +1,127 @@
+use actix_web::http::header::ContentType;
+use actix_web::{HttpResponse, web};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(serde::Deserialize)]
+pub struct Parameters {
+    unsubscribe_token: String,
+}
+
+#[tracing::instrument(name = "unsubscribe_form", skip(parameters, db_connection_pool))]
+pub async fn unsubscribe_form(
+    parameters: web::Query<Parameters>,
+    db_connection_pool: web::Data<PgPool>,
+) -> HttpResponse {
+    let subscriber_id = match get_subscriber_id_from_token(
+        &db_connection_pool,
+        &parameters.unsubscribe_token,
+    )
+    .await
+    {
+        Ok(subscriber_id) => subscriber_id,
+        Err(_) => return HttpResponse::InternalServerError().finish(),
+    };
+
+    if subscriber_id.is_none() {
+        return HttpResponse::Unauthorized().finish();
+    }
+
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Unsubscribe</title>
+</head>
+<body>
+    <p>Do you want to stop receiving our newsletter?</p>
+    <form action="/subscriptions/unsubscribe?unsubscribe_token={}" method="post">
+        <button type="submit">Unsubscribe</button>
+    </form>
+</body>
+</html>"#,
+            parameters.unsubscribe_token
+        ))
+}
+
+#[tracing::instrument(name = "unsubscribe", skip(parameters, db_connection_pool))]
+pub async fn unsubscribe(
+    parameters: web::Query<Parameters>,
+    db_connection_pool: web::Data<PgPool>,
+) -> HttpResponse {
+    let subscriber_id = match get_subscriber_id_from_token(
+        &db_connection_pool,
+        &parameters.unsubscribe_token,
+    )
+    .await
+    {
+        Ok(subscriber_id) => subscriber_id,
+        Err(_) => return HttpResponse::InternalServerError().finish(),
+    };
+
+    match subscriber_id {
+        None => HttpResponse::Unauthorized().finish(),
+        Some(subscriber_id) => {
+            if unsubscribe_subscriber(&db_connection_pool, subscriber_id)
+                .await
+                .is_err()
+            {
+                return HttpResponse::InternalServerError().finish();
+            }
+
+            HttpResponse::Ok()
+                .content_type(ContentType::html())
+                .body("<p>You have been unsubscribed.</p>")
+        }
+    }
+}
+
+#[tracing::instrument(
+    name = "get_subscriber_id_from_unsubscribe_token",
+    skip(db_connection_pool, unsubscribe_token)
+)]
+async fn get_subscriber_id_from_token(
+    db_connection_pool: &PgPool,
+    unsubscribe_token: &str,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    let record = sqlx::query!(
+        "SELECT id FROM subscriptions WHERE unsubscribe_token = $1",
+        unsubscribe_token
+    )
+    .fetch_optional(db_connection_pool)
+    .await
+    .map_err(|error| {
+        tracing::error!(
+            "Failed to fetch subscriber id from unsubscribe token: {}",
+            error
+        );
+        error
+    })?;
+
+    Ok(record.map(|record| record.id))
+}
+
+#[tracing::instrument(
+    name = "unsubscribe_subscriber",
+    skip(db_connection_pool, subscriber_id)
+)]
+async fn unsubscribe_subscriber(
+    db_connection_pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE subscriptions SET status = 'unsubscribed' WHERE id = $1",
+        subscriber_id
+    )
+    .execute(db_connection_pool)
+    .await
+    .map_err(|error| {
+        tracing::error!("Failed to update subscription status: {}", error);
+        error
+    })?;
+
+    Ok(())
+}
@@ -1,7 +1,7 @@
-use crate::session_state::TypedSession;
-use actix_web::error::ErrorInternalServerError;
-use actix_web::http::header::LOCATION;
-use actix_web::HttpResponse;
+use crate::application::TrustedProxies;
+use actix_web::http::header::{LOCATION, X_FORWARDED_FOR};
+use actix_web::{HttpRequest, HttpResponse, web};
+use sha2::{Digest, Sha256};
 use std::error::Error;
 
 pub fn error_chain_fmt(error: &impl Error, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -14,20 +14,133 @@ pub fn error_chain_fmt(error: &impl Error, f: &mut std::fmt::Formatter<'_>) -> s
     Ok(())
 }
 
-pub fn get_redirect_if_session_without_user_id(
-    session: &TypedSession,
-) -> Result<Option<HttpResponse>, actix_web::Error> {
-    if session
-        .get_user_id()
-        .map_err(|error| ErrorInternalServerError(error))?
-        .is_none()
-    {
-        return Ok(Some(
-            HttpResponse::SeeOther()
-                .insert_header((LOCATION, "/login"))
-                .finish(),
-        ));
+pub fn see_other(location: &str) -> HttpResponse {
+    HttpResponse::SeeOther()
+        .insert_header((LOCATION, location))
+        .finish()
+}
+
+// Anybody can send forwarding headers, so they are only read when the request comes from one of
+// our proxies. Behind a chain of proxies, the client is the right-most address that is not one of
+// them.
+pub fn client_ip(request: &HttpRequest) -> Option<String> {
+    let mut client_ip = request.peer_addr()?.ip();
+
+    let Some(trusted_proxies) = request.app_data::<web::Data<TrustedProxies>>() else {
+        return Some(client_ip.to_string());
     };
 
-    Ok(None)
+    let forwarded_ips: Vec<&str> = request
+        .headers()
+        .get_all(X_FORWARDED_FOR)
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .collect();
+
+    for forwarded_ip in forwarded_ips.into_iter().rev() {
+        if !trusted_proxies
+            .0
+            .iter()
+            .any(|network| network.contains(&client_ip))
+        {
+            break;
+        }
+
+        match forwarded_ip.trim().parse() {
+            Ok(forwarded_ip) => client_ip = forwarded_ip,
+            Err(_) => break,
+        }
+    }
+
+    Some(client_ip.to_string())
+}
+
+// Single-use secrets (reset tokens, recovery codes) are only stored as a digest, so that a leaked
+// table cannot be used to redeem them.
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+// For values the application does not validate itself, such as request headers.
+pub fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#x27;"),
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::application::TrustedProxies;
+    use crate::utils::{client_ip, escape_html, hash_token};
+    use actix_web::test::TestRequest;
+    use actix_web::web;
+
+    fn request_from(peer_addr: &str, x_forwarded_for: &str) -> TestRequest {
+        TestRequest::default()
+            .peer_addr(peer_addr.parse().unwrap())
+            .insert_header(("X-Forwarded-For", x_forwarded_for))
+    }
+
+    fn trusted_proxies(networks: &[&str]) -> web::Data<TrustedProxies> {
+        web::Data::new(TrustedProxies(
+            networks
+                .iter()
+                .map(|network| network.parse().unwrap())
+                .collect(),
+        ))
+    }
+
+    #[test]
+    fn given_no_trusted_proxies_then_forwarding_headers_should_be_ignored() {
+        let request = request_from("203.0.113.7:4000", "198.51.100.1")
+            .app_data(trusted_proxies(&[]))
+            .to_http_request();
+
+        assert_eq!(client_ip(&request).as_deref(), Some("203.0.113.7"));
+    }
+
+    #[test]
+    fn given_a_request_from_a_trusted_proxy_then_the_forwarded_address_should_be_used() {
+        let request = request_from("10.0.0.2:4000", "198.51.100.1, 10.0.0.3")
+            .app_data(trusted_proxies(&["10.0.0.0/8"]))
+            .to_http_request();
+
+        assert_eq!(client_ip(&request).as_deref(), Some("198.51.100.1"));
+    }
+
+    #[test]
+    fn given_a_spoofed_left_most_address_then_it_should_be_ignored() {
+        let request = request_from("10.0.0.2:4000", "1.2.3.4, 198.51.100.1")
+            .app_data(trusted_proxies(&["10.0.0.0/8"]))
+            .to_http_request();
+
+        assert_eq!(client_ip(&request).as_deref(), Some("198.51.100.1"));
+    }
+
+    #[test]
+    fn then_it_should_hash_the_token_deterministically() {
+        assert_eq!(hash_token("a-token"), hash_token("a-token"));
+        assert_ne!(hash_token("a-token"), hash_token("another-token"));
+        assert_ne!(hash_token("a-token"), "a-token");
+    }
+
+    #[test]
+    fn then_it_should_escape_html_special_characters() {
+        assert_eq!(
+            escape_html(r#"<script>alert("x" & 'y')</script>"#),
+            "&lt;script&gt;alert(&quot;x&quot; &amp; &#x27;y&#x27;)&lt;/script&gt;"
+        );
+        assert_eq!(escape_html("Mozilla/5.0"), "Mozilla/5.0");
+    }
 }
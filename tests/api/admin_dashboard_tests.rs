@@ -8,3 +8,30 @@ async fn given_an_unauthenticated_user_then_it_should_redirect_to_login() {
 
     test_app.assert_is_redirect_to(&response, "/login");
 }
+
+#[tokio::test]
+async fn given_a_logout_then_it_should_end_the_session() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+
+    let html_page = test_app.get_admin_dashboard_html().await;
+    assert!(html_page.contains(&format!("Welcome {}", test_app.test_user.username)));
+
+    let response = test_app.post_logout().await;
+    test_app.assert_is_redirect_to(&response, "/login");
+
+    let html_page = test_app.get_login_html().await;
+    assert!(html_page.contains("<p><i>You have successfully logged out.</i></p>"));
+
+    let response = test_app.get_admin_dashboard().await;
+    test_app.assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn given_an_unauthenticated_user_requesting_to_log_out_then_it_should_redirect_to_login() {
+    let test_app = spawn_server().await;
+
+    let response = test_app.post_logout().await;
+
+    test_app.assert_is_redirect_to(&response, "/login");
+}
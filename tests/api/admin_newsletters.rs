@@ -0,0 +1,353 @@
+use crate::helpers::{TestApp, TestUser, spawn_server};
+use crate::newsletter_tests::create_and_confirm_subscription;
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+fn newsletter_form(idempotency_key: &str) -> serde_json::Value {
+    serde_json::json!({
+        "title": "Newsletter title",
+        "html_content": "<p>Newsletter HTML content</p>",
+        "text_content": "Newsletter plain content",
+        "idempotency_key": idempotency_key,
+    })
+}
+
+pub async fn create_draft(test_app: &TestApp) -> Uuid {
+    let response = test_app
+        .post_newsletter_draft(&newsletter_form(&Uuid::new_v4().to_string()))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 303);
+
+    response.headers()["Location"]
+        .to_str()
+        .unwrap()
+        .strip_prefix("/admin/newsletters/")
+        .and_then(|newsletter_issue_id| newsletter_issue_id.parse().ok())
+        .expect("The draft id is missing from the redirect")
+}
+
+pub async fn get_status(test_app: &TestApp, newsletter_issue_id: Uuid) -> String {
+    sqlx::query!(
+        "SELECT status FROM newsletter_issues WHERE newsletter_issue_id = $1",
+        newsletter_issue_id
+    )
+    .fetch_one(&test_app.db_connection_pool)
+    .await
+    .expect("Failed to fetch the newsletter issue")
+    .status
+}
+
+pub async fn count_queued_deliveries(test_app: &TestApp) -> i64 {
+    sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM issue_delivery_queue"#)
+        .fetch_one(&test_app.db_connection_pool)
+        .await
+        .expect("Failed to count the queued deliveries")
+        .count
+}
+
+#[tokio::test]
+async fn given_an_unauthenticated_user_then_it_should_redirect_to_login() {
+    let test_app = spawn_server().await;
+
+    let response = test_app.get_new_newsletter_issue().await;
+    test_app.assert_is_redirect_to(&response, "/login");
+
+    let response = test_app
+        .post_newsletter_draft(&newsletter_form(&Uuid::new_v4().to_string()))
+        .await;
+    test_app.assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn given_a_viewer_then_the_composer_should_be_forbidden() {
+    let test_app = spawn_server().await;
+    let viewer = TestUser::generate_with_role("viewer");
+    viewer.store(&test_app.db_connection_pool).await;
+    test_app.login_as(&viewer).await;
+
+    let response = test_app.get_new_newsletter_issue().await;
+
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn given_a_valid_form_then_it_should_save_a_draft_without_sending_it() {
+    let test_app = spawn_server().await;
+    create_and_confirm_subscription(&test_app).await;
+    test_app.login_test_user().await;
+
+    let html_page = test_app.get_new_newsletter_issue_html().await;
+    assert!(html_page.contains(r#"name="idempotency_key""#));
+
+    let newsletter_issue_id = create_draft(&test_app).await;
+
+    let html_page = test_app
+        .get_newsletter_issue_html(newsletter_issue_id)
+        .await;
+    assert!(html_page.contains("<p><i>The draft has been saved.</i></p>"));
+    assert!(html_page.contains(r#"value="Newsletter title""#));
+    assert!(html_page.contains("&lt;p&gt;Newsletter HTML content&lt;/p&gt;</textarea>"));
+
+    assert_eq!(get_status(&test_app, newsletter_issue_id).await, "draft");
+    assert_eq!(count_queued_deliveries(&test_app).await, 0);
+}
+
+#[tokio::test]
+async fn given_a_large_issue_then_it_should_save_the_draft() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+
+    let mut form = newsletter_form(&Uuid::new_v4().to_string());
+    form["html_content"] = serde_json::json!(format!("<p>{}</p>", "a".repeat(500_000)));
+
+    let response = test_app.post_newsletter_draft(&form).await;
+
+    assert_eq!(response.status().as_u16(), 303);
+}
+
+#[tokio::test]
+async fn given_a_draft_then_it_should_be_editable() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+    let newsletter_issue_id = create_draft(&test_app).await;
+
+    let response = test_app
+        .post_save_newsletter_draft(
+            newsletter_issue_id,
+            &serde_json::json!({
+                "title": "Updated title",
+                "html_content": "<p>Updated HTML content</p>",
+                "text_content": "Updated plain content",
+            }),
+        )
+        .await;
+    test_app.assert_is_redirect_to(
+        &response,
+        &format!("/admin/newsletters/{}", newsletter_issue_id),
+    );
+
+    let issue = sqlx::query!(
+        "SELECT title, text_content FROM newsletter_issues WHERE newsletter_issue_id = $1",
+        newsletter_issue_id
+    )
+    .fetch_one(&test_app.db_connection_pool)
+    .await
+    .expect("Failed to fetch the newsletter issue");
+    assert_eq!(issue.title, "Updated title");
+    assert_eq!(issue.text_content, "Updated plain content");
+}
+
+#[tokio::test]
+async fn given_a_draft_then_it_should_preview_both_parts_in_a_sandbox() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+    let newsletter_issue_id = create_draft(&test_app).await;
+
+    let response = test_app
+        .get_newsletter_issue_preview(newsletter_issue_id)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let content_security_policy = response.headers()["Content-Security-Policy"]
+        .to_str()
+        .unwrap()
+        .to_owned();
+    assert!(content_security_policy.contains("style-src 'unsafe-inline'"));
+    assert!(!content_security_policy.contains("script-src"));
+
+    let html_page = response.text().await.unwrap();
+    assert!(
+        html_page
+            .contains(r#"<iframe sandbox="" srcdoc="&lt;p&gt;Newsletter HTML content&lt;/p&gt;""#)
+    );
+    assert!(html_page.contains("<pre>Newsletter plain content</pre>"));
+}
+
+#[tokio::test]
+async fn given_a_draft_then_it_should_send_a_test_email_to_a_single_address() {
+    let test_app = spawn_server().await;
+    create_and_confirm_subscription(&test_app).await;
+    test_app.login_test_user().await;
+    let newsletter_issue_id = create_draft(&test_app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    let response = test_app
+        .post_newsletter_test(newsletter_issue_id, "editor@example.com")
+        .await;
+    test_app.assert_is_redirect_to(
+        &response,
+        &format!("/admin/newsletters/{}", newsletter_issue_id),
+    );
+
+    let html_page = test_app
+        .get_newsletter_issue_html(newsletter_issue_id)
+        .await;
+    assert!(html_page.contains("<p><i>A test email has been sent to editor@example.com.</i></p>"));
+
+    let email_request = test_app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["To"], "editor@example.com");
+    assert_eq!(body["Subject"], "[Test] Newsletter title");
+
+    assert_eq!(get_status(&test_app, newsletter_issue_id).await, "draft");
+    assert_eq!(count_queued_deliveries(&test_app).await, 0);
+
+    test_app
+        .post_newsletter_test(newsletter_issue_id, "not-an-email")
+        .await;
+    let html_page = test_app
+        .get_newsletter_issue_html(newsletter_issue_id)
+        .await;
+    assert!(html_page.contains("Enter a valid email address to send the test to."));
+}
+
+#[tokio::test]
+async fn given_too_many_test_emails_then_further_ones_should_not_be_sent() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+    let newsletter_issue_id = create_draft(&test_app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(10)
+        .mount(&test_app.email_server)
+        .await;
+
+    for _ in 0..10 {
+        test_app
+            .post_newsletter_test(newsletter_issue_id, "editor@example.com")
+            .await;
+    }
+
+    test_app
+        .post_newsletter_test(newsletter_issue_id, "editor@example.com")
+        .await;
+
+    let html_page = test_app
+        .get_newsletter_issue_html(newsletter_issue_id)
+        .await;
+    assert!(html_page.contains("Too many test emails have been sent."));
+}
+
+#[tokio::test]
+async fn given_a_published_draft_then_it_should_be_sent_once_and_no_longer_editable() {
+    let test_app = spawn_server().await;
+    create_and_confirm_subscription(&test_app).await;
+    test_app.login_test_user().await;
+    let newsletter_issue_id = create_draft(&test_app).await;
+
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    let response = test_app
+        .post_publish_newsletter_draft(newsletter_issue_id)
+        .await;
+    test_app.assert_is_redirect_to(
+        &response,
+        &format!("/admin/newsletters/{}", newsletter_issue_id),
+    );
+
+    let html_page = test_app
+        .get_newsletter_issue_html(newsletter_issue_id)
+        .await;
+    assert!(html_page.contains(
+        "<p><i>The newsletter issue has been accepted - emails will go out shortly.</i></p>"
+    ));
+    assert_eq!(get_status(&test_app, newsletter_issue_id).await, "sending");
+
+    test_app
+        .post_publish_newsletter_draft(newsletter_issue_id)
+        .await;
+    let html_page = test_app
+        .get_newsletter_issue_html(newsletter_issue_id)
+        .await;
+    assert!(html_page.contains("<p><i>This newsletter issue has already been published.</i></p>"));
+
+    test_app
+        .post_save_newsletter_draft(
+            newsletter_issue_id,
+            &serde_json::json!({
+                "title": "Updated title",
+                "html_content": "<p>Updated HTML content</p>",
+                "text_content": "Updated plain content",
+            }),
+        )
+        .await;
+    let html_page = test_app
+        .get_newsletter_issue_html(newsletter_issue_id)
+        .await;
+    assert!(html_page.contains("<p><i>Only drafts can be edited.</i></p>"));
+
+    test_app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn given_missing_content_then_it_should_show_an_error_and_store_nothing() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+
+    let response = test_app
+        .post_newsletter_draft(&serde_json::json!({
+            "title": "Newsletter title",
+            "html_content": "",
+            "text_content": "Newsletter plain content",
+            "idempotency_key": Uuid::new_v4().to_string(),
+        }))
+        .await;
+    test_app.assert_is_redirect_to(&response, "/admin/newsletters/new");
+
+    let html_page = test_app.get_new_newsletter_issue_html().await;
+    assert!(html_page.contains(
+        "<p><i>The title, HTML content and plain text content are all required.</i></p>"
+    ));
+
+    let issues = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_all(&test_app.db_connection_pool)
+        .await
+        .expect("Failed to fetch the newsletter issues");
+    assert!(issues.is_empty());
+}
+
+#[tokio::test]
+async fn given_a_resubmitted_form_then_it_should_store_the_draft_only_once() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+    let idempotency_key = Uuid::new_v4().to_string();
+
+    let first_response = test_app
+        .post_newsletter_draft(&newsletter_form(&idempotency_key))
+        .await;
+    let second_response = test_app
+        .post_newsletter_draft(&newsletter_form(&idempotency_key))
+        .await;
+
+    assert_eq!(
+        first_response.headers()["Location"],
+        second_response.headers()["Location"]
+    );
+
+    let issues = sqlx::query!("SELECT title FROM newsletter_issues")
+        .fetch_all(&test_app.db_connection_pool)
+        .await
+        .expect("Failed to fetch the newsletter issues");
+    assert_eq!(issues.len(), 1);
+}
@@ -0,0 +1,164 @@
+use crate::helpers::{TestApp, newsletter_request_body, spawn_server};
+use uuid::Uuid;
+
+async fn create_api_token(test_app: &TestApp, scope: &str) -> String {
+    let response = test_app
+        .post_api_tokens(&[("name", "CI pipeline"), ("scope", scope)])
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let html_page = response.text().await.unwrap();
+
+    html_page
+        .split("<code>")
+        .nth(1)
+        .and_then(|rest| rest.split("</code>").next())
+        .expect("The API token is missing from the page")
+        .to_owned()
+}
+
+async fn get_api_token_id(test_app: &TestApp) -> Uuid {
+    sqlx::query!(
+        "SELECT api_token_id FROM api_tokens WHERE user_id = $1",
+        test_app.test_user.user_id
+    )
+    .fetch_one(&test_app.db_connection_pool)
+    .await
+    .expect("Failed to fetch the API token")
+    .api_token_id
+}
+
+#[tokio::test]
+async fn given_a_token_with_the_publish_scope_then_it_should_publish_with_bearer_auth() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+    let token = create_api_token(&test_app, "newsletters:publish").await;
+
+    let response = test_app
+        .post_newsletters_with_bearer_token(newsletter_request_body(), &token)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 202);
+
+    let stored_token = sqlx::query!(
+        "SELECT token_hash, last_used_at FROM api_tokens WHERE user_id = $1",
+        test_app.test_user.user_id
+    )
+    .fetch_one(&test_app.db_connection_pool)
+    .await
+    .expect("Failed to fetch the API token");
+
+    assert_ne!(stored_token.token_hash, token);
+    assert!(stored_token.last_used_at.is_some());
+}
+
+#[tokio::test]
+async fn given_a_token_without_the_publish_scope_then_it_should_return_403() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+    let token = create_api_token(&test_app, "subscribers:read").await;
+
+    let response = test_app
+        .post_newsletters_with_bearer_token(newsletter_request_body(), &token)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn given_a_token_with_the_deliveries_scope_then_it_should_only_read_delivery_reports() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+    let response = test_app.post_newsletters(newsletter_request_body()).await;
+    let body: serde_json::Value = response.json().await.unwrap();
+    let newsletter_issue_id: Uuid = body["newsletter_issue_id"]
+        .as_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+
+    let read_token = create_api_token(&test_app, "deliveries:read").await;
+    let response = test_app
+        .get_delivery_report_with_bearer_token(newsletter_issue_id, &read_token)
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let subscribers_token = create_api_token(&test_app, "subscribers:read").await;
+    let response = test_app
+        .get_delivery_report_with_bearer_token(newsletter_issue_id, &subscribers_token)
+        .await;
+    assert_eq!(response.status().as_u16(), 403);
+
+    let publish_token = create_api_token(&test_app, "newsletters:publish").await;
+    let response = test_app
+        .get_delivery_report_with_bearer_token(newsletter_issue_id, &publish_token)
+        .await;
+    assert_eq!(response.status().as_u16(), 403);
+
+    test_app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn given_a_revoked_token_then_it_should_return_401() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+    let token = create_api_token(&test_app, "newsletters:publish").await;
+
+    let response = test_app
+        .post_revoke_api_token(get_api_token_id(&test_app).await)
+        .await;
+
+    test_app.assert_is_redirect_to(&response, "/admin/api-tokens");
+
+    let html_page = test_app.get_api_tokens_html().await;
+    assert!(html_page.contains("<p><i>The API token has been revoked.</i></p>"));
+    assert!(!html_page.contains("CI pipeline"));
+
+    let response = test_app
+        .post_newsletters_with_bearer_token(newsletter_request_body(), &token)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn given_an_expired_token_then_it_should_return_401() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+    let token = create_api_token(&test_app, "newsletters:publish").await;
+
+    sqlx::query!("UPDATE api_tokens SET expires_at = now() - interval '1 minute'")
+        .execute(&test_app.db_connection_pool)
+        .await
+        .expect("Failed to expire the API token");
+
+    let response = test_app
+        .post_newsletters_with_bearer_token(newsletter_request_body(), &token)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn then_the_api_tokens_page_should_list_created_tokens() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+    create_api_token(&test_app, "subscribers:read").await;
+
+    let html_page = test_app.get_api_tokens_html().await;
+
+    assert!(html_page.contains("<td>CI pipeline</td>"));
+    assert!(html_page.contains("<td>subscribers:read</td>"));
+}
+
+#[tokio::test]
+async fn given_an_anonymous_user_then_it_should_redirect_to_login() {
+    let test_app = spawn_server().await;
+
+    let response = test_app
+        .post_api_tokens(&[("name", "CI pipeline"), ("scope", "newsletters:publish")])
+        .await;
+
+    test_app.assert_is_redirect_to(&response, "/login");
+}
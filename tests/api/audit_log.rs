@@ -0,0 +1,275 @@
+use crate::helpers::{TestApp, TestUser, newsletter_request_body, spawn_server};
+use chrono::{Duration, Utc};
+use uuid::Uuid;
+
+struct RecordedEvent {
+    actor_user_id: Option<Uuid>,
+    actor: String,
+    target: Option<String>,
+    ip_address: Option<String>,
+    outcome: String,
+}
+
+async fn get_events(test_app: &TestApp, action: &str) -> Vec<RecordedEvent> {
+    sqlx::query_as!(
+        RecordedEvent,
+        r#"
+        SELECT actor_user_id, actor, target, ip_address, outcome
+        FROM audit_events
+        WHERE action = $1
+        ORDER BY occurred_at
+        "#,
+        action
+    )
+    .fetch_all(&test_app.db_connection_pool)
+    .await
+    .expect("Failed to fetch the audit events")
+}
+
+#[tokio::test]
+async fn given_login_attempts_then_it_should_record_failures_and_successes() {
+    let test_app = spawn_server().await;
+
+    test_app
+        .post_login(&serde_json::json!({
+            "username": &test_app.test_user.username,
+            "password": "wrong-password",
+        }))
+        .await;
+    test_app.login_test_user().await;
+
+    let events = get_events(&test_app, "login").await;
+    assert_eq!(events.len(), 2);
+
+    assert_eq!(events[0].outcome, "failure");
+    assert_eq!(events[0].actor, test_app.test_user.username);
+    assert_eq!(events[0].actor_user_id, None);
+    assert_eq!(events[0].ip_address.as_deref(), Some("127.0.0.1"));
+
+    assert_eq!(events[1].outcome, "success");
+    assert_eq!(events[1].actor_user_id, Some(test_app.test_user.user_id));
+}
+
+#[tokio::test]
+async fn given_a_very_long_username_then_the_recorded_actor_should_be_truncated() {
+    let test_app = spawn_server().await;
+
+    test_app
+        .post_login(&serde_json::json!({
+            "username": "a".repeat(10_000),
+            "password": "wrong-password",
+        }))
+        .await;
+
+    let events = get_events(&test_app, "login").await;
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].actor, "a".repeat(64));
+}
+
+#[tokio::test]
+async fn given_a_password_change_then_it_should_record_it() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+
+    let new_password = Uuid::new_v4().to_string();
+    test_app
+        .post_change_password(&serde_json::json!({
+            "current_password": &test_app.test_user.password,
+            "new_password": &new_password,
+            "new_password_check": &new_password,
+        }))
+        .await;
+
+    let events = get_events(&test_app, "change_password").await;
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].outcome, "success");
+    assert_eq!(events[0].actor, test_app.test_user.username);
+}
+
+#[tokio::test]
+async fn given_publish_requests_then_it_should_record_the_publisher_and_the_issue() {
+    let test_app = spawn_server().await;
+
+    let response = test_app.post_newsletters(newsletter_request_body()).await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/newsletters", &test_app.address))
+        .basic_auth("mallory", Some("guess"))
+        .json(&newsletter_request_body())
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 401);
+
+    let events = get_events(&test_app, "publish_newsletter").await;
+    assert_eq!(events.len(), 2);
+
+    assert_eq!(events[0].outcome, "success");
+    assert_eq!(events[0].actor, test_app.test_user.username);
+    assert!(
+        events[0]
+            .target
+            .as_deref()
+            .unwrap()
+            .starts_with("Newsletter title (")
+    );
+
+    assert_eq!(events[1].outcome, "failure");
+    assert_eq!(events[1].actor, "mallory");
+    assert_eq!(events[1].target, None);
+}
+
+#[tokio::test]
+async fn given_schedule_changes_then_it_should_record_them_and_credit_the_scheduler() {
+    let test_app = spawn_server().await;
+
+    let mut request_body = newsletter_request_body();
+    request_body["scheduled_at"] = serde_json::json!(Utc::now() + Duration::days(1));
+    let response = test_app.post_newsletters(request_body).await;
+    assert_eq!(response.status().as_u16(), 202);
+    let body: serde_json::Value = response.json().await.unwrap();
+    let newsletter_issue_id: Uuid = body["newsletter_issue_id"]
+        .as_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+
+    let response = test_app
+        .put_newsletter_schedule(
+            newsletter_issue_id,
+            serde_json::json!({ "scheduled_at": Utc::now() + Duration::days(2) }),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = test_app
+        .delete_newsletter_schedule(newsletter_issue_id)
+        .await;
+    assert_eq!(response.status().as_u16(), 204);
+
+    for action in [
+        "schedule_newsletter",
+        "reschedule_newsletter",
+        "cancel_newsletter_schedule",
+    ] {
+        let events = get_events(&test_app, action).await;
+        assert_eq!(events.len(), 1, "{action}");
+        assert_eq!(events[0].actor, test_app.test_user.username);
+        assert_eq!(events[0].actor_user_id, Some(test_app.test_user.user_id));
+        assert_eq!(
+            events[0].target,
+            Some(format!("Newsletter title ({})", newsletter_issue_id))
+        );
+    }
+
+    let response = test_app
+        .put_newsletter_schedule(
+            newsletter_issue_id,
+            serde_json::json!({ "scheduled_at": Utc::now() + Duration::days(1) }),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(get_events(&test_app, "schedule_newsletter").await.len(), 2);
+
+    sqlx::query!(
+        "UPDATE newsletter_issues SET scheduled_at = now() - interval '1 minute' WHERE newsletter_issue_id = $1",
+        newsletter_issue_id
+    )
+    .execute(&test_app.db_connection_pool)
+    .await
+    .unwrap();
+    zero2prod::newsletter_issues::promote_due_issues(&test_app.db_connection_pool)
+        .await
+        .unwrap();
+
+    let events = get_events(&test_app, "publish_newsletter").await;
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].actor, test_app.test_user.username);
+    assert_eq!(events[0].actor_user_id, Some(test_app.test_user.user_id));
+
+    test_app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn given_an_owner_then_it_should_browse_and_filter_the_audit_log() {
+    let test_app = spawn_server().await;
+    test_app
+        .post_login(&serde_json::json!({
+            "username": "<script>mallory</script>",
+            "password": "guess",
+        }))
+        .await;
+    test_app.login_test_user().await;
+
+    let html_page = test_app.get_audit_log_html(&[]).await;
+    assert!(html_page.contains(&format!("<td>{}</td>", test_app.test_user.username)));
+    assert!(html_page.contains("&lt;script&gt;mallory&lt;/script&gt;"));
+    assert!(!html_page.contains("<script>mallory</script>"));
+
+    let html_page = test_app
+        .get_audit_log_html(&[("action", "login"), ("outcome", "success")])
+        .await;
+    assert!(html_page.contains(&format!("<td>{}</td>", test_app.test_user.username)));
+    assert!(!html_page.contains("mallory"));
+
+    let response = test_app.get_audit_log(&[("outcome", "maybe")]).await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn given_more_events_than_a_page_then_it_should_paginate() {
+    let test_app = spawn_server().await;
+
+    for _ in 0..51 {
+        sqlx::query!(
+            r#"
+            INSERT INTO audit_events (audit_event_id, occurred_at, actor, action, outcome)
+            VALUES ($1, $2, 'mallory', 'login', 'failure')
+            "#,
+            Uuid::new_v4(),
+            Utc::now() - chrono::Duration::hours(1)
+        )
+        .execute(&test_app.db_connection_pool)
+        .await
+        .expect("Failed to store an audit event");
+    }
+
+    test_app.login_test_user().await;
+
+    let html_page = test_app.get_audit_log_html(&[("actor", "mallory")]).await;
+    assert_eq!(html_page.matches("<td>mallory</td>").count(), 50);
+    assert!(html_page.contains("Next page"));
+    assert!(!html_page.contains("Previous page"));
+
+    let html_page = test_app
+        .get_audit_log_html(&[("actor", "mallory"), ("page", "2")])
+        .await;
+    assert_eq!(html_page.matches("<td>mallory</td>").count(), 1);
+    assert!(!html_page.contains("Next page"));
+    assert!(html_page.contains("Previous page"));
+}
+
+#[tokio::test]
+async fn given_a_page_beyond_any_offset_then_it_should_return_400() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+
+    let response = test_app
+        .get_audit_log(&[("page", &i64::MAX.to_string())])
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn given_an_editor_then_the_audit_log_should_be_forbidden() {
+    let test_app = spawn_server().await;
+    let editor = TestUser::generate_with_role("editor");
+    editor.store(&test_app.db_connection_pool).await;
+    test_app.login_as(&editor).await;
+
+    let response = test_app.get_audit_log(&[]).await;
+
+    assert_eq!(response.status().as_u16(), 403);
+}
@@ -0,0 +1,118 @@
+use crate::helpers::{TestApp, TestUser, spawn_server};
+use fake::Fake;
+use fake::faker::internet::en::SafeEmail;
+use uuid::Uuid;
+
+#[tokio::test]
+async fn given_an_unauthenticated_user_requesting_to_change_email_then_it_should_redirect_to_login()
+{
+    let test_app = spawn_server().await;
+
+    let response = test_app
+        .post_change_email(&serde_json::json!({
+            "email": SafeEmail().fake::<String>(),
+            "current_password": Uuid::new_v4().to_string(),
+        }))
+        .await;
+
+    test_app.assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn given_a_valid_email_and_password_then_it_should_change_the_email() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+    let new_email: String = SafeEmail().fake();
+
+    let response = test_app
+        .post_change_email(&serde_json::json!({
+            "email": &new_email,
+            "current_password": &test_app.test_user.password,
+        }))
+        .await;
+
+    test_app.assert_is_redirect_to(&response, "/admin/email");
+
+    let html_page = test_app.get_change_email_html().await;
+    assert!(html_page.contains("<p><i>Your email address has been changed.</i></p>"));
+    assert!(html_page.contains(&format!("Your current email address is {}.", new_email)));
+
+    assert_eq!(stored_email(&test_app).await, Some(new_email));
+}
+
+#[tokio::test]
+async fn given_a_wrong_current_password_then_it_should_not_change_the_email() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+
+    let response = test_app
+        .post_change_email(&serde_json::json!({
+            "email": SafeEmail().fake::<String>(),
+            "current_password": Uuid::new_v4().to_string(),
+        }))
+        .await;
+
+    test_app.assert_is_redirect_to(&response, "/admin/email");
+
+    let html_page = test_app.get_change_email_html().await;
+    assert!(html_page.contains("<p><i>The current password is incorrect.</i></p>"));
+
+    assert_eq!(
+        stored_email(&test_app).await,
+        Some(test_app.test_user.email.clone())
+    );
+}
+
+#[tokio::test]
+async fn given_an_invalid_email_then_it_should_show_an_error() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+
+    let response = test_app
+        .post_change_email(&serde_json::json!({
+            "email": "not-an-email",
+            "current_password": &test_app.test_user.password,
+        }))
+        .await;
+
+    test_app.assert_is_redirect_to(&response, "/admin/email");
+
+    let html_page = test_app.get_change_email_html().await;
+    assert!(html_page.contains("<p><i>The email address is invalid.</i></p>"));
+}
+
+#[tokio::test]
+async fn given_an_email_used_by_another_user_then_it_should_show_an_error() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+    let other_user = TestUser::generate_with_role("editor");
+    other_user.store(&test_app.db_connection_pool).await;
+
+    let response = test_app
+        .post_change_email(&serde_json::json!({
+            "email": &other_user.email,
+            "current_password": &test_app.test_user.password,
+        }))
+        .await;
+
+    test_app.assert_is_redirect_to(&response, "/admin/email");
+
+    let html_page = test_app.get_change_email_html().await;
+    assert!(html_page.contains("<p><i>Another user already has this email address.</i></p>"));
+
+    assert_eq!(
+        stored_email(&test_app).await,
+        Some(test_app.test_user.email.clone())
+    );
+}
+
+async fn stored_email(test_app: &TestApp) -> Option<String> {
+    sqlx::query!(
+        "SELECT email FROM users WHERE user_id = $1",
+        test_app.test_user.user_id
+    )
+    .fetch_one(&test_app.db_connection_pool)
+    .await
+    .expect("Failed to fetch the user's email")
+    .email
+}
@@ -28,3 +28,104 @@ async fn given_an_unauthenticated_user_requesting_to_change_password_then_it_sho
 
     test_app.assert_is_redirect_to(&response, "/login");
 }
+
+#[tokio::test]
+async fn given_new_passwords_that_do_not_match_then_it_should_show_an_error() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+
+    let response = test_app
+        .post_change_password(&serde_json::json!({
+            "current_password": &test_app.test_user.password,
+            "new_password": Uuid::new_v4().to_string(),
+            "new_password_check": Uuid::new_v4().to_string(),
+        }))
+        .await;
+
+    test_app.assert_is_redirect_to(&response, "/admin/password");
+
+    let html_page = test_app.get_change_password_html().await;
+    assert!(html_page.contains(
+        "<p><i>You entered two different new passwords - the field values must match.</i></p>"
+    ));
+}
+
+#[tokio::test]
+async fn given_an_invalid_current_password_then_it_should_show_an_error() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+
+    let new_password = Uuid::new_v4().to_string();
+
+    let response = test_app
+        .post_change_password(&serde_json::json!({
+            "current_password": Uuid::new_v4().to_string(),
+            "new_password": &new_password,
+            "new_password_check": &new_password,
+        }))
+        .await;
+
+    test_app.assert_is_redirect_to(&response, "/admin/password");
+
+    let html_page = test_app.get_change_password_html().await;
+    assert!(html_page.contains("<p><i>The current password is incorrect.</i></p>"));
+}
+
+#[tokio::test]
+async fn given_a_new_password_with_an_invalid_length_then_it_should_show_an_error() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+
+    let test_cases = vec![("a".repeat(11), "too short"), ("a".repeat(129), "too long")];
+
+    for (new_password, description) in test_cases {
+        let response = test_app
+            .post_change_password(&serde_json::json!({
+                "current_password": &test_app.test_user.password,
+                "new_password": &new_password,
+                "new_password_check": &new_password,
+            }))
+            .await;
+
+        test_app.assert_is_redirect_to(&response, "/admin/password");
+
+        let html_page = test_app.get_change_password_html().await;
+        assert!(
+            html_page.contains(
+                "<p><i>The new password must be between 12 and 128 characters long.</i></p>"
+            ),
+            "The form did not show an error when the new password was {}",
+            description
+        );
+    }
+}
+
+#[tokio::test]
+async fn given_a_valid_request_then_it_should_change_the_password() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+
+    let new_password = Uuid::new_v4().to_string();
+
+    let response = test_app
+        .post_change_password(&serde_json::json!({
+            "current_password": &test_app.test_user.password,
+            "new_password": &new_password,
+            "new_password_check": &new_password,
+        }))
+        .await;
+
+    test_app.assert_is_redirect_to(&response, "/admin/password");
+
+    let html_page = test_app.get_change_password_html().await;
+    assert!(html_page.contains("<p><i>Your password has been changed.</i></p>"));
+
+    let response = test_app
+        .post_login(&serde_json::json!({
+            "username": &test_app.test_user.username,
+            "password": &new_password,
+        }))
+        .await;
+
+    test_app.assert_is_redirect_to(&response, "/admin/dashboard");
+}
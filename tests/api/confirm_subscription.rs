@@ -1,4 +1,4 @@
-use crate::helpers::spawn_server;
+use crate::helpers::{TestApp, spawn_server};
 use wiremock::matchers::{method, path};
 use wiremock::{Mock, ResponseTemplate};
 
@@ -63,3 +63,109 @@ async fn then_it_should_confirm_subscription() {
     assert_eq!("ursula_le_guin@gmail.com", record.email);
     assert_eq!("confirmed", record.status);
 }
+
+#[tokio::test]
+async fn given_an_already_used_confirmation_link_then_it_should_return_401() {
+    let test_app = spawn_server().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&test_app.email_server)
+        .await;
+
+    test_app.post_subscriptions(body.to_owned()).await;
+    let email_request = &test_app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = test_app.get_confirmation_links(email_request);
+
+    reqwest::get(confirmation_links.html.clone())
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let response = reqwest::get(confirmation_links.html).await.unwrap();
+
+    assert_eq!(401, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn given_an_expired_confirmation_link_then_it_should_return_410_with_a_resend_form() {
+    let test_app = spawn_server().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&test_app.email_server)
+        .await;
+
+    test_app.post_subscriptions(body.to_owned()).await;
+    let email_request = &test_app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = test_app.get_confirmation_links(email_request);
+    expire_subscription_tokens(&test_app).await;
+
+    let response = reqwest::get(confirmation_links.html).await.unwrap();
+
+    assert_eq!(410, response.status().as_u16());
+    assert!(
+        response
+            .text()
+            .await
+            .unwrap()
+            .contains(r#"<form action="/subscriptions/confirm/resend" method="post">"#)
+    );
+
+    let record = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&test_app.db_connection_pool)
+        .await
+        .expect("Failed to fetch record");
+
+    assert_eq!("pending_confirmation", record.status);
+}
+
+#[tokio::test]
+async fn given_an_expired_token_when_requesting_a_new_link_then_it_should_send_a_working_link() {
+    let test_app = spawn_server().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&test_app.email_server)
+        .await;
+
+    test_app.post_subscriptions(body.to_owned()).await;
+    let email_request = &test_app.email_server.received_requests().await.unwrap()[0];
+    let expired_links = test_app.get_confirmation_links(email_request);
+    expire_subscription_tokens(&test_app).await;
+
+    let expired_token = expired_links
+        .html
+        .query_pairs()
+        .find(|(name, _)| name == "subscription_token")
+        .map(|(_, value)| value.into_owned())
+        .unwrap();
+
+    let response = test_app.post_resend_confirmation(&expired_token).await;
+
+    assert_eq!(200, response.status().as_u16());
+
+    let email_request = &test_app.email_server.received_requests().await.unwrap()[1];
+    let confirmation_links = test_app.get_confirmation_links(email_request);
+
+    assert_ne!(expired_links.html, confirmation_links.html);
+
+    let response = reqwest::get(confirmation_links.html).await.unwrap();
+
+    assert_eq!(200, response.status().as_u16());
+}
+
+async fn expire_subscription_tokens(test_app: &TestApp) {
+    sqlx::query!("UPDATE subscriptions_tokens SET created_at = now() - interval '1 year'")
+        .execute(&test_app.db_connection_pool)
+        .await
+        .expect("Failed to expire the subscription tokens");
+}
@@ -0,0 +1,122 @@
+use crate::helpers::spawn_server;
+
+#[tokio::test]
+async fn then_the_login_form_should_embed_a_csrf_token() {
+    let test_app = spawn_server().await;
+
+    let html_page = test_app.get_login_html().await;
+
+    assert!(html_page.contains(r#"<input type="hidden" name="csrf_token" value=""#));
+}
+
+#[tokio::test]
+async fn given_a_login_without_csrf_token_then_it_should_return_403() {
+    let test_app = spawn_server().await;
+    test_app.get_login_html().await;
+
+    let response = test_app
+        .http_client
+        .post(format!("{}/login", &test_app.address))
+        .form(&[
+            ("username", test_app.test_user.username.as_str()),
+            ("password", test_app.test_user.password.as_str()),
+        ])
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn given_a_wrong_csrf_token_then_changing_the_password_should_return_403() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+
+    let new_password = uuid::Uuid::new_v4().to_string();
+
+    let response = test_app
+        .http_client
+        .post(format!("{}/admin/password", &test_app.address))
+        .form(&[
+            ("current_password", test_app.test_user.password.as_str()),
+            ("new_password", new_password.as_str()),
+            ("new_password_check", new_password.as_str()),
+            ("csrf_token", "forged"),
+        ])
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn given_another_origin_then_it_should_return_403_even_with_a_valid_token() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+    let csrf_token = test_app.get_csrf_token().await;
+
+    let response = test_app
+        .http_client
+        .post(format!("{}/admin/logout", &test_app.address))
+        .header("Origin", "https://evil.example.com")
+        .form(&[("csrf_token", csrf_token.as_str())])
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn given_forwarding_headers_naming_another_origin_then_it_should_return_403() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+    let csrf_token = test_app.get_csrf_token().await;
+
+    let response = test_app
+        .http_client
+        .post(format!("{}/admin/logout", &test_app.address))
+        .header("Origin", "https://evil.example.com")
+        .header("X-Forwarded-Host", "evil.example.com")
+        .header("X-Forwarded-Proto", "https")
+        .form(&[("csrf_token", csrf_token.as_str())])
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn given_the_configured_origin_then_it_should_accept_the_request() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+    let csrf_token = test_app.get_csrf_token().await;
+
+    let response = test_app
+        .http_client
+        .post(format!("{}/admin/logout", &test_app.address))
+        .header("Origin", &test_app.base_url)
+        .form(&[("csrf_token", csrf_token.as_str())])
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    test_app.assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn then_the_admin_forms_should_embed_the_session_csrf_token() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+    let csrf_token = test_app.get_csrf_token().await;
+
+    let html_page = test_app.get_change_password_html().await;
+
+    assert!(html_page.contains(&format!(
+        r#"<input type="hidden" name="csrf_token" value="{}" />"#,
+        csrf_token
+    )));
+}
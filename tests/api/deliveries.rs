@@ -0,0 +1,392 @@
+use crate::helpers::{TestApp, TestUser, newsletter_request_body, spawn_server};
+use crate::newsletter_tests::create_and_confirm_subscription;
+use chrono::Utc;
+use std::time::Duration;
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, Request, ResponseTemplate};
+use zero2prod::issue_delivery_worker::{ExecutionOutcome, try_execute_task};
+
+async fn publish_newsletter(test_app: &TestApp) -> Uuid {
+    let response = test_app
+        .post_newsletters(serde_json::json!({
+            "title": "Newsletter title",
+            "content": {
+                "text": "Newsletter plain content",
+                "html": "<p>Newsletter HTML content</p>",
+            }
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 202);
+
+    let body: serde_json::Value = response.json().await.unwrap();
+
+    body["newsletter_issue_id"]
+        .as_str()
+        .unwrap()
+        .parse()
+        .unwrap()
+}
+
+async fn get_subscriber_email(test_app: &TestApp) -> String {
+    sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_one(&test_app.db_connection_pool)
+        .await
+        .expect("Failed to fetch the subscriber")
+        .email
+}
+
+#[tokio::test]
+async fn given_a_delivered_issue_then_it_should_record_the_provider_message_id() {
+    let test_app = spawn_server().await;
+    create_and_confirm_subscription(&test_app).await;
+
+    // Slow deliveries down so that the delivery is still pending when we first look at it
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(serde_json::json!([
+                    { "ErrorCode": 0, "Message": "OK", "MessageID": "message-1" },
+                ]))
+                .set_delay(Duration::from_secs(1)),
+        )
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    let newsletter_issue_id = publish_newsletter(&test_app).await;
+
+    let response = test_app.get_delivery_report(newsletter_issue_id).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["status"], "sending");
+    assert_eq!(report["pending"], 1);
+    assert_eq!(report["sent"], 0);
+
+    test_app.dispatch_all_pending_emails().await;
+
+    let delivery = sqlx::query!(
+        r#"
+        SELECT subscriber_email, status, n_attempts, last_error, provider_message_id
+        FROM deliveries
+        WHERE newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_one(&test_app.db_connection_pool)
+    .await
+    .expect("Failed to fetch the delivery");
+
+    assert_eq!(
+        delivery.subscriber_email,
+        get_subscriber_email(&test_app).await
+    );
+    assert_eq!(delivery.status, "sent");
+    assert_eq!(delivery.n_attempts, 1);
+    assert_eq!(delivery.last_error, None);
+    assert_eq!(delivery.provider_message_id.as_deref(), Some("message-1"));
+
+    let report: serde_json::Value = test_app
+        .get_delivery_report(newsletter_issue_id)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(report["pending"], 0);
+    assert_eq!(report["sent"], 1);
+    assert_eq!(report["failed"], 0);
+}
+
+#[tokio::test]
+async fn given_a_rejected_email_then_it_should_report_the_failed_delivery_and_its_error() {
+    let test_app = spawn_server().await;
+    create_and_confirm_subscription(&test_app).await;
+
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(422))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    let newsletter_issue_id = publish_newsletter(&test_app).await;
+    test_app.dispatch_all_pending_emails().await;
+
+    let report: serde_json::Value = test_app
+        .get_delivery_report(newsletter_issue_id)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(report["sent"], 0);
+    assert_eq!(report["failed"], 1);
+
+    test_app.login_test_user().await;
+    let html_page = test_app
+        .get_newsletter_issue_report_html(newsletter_issue_id)
+        .await;
+
+    assert!(html_page.contains(&get_subscriber_email(&test_app).await));
+    assert!(html_page.contains("The email provider rejected the email"));
+    assert!(html_page.contains("422 Unprocessable Entity"));
+}
+
+#[tokio::test]
+async fn given_a_batch_with_a_rejected_recipient_then_only_that_delivery_should_fail() {
+    let test_app = spawn_server().await;
+    create_and_confirm_subscription(&test_app).await;
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status, unsubscribe_token)
+        VALUES ($1, 'inactive@example.com', 'Inactive', now(), 'confirmed', $2)
+        "#,
+        Uuid::new_v4(),
+        Uuid::new_v4().to_string(),
+    )
+    .execute(&test_app.db_connection_pool)
+    .await
+    .unwrap();
+
+    // Both subscribers go out in a single call, which reports an outcome for each of them
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(|request: &Request| {
+            let messages: Vec<serde_json::Value> = serde_json::from_slice(&request.body).unwrap();
+            let outcomes: Vec<_> = messages
+                .iter()
+                .map(|message| match message["To"].as_str() {
+                    Some("inactive@example.com") => serde_json::json!({
+                        "ErrorCode": 406,
+                        "Message": "You tried to send to a recipient that has been marked as inactive.",
+                    }),
+                    _ => serde_json::json!({
+                        "ErrorCode": 0,
+                        "Message": "OK",
+                        "MessageID": Uuid::new_v4().to_string(),
+                    }),
+                })
+                .collect();
+
+            ResponseTemplate::new(200).set_body_json(outcomes)
+        })
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    let newsletter_issue_id = publish_newsletter(&test_app).await;
+    test_app.dispatch_all_pending_emails().await;
+
+    let report: serde_json::Value = test_app
+        .get_delivery_report(newsletter_issue_id)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(report["sent"], 1);
+    assert_eq!(report["failed"], 1);
+
+    test_app.login_test_user().await;
+    let html_page = test_app
+        .get_newsletter_issue_report_html(newsletter_issue_id)
+        .await;
+
+    assert!(html_page.contains("inactive@example.com"));
+    assert!(html_page.contains("Postmark error 406"));
+}
+
+#[tokio::test]
+async fn given_an_unknown_issue_then_the_delivery_report_should_return_404() {
+    let test_app = spawn_server().await;
+
+    let response = test_app.get_delivery_report(Uuid::new_v4()).await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn given_a_viewer_then_it_should_read_delivery_reports() {
+    let test_app = spawn_server().await;
+    create_and_confirm_subscription(&test_app).await;
+    let viewer = TestUser::generate_with_role("viewer");
+    viewer.store(&test_app.db_connection_pool).await;
+
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(422))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    let newsletter_issue_id = publish_newsletter(&test_app).await;
+    test_app.dispatch_all_pending_emails().await;
+
+    let response = test_app
+        .get_delivery_report_as(newsletter_issue_id, &viewer)
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    test_app.login_as(&viewer).await;
+    let html_page = test_app
+        .get_newsletter_issue_report_html(newsletter_issue_id)
+        .await;
+    assert!(html_page.contains(&get_subscriber_email(&test_app).await));
+}
+
+#[tokio::test]
+async fn given_a_failed_report_request_then_it_should_not_be_audited_as_a_publication() {
+    let test_app = spawn_server().await;
+
+    let response = reqwest::Client::new()
+        .get(format!(
+            "{}/newsletters/{}/deliveries",
+            &test_app.address,
+            Uuid::new_v4()
+        ))
+        .basic_auth("mallory", Some("guess"))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 401);
+
+    let events =
+        sqlx::query!("SELECT action FROM audit_events WHERE action = 'publish_newsletter'")
+            .fetch_all(&test_app.db_connection_pool)
+            .await
+            .expect("Failed to fetch the audit events");
+    assert!(events.is_empty());
+}
+
+#[tokio::test]
+async fn given_a_task_claimed_by_another_worker_then_it_should_wait_for_the_claim_to_expire() {
+    let test_app = spawn_server().await;
+    create_and_confirm_subscription(&test_app).await;
+
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    // A scheduled issue has nothing queued yet, so the task below is the only one.
+    let mut request_body = newsletter_request_body();
+    request_body["scheduled_at"] = serde_json::json!(Utc::now() + chrono::Duration::days(1));
+    let response = test_app.post_newsletters(request_body).await;
+    let body: serde_json::Value = response.json().await.unwrap();
+    let newsletter_issue_id: Uuid = body["newsletter_issue_id"]
+        .as_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_delivery_queue (newsletter_issue_id, subscriber_email, claimed_until)
+        VALUES ($1, $2, now() + interval '1 hour')
+        "#,
+        newsletter_issue_id,
+        get_subscriber_email(&test_app).await
+    )
+    .execute(&test_app.db_connection_pool)
+    .await
+    .unwrap();
+
+    let outcome = try_execute_task(
+        &test_app.db_connection_pool,
+        &test_app.email_client,
+        &test_app.base_url,
+    )
+    .await
+    .unwrap();
+    assert!(matches!(outcome, ExecutionOutcome::EmptyQueue));
+
+    sqlx::query!("UPDATE issue_delivery_queue SET claimed_until = now() - interval '1 minute'")
+        .execute(&test_app.db_connection_pool)
+        .await
+        .unwrap();
+
+    test_app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn given_two_issues_queued_for_one_subscriber_then_both_should_be_sent() {
+    let test_app = spawn_server().await;
+    create_and_confirm_subscription(&test_app).await;
+    let subscriber_email = get_subscriber_email(&test_app).await;
+
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(|request: &Request| {
+            let messages: Vec<serde_json::Value> = serde_json::from_slice(&request.body).unwrap();
+            let outcomes: Vec<_> = messages
+                .iter()
+                .map(|_| {
+                    serde_json::json!({
+                        "ErrorCode": 0,
+                        "Message": "OK",
+                        "MessageID": Uuid::new_v4().to_string(),
+                    })
+                })
+                .collect();
+
+            ResponseTemplate::new(200).set_body_json(outcomes)
+        })
+        .mount(&test_app.email_server)
+        .await;
+
+    // Scheduled issues queue nothing, so both tasks below are queued together and end up in the
+    // same batch.
+    let mut newsletter_issue_ids = Vec::new();
+
+    for _ in 0..2 {
+        let mut request_body = newsletter_request_body();
+        request_body["scheduled_at"] = serde_json::json!(Utc::now() + chrono::Duration::days(1));
+        let response = test_app.post_newsletters(request_body).await;
+        let body: serde_json::Value = response.json().await.unwrap();
+        let newsletter_issue_id: Uuid = body["newsletter_issue_id"]
+            .as_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        newsletter_issue_ids.push(newsletter_issue_id);
+    }
+
+    sqlx::query!(
+        r#"
+        INSERT INTO deliveries (newsletter_issue_id, subscriber_email, status, updated_at)
+        SELECT newsletter_issue_id, $2, 'pending', now()
+        FROM UNNEST($1::uuid[]) AS newsletter_issue_id
+        "#,
+        &newsletter_issue_ids,
+        subscriber_email
+    )
+    .execute(&test_app.db_connection_pool)
+    .await
+    .unwrap();
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_delivery_queue (newsletter_issue_id, subscriber_email)
+        SELECT newsletter_issue_id, $2
+        FROM UNNEST($1::uuid[]) AS newsletter_issue_id
+        "#,
+        &newsletter_issue_ids,
+        subscriber_email
+    )
+    .execute(&test_app.db_connection_pool)
+    .await
+    .unwrap();
+
+    test_app.dispatch_all_pending_emails().await;
+
+    let statuses = sqlx::query!(
+        "SELECT status FROM deliveries WHERE newsletter_issue_id = ANY($1)",
+        &newsletter_issue_ids
+    )
+    .fetch_all(&test_app.db_connection_pool)
+    .await
+    .expect("Failed to fetch the deliveries");
+    assert_eq!(statuses.len(), 2);
+    assert!(statuses.iter().all(|delivery| delivery.status == "sent"));
+}
@@ -10,7 +10,7 @@ async fn health_check_success() {
         .get(service_url.clone())
         .send()
         .await
-        .expect(&format!("Unable to perform the request to {}", service_url));
+        .unwrap_or_else(|_| panic!("Unable to perform the request to {}", service_url));
 
     assert!(response.status().is_success());
     assert_eq!(Some(0), response.content_length());
@@ -1,33 +1,49 @@
 use crate::utils::get_first_link;
-use argon2::password_hash::rand_core::OsRng;
 use argon2::password_hash::SaltString;
+use argon2::password_hash::rand_core::OsRng;
 use argon2::{Algorithm, Argon2, Params, PasswordHasher, Version};
+use fake::Fake;
+use fake::faker::internet::en::SafeEmail;
 use once_cell::sync::Lazy;
 use reqwest::Url;
 use sqlx::{Connection, Executor, PgConnection, PgPool};
 use std::net::SocketAddr;
+use std::time::Duration;
 use uuid::Uuid;
 use wiremock::MockServer;
 use zero2prod::application::Application;
-use zero2prod::configuration::get_configuration;
+use zero2prod::configuration::{EmailProvider, get_configuration};
+use zero2prod::email_client::EmailClient;
+use zero2prod::issue_delivery_worker::{ExecutionOutcome, try_execute_task};
 use zero2prod::{configuration, telemetry};
 
+const NO_FIELDS: &[(&str, &str)] = &[];
+pub const TEST_USER_AGENT: &str = "zero2prod-api-tests";
+
 pub struct TestUser {
     pub user_id: Uuid,
     pub username: String,
     pub password: String,
+    pub email: String,
+    pub role: String,
 }
 
 impl TestUser {
     pub fn generate() -> Self {
+        Self::generate_with_role("owner")
+    }
+
+    pub fn generate_with_role(role: &str) -> Self {
         Self {
             user_id: Uuid::new_v4(),
             username: Uuid::new_v4().to_string(),
             password: Uuid::new_v4().to_string(),
+            email: SafeEmail().fake(),
+            role: role.to_string(),
         }
     }
 
-    async fn store(&self, db_pool: &PgPool) {
+    pub async fn store(&self, db_pool: &PgPool) {
         let salt: SaltString = SaltString::generate(&mut OsRng);
 
         let password_hash = Argon2::new(
@@ -40,10 +56,15 @@ impl TestUser {
         .to_string();
 
         sqlx::query!(
-            "INSERT INTO users (user_id, username, password_hash) VALUES ($1, $2, $3)",
+            r#"
+            INSERT INTO users (user_id, username, password_hash, email, role)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
             self.user_id,
             self.username,
             password_hash,
+            self.email,
+            self.role,
         )
         .execute(db_pool)
         .await
@@ -63,6 +84,8 @@ pub struct TestApp {
     pub email_server: MockServer,
     pub test_user: TestUser,
     pub http_client: reqwest::Client,
+    pub email_client: EmailClient,
+    pub base_url: String,
 }
 
 impl TestApp {
@@ -88,6 +111,165 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    pub async fn post_newsletters_with_idempotency_key(
+        &self,
+        body: serde_json::Value,
+        idempotency_key: &str,
+    ) -> reqwest::Response {
+        let test_user = &self.test_user;
+
+        self.http_client
+            .post(format!("{}/newsletters", &self.address))
+            .basic_auth(&test_user.username, Some(&test_user.password))
+            .header("Idempotency-Key", idempotency_key)
+            .json(&body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn put_newsletter_schedule(
+        &self,
+        newsletter_issue_id: Uuid,
+        body: serde_json::Value,
+    ) -> reqwest::Response {
+        let test_user = &self.test_user;
+
+        self.http_client
+            .put(format!(
+                "{}/newsletters/{}/schedule",
+                &self.address, newsletter_issue_id
+            ))
+            .basic_auth(&test_user.username, Some(&test_user.password))
+            .json(&body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn delete_newsletter_schedule(&self, newsletter_issue_id: Uuid) -> reqwest::Response {
+        let test_user = &self.test_user;
+
+        self.http_client
+            .delete(format!(
+                "{}/newsletters/{}/schedule",
+                &self.address, newsletter_issue_id
+            ))
+            .basic_auth(&test_user.username, Some(&test_user.password))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_delivery_report(&self, newsletter_issue_id: Uuid) -> reqwest::Response {
+        self.get_delivery_report_as(newsletter_issue_id, &self.test_user)
+            .await
+    }
+
+    pub async fn get_delivery_report_as(
+        &self,
+        newsletter_issue_id: Uuid,
+        user: &TestUser,
+    ) -> reqwest::Response {
+        self.http_client
+            .get(format!(
+                "{}/newsletters/{}/deliveries",
+                &self.address, newsletter_issue_id
+            ))
+            .basic_auth(&user.username, Some(&user.password))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_delivery_report_with_bearer_token(
+        &self,
+        newsletter_issue_id: Uuid,
+        token: &str,
+    ) -> reqwest::Response {
+        self.http_client
+            .get(format!(
+                "{}/newsletters/{}/deliveries",
+                &self.address, newsletter_issue_id
+            ))
+            .bearer_auth(token)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_resend_confirmation(&self, subscription_token: &str) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/subscriptions/confirm/resend", &self.address))
+            .form(&[("subscription_token", subscription_token)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_unsubscribe(&self, unsubscribe_token: &str) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/subscriptions/unsubscribe", &self.address))
+            .query(&[("unsubscribe_token", unsubscribe_token)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_unsubscribe(&self, unsubscribe_token: &str) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/subscriptions/unsubscribe", &self.address))
+            .query(&[("unsubscribe_token", unsubscribe_token)])
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body("List-Unsubscribe=One-Click")
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn dispatch_all_pending_emails(&self) {
+        loop {
+            if let ExecutionOutcome::EmptyQueue =
+                try_execute_task(&self.db_connection_pool, &self.email_client, &self.base_url)
+                    .await
+                    .unwrap()
+            {
+                break;
+            }
+        }
+
+        // The background worker might still be delivering a task it dequeued before us.
+        loop {
+            let pending_tasks = sqlx::query!("SELECT COUNT(*) AS count FROM issue_delivery_queue")
+                .fetch_one(&self.db_connection_pool)
+                .await
+                .expect("Failed to count pending delivery tasks")
+                .count
+                .unwrap_or_default();
+
+            if pending_tasks == 0 {
+                break;
+            }
+
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+
+    // Some emails are sent in the background, once the response has already been returned.
+    pub async fn wait_for_email_requests(&self, n_requests: usize) -> Vec<wiremock::Request> {
+        for _ in 0..100 {
+            let email_requests = self.email_server.received_requests().await.unwrap();
+
+            if email_requests.len() >= n_requests {
+                return email_requests;
+            }
+
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+
+        panic!("The email server did not receive {} requests", n_requests);
+    }
+
     pub fn get_confirmation_links(&self, email_request: &wiremock::Request) -> ConfirmationLinks {
         let email_request_body: serde_json::Value =
             serde_json::from_slice(&email_request.body).unwrap();
@@ -120,12 +302,7 @@ impl TestApp {
     where
         Body: serde::Serialize,
     {
-        self.http_client
-            .post(format!("{}/login", &self.address))
-            .form(body)
-            .send()
-            .await
-            .expect("Failed to execute request.")
+        self.post_form("/login", body).await
     }
 
     pub async fn get_login_html(&self) -> String {
@@ -155,6 +332,10 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    pub async fn post_logout(&self) -> reqwest::Response {
+        self.post_form("/admin/logout", NO_FIELDS).await
+    }
+
     pub async fn get_change_password(&self) -> reqwest::Response {
         self.http_client
             .get(format!("{}/admin/password", &self.address))
@@ -163,18 +344,421 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    pub async fn get_change_password_html(&self) -> String {
+        self.get_change_password()
+            .await
+            .text()
+            .await
+            .expect("Failed to parse html response body.")
+    }
+
+    pub async fn login_test_user(&self) {
+        let request_body = serde_json::json!({
+            "username": &self.test_user.username,
+            "password": &self.test_user.password,
+        });
+
+        let response = self.post_login(&request_body).await;
+
+        self.assert_is_redirect_to(&response, "/admin/dashboard");
+    }
+
     pub async fn post_change_password<Body>(&self, body: &Body) -> reqwest::Response
     where
         Body: serde::Serialize,
     {
+        self.post_form("/admin/password", body).await
+    }
+
+    pub async fn get_change_email_html(&self) -> String {
         self.http_client
-            .post(format!("{}/admin/password", &self.address))
+            .get(format!("{}/admin/email", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .expect("Failed to parse html response body.")
+    }
+
+    pub async fn post_change_email<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.post_form("/admin/email", body).await
+    }
+
+    pub async fn get_password_reset_html(&self) -> String {
+        self.http_client
+            .get(format!("{}/password-reset", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .expect("Failed to parse html response body.")
+    }
+
+    pub async fn post_password_reset_request(&self, username: &str) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/password-reset", &self.address))
+            .form(&[("username", username)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_password_reset(&self, reset_link: Url) -> reqwest::Response {
+        self.http_client
+            .get(reset_link)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_password_reset<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/password-reset/confirm", &self.address))
             .form(body)
             .send()
             .await
             .expect("Failed to execute request.")
     }
 
+    pub async fn get_two_factor_html(&self) -> String {
+        self.http_client
+            .get(format!("{}/admin/two-factor", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .expect("Failed to parse html response body.")
+    }
+
+    pub async fn post_two_factor_setup(&self) -> reqwest::Response {
+        self.post_form("/admin/two-factor/setup", NO_FIELDS).await
+    }
+
+    pub async fn post_two_factor_confirm(&self, code: &str) -> reqwest::Response {
+        self.post_form("/admin/two-factor/confirm", &[("code", code)])
+            .await
+    }
+
+    pub async fn post_two_factor_disable(&self, code: &str) -> reqwest::Response {
+        self.post_form("/admin/two-factor/disable", &[("code", code)])
+            .await
+    }
+
+    pub async fn get_two_factor_login(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/login/two-factor", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_two_factor_login(&self, code: &str) -> reqwest::Response {
+        self.post_form("/login/two-factor", &[("code", code)]).await
+    }
+
+    pub async fn get_api_tokens_html(&self) -> String {
+        self.http_client
+            .get(format!("{}/admin/api-tokens", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .expect("Failed to parse html response body.")
+    }
+
+    pub async fn post_api_tokens(&self, fields: &[(&str, &str)]) -> reqwest::Response {
+        self.post_form("/admin/api-tokens", fields).await
+    }
+
+    pub async fn post_revoke_api_token(&self, api_token_id: Uuid) -> reqwest::Response {
+        self.post_form(
+            &format!("/admin/api-tokens/{}/revoke", api_token_id),
+            NO_FIELDS,
+        )
+        .await
+    }
+
+    pub async fn post_newsletters_with_bearer_token(
+        &self,
+        body: serde_json::Value,
+        token: &str,
+    ) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/newsletters", &self.address))
+            .bearer_auth(token)
+            .json(&body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn login_as(&self, user: &TestUser) {
+        let request_body = serde_json::json!({
+            "username": &user.username,
+            "password": &user.password,
+        });
+
+        let response = self.post_login(&request_body).await;
+
+        self.assert_is_redirect_to(&response, "/admin/dashboard");
+    }
+
+    pub async fn get_users(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/admin/users", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_users_html(&self) -> String {
+        self.get_users()
+            .await
+            .text()
+            .await
+            .expect("Failed to parse html response body.")
+    }
+
+    pub async fn post_invite_user(&self, fields: &[(&str, &str)]) -> reqwest::Response {
+        self.post_form("/admin/users", fields).await
+    }
+
+    pub async fn post_user_action(
+        &self,
+        user_id: Uuid,
+        action: &str,
+        fields: &[(&str, &str)],
+    ) -> reqwest::Response {
+        self.post_form(&format!("/admin/users/{}/{}", user_id, action), fields)
+            .await
+    }
+
+    pub async fn get_sessions(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/admin/sessions", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_sessions_html(&self) -> String {
+        self.get_sessions()
+            .await
+            .text()
+            .await
+            .expect("Failed to parse html response body.")
+    }
+
+    pub async fn post_revoke_session(&self, session_id: Uuid) -> reqwest::Response {
+        self.post_form(&format!("/admin/sessions/{}/revoke", session_id), NO_FIELDS)
+            .await
+    }
+
+    pub async fn post_revoke_other_sessions(&self) -> reqwest::Response {
+        self.post_form("/admin/sessions/revoke-others", NO_FIELDS)
+            .await
+    }
+
+    pub async fn get_new_newsletter_issue(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/admin/newsletters/new", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_new_newsletter_issue_html(&self) -> String {
+        self.get_new_newsletter_issue()
+            .await
+            .text()
+            .await
+            .expect("Failed to parse html response body.")
+    }
+
+    pub async fn post_newsletter_draft<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.post_form("/admin/newsletters", body).await
+    }
+
+    pub async fn get_newsletter_issue(&self, newsletter_issue_id: Uuid) -> reqwest::Response {
+        self.http_client
+            .get(format!(
+                "{}/admin/newsletters/{}",
+                &self.address, newsletter_issue_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_newsletter_issue_html(&self, newsletter_issue_id: Uuid) -> String {
+        self.get_newsletter_issue(newsletter_issue_id)
+            .await
+            .text()
+            .await
+            .expect("Failed to parse html response body.")
+    }
+
+    pub async fn get_newsletter_issue_preview(
+        &self,
+        newsletter_issue_id: Uuid,
+    ) -> reqwest::Response {
+        self.http_client
+            .get(format!(
+                "{}/admin/newsletters/{}/preview",
+                &self.address, newsletter_issue_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_newsletter_issue_report_html(&self, newsletter_issue_id: Uuid) -> String {
+        self.http_client
+            .get(format!(
+                "{}/admin/newsletters/{}/report",
+                &self.address, newsletter_issue_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .expect("Failed to parse html response body.")
+    }
+
+    pub async fn post_save_newsletter_draft<Body>(
+        &self,
+        newsletter_issue_id: Uuid,
+        body: &Body,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.post_form(&format!("/admin/newsletters/{}", newsletter_issue_id), body)
+            .await
+    }
+
+    pub async fn post_newsletter_test(
+        &self,
+        newsletter_issue_id: Uuid,
+        email: &str,
+    ) -> reqwest::Response {
+        self.post_form(
+            &format!("/admin/newsletters/{}/test", newsletter_issue_id),
+            &[("email", email)],
+        )
+        .await
+    }
+
+    pub async fn post_publish_newsletter_draft(
+        &self,
+        newsletter_issue_id: Uuid,
+    ) -> reqwest::Response {
+        self.post_form(
+            &format!("/admin/newsletters/{}/publish", newsletter_issue_id),
+            NO_FIELDS,
+        )
+        .await
+    }
+
+    pub async fn post_schedule_newsletter_issue(
+        &self,
+        newsletter_issue_id: Uuid,
+        scheduled_for: &str,
+        timezone: &str,
+    ) -> reqwest::Response {
+        self.post_form(
+            &format!("/admin/newsletters/{}/schedule", newsletter_issue_id),
+            &[("scheduled_for", scheduled_for), ("timezone", timezone)],
+        )
+        .await
+    }
+
+    pub async fn post_cancel_newsletter_issue_schedule(
+        &self,
+        newsletter_issue_id: Uuid,
+    ) -> reqwest::Response {
+        self.post_form(
+            &format!("/admin/newsletters/{}/cancel-schedule", newsletter_issue_id),
+            NO_FIELDS,
+        )
+        .await
+    }
+
+    pub async fn get_audit_log(&self, query: &[(&str, &str)]) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/admin/audit-log", &self.address))
+            .query(query)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_audit_log_html(&self, query: &[(&str, &str)]) -> String {
+        self.get_audit_log(query)
+            .await
+            .text()
+            .await
+            .expect("Failed to parse html response body.")
+    }
+
+    pub async fn get_csrf_token(&self) -> String {
+        let html_page = self.get_login_html().await;
+
+        html_page
+            .split(r#"name="csrf_token" value=""#)
+            .nth(1)
+            .and_then(|rest| rest.split('"').next())
+            .expect("The CSRF token is missing from the login form")
+            .to_owned()
+    }
+
+    // Form posts to the login flow and the admin area must carry the session's CSRF token, as the
+    // forms rendered by the application do.
+    pub async fn post_form<Body>(&self, path: &str, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize + ?Sized,
+    {
+        let csrf_token = self.get_csrf_token().await;
+
+        let mut request = self
+            .http_client
+            .post(format!("{}{}", &self.address, path))
+            .form(body)
+            .build()
+            .expect("Failed to build request.");
+
+        let mut form = request
+            .body()
+            .and_then(|body| body.as_bytes())
+            .map(|body| String::from_utf8(body.to_vec()).unwrap())
+            .unwrap_or_default();
+
+        if !form.is_empty() {
+            form.push('&');
+        }
+        form.push_str(&format!("csrf_token={}", csrf_token));
+        *request.body_mut() = Some(form.into());
+
+        self.http_client
+            .execute(request)
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub fn assert_is_redirect_to(&self, response: &reqwest::Response, location: &str) {
         assert_eq!(response.status(), reqwest::StatusCode::SEE_OTHER);
         assert_eq!(response.headers().get("Location").unwrap(), location);
@@ -208,6 +792,7 @@ pub async fn spawn_server() -> TestApp {
         let mut test_configuration = get_configuration().expect("Failed to read configuration");
         test_configuration.database.database_name = Uuid::new_v4().to_string();
         test_configuration.application.port = 0;
+        test_configuration.email_client.provider = EmailProvider::Postmark;
         test_configuration.email_client.base_url = email_server.uri();
         test_configuration
     };
@@ -217,10 +802,10 @@ pub async fn spawn_server() -> TestApp {
     let server = Application::build(configuration.clone())
         .await
         .expect("Failed to build server");
-    let socket_addr = server.socket_addr().clone();
+    let socket_addr = *server.socket_addr();
     let http_address = format!("http://{}", server.address());
 
-    let _ = tokio::spawn(server.run_until_stopped());
+    tokio::spawn(server.run_until_stopped());
 
     let db_connection_pool = Application::get_connection_pool(&configuration.database);
 
@@ -230,6 +815,7 @@ pub async fn spawn_server() -> TestApp {
     let http_client = reqwest::Client::builder()
         .redirect(reqwest::redirect::Policy::none())
         .cookie_store(true)
+        .user_agent(TEST_USER_AGENT)
         .build()
         .unwrap();
 
@@ -240,6 +826,11 @@ pub async fn spawn_server() -> TestApp {
         email_server,
         test_user,
         http_client,
+        email_client: configuration
+            .email_client
+            .client()
+            .expect("Failed to build email client"),
+        base_url: configuration.application.base_url,
     }
 }
 
@@ -264,3 +855,13 @@ pub async fn configure_database(database_settings: &configuration::DatabaseSetti
 
     db_connection_pool
 }
+
+pub fn newsletter_request_body() -> serde_json::Value {
+    serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "text": "Newsletter plain content",
+            "html": "<p>Newsletter HTML content</p>",
+        }
+    })
+}
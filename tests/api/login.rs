@@ -38,3 +38,62 @@ async fn given_success_login_then_it_should_redirect_to_admin_dashboard() {
     let html_page = test_app.get_admin_dashboard_html().await;
     assert!(html_page.contains(&format!("Welcome {}", test_app.test_user.username)));
 }
+
+#[tokio::test]
+async fn given_too_many_failed_attempts_then_it_should_lock_the_account_out_with_429() {
+    let test_app = spawn_server().await;
+
+    let wrong_credentials = serde_json::json!({
+        "username": &test_app.test_user.username,
+        "password": "wrong_password",
+    });
+
+    for _ in 0..5 {
+        let response = test_app.post_login(&wrong_credentials).await;
+
+        test_app.assert_is_redirect_to(&response, "/login");
+    }
+
+    let response = test_app
+        .post_login(&serde_json::json!({
+            "username": &test_app.test_user.username,
+            "password": &test_app.test_user.password,
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 429);
+
+    let retry_after: u64 = response.headers()["Retry-After"]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+
+    assert!(retry_after > 0);
+}
+
+#[tokio::test]
+async fn given_a_hash_with_outdated_params_then_login_should_upgrade_it() {
+    let test_app = spawn_server().await;
+
+    let get_password_hash = || async {
+        sqlx::query!(
+            "SELECT password_hash FROM users WHERE user_id = $1",
+            test_app.test_user.user_id
+        )
+        .fetch_one(&test_app.db_connection_pool)
+        .await
+        .expect("Failed to fetch the password hash")
+        .password_hash
+    };
+
+    assert!(get_password_hash().await.contains("m=15000,t=2,p=1"));
+
+    test_app.login_test_user().await;
+
+    let password_hash = get_password_hash().await;
+    assert!(password_hash.starts_with("$argon2id$v=19$m=19456,t=2,p=1$"));
+
+    test_app.post_logout().await;
+    test_app.login_test_user().await;
+}
@@ -1,9 +1,22 @@
 pub mod admin_dashboard_tests;
+mod admin_newsletters;
+mod api_tokens;
+mod audit_log;
+mod change_email;
 pub mod change_password;
 mod confirm_subscription;
+mod csrf;
+mod deliveries;
 mod health_check;
 mod helpers;
 pub mod login;
 mod newsletter_tests;
+mod password_reset;
+mod scheduled_newsletters;
+mod security_headers;
+mod sessions;
 mod subscriptions;
+mod two_factor;
+mod unsubscribe;
+mod users;
 mod utils;
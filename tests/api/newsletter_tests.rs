@@ -24,7 +24,8 @@ async fn then_it_should_not_send_newsletter_to_unconfirmed_subscribers() {
 
     let response = test_app.post_newsletters(newsletter_request_body).await;
 
-    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert_eq!(response.status(), reqwest::StatusCode::ACCEPTED);
+    test_app.dispatch_all_pending_emails().await;
 }
 
 #[tokio::test]
@@ -33,7 +34,7 @@ async fn then_it_should_send_newsletter_to_confirmed_subscribers() {
 
     create_and_confirm_subscription(&test_app).await;
 
-    Mock::given(path("/email"))
+    Mock::given(path("/email/batch"))
         .and(method("POST"))
         .respond_with(ResponseTemplate::new(200))
         .expect(1)
@@ -50,7 +51,156 @@ async fn then_it_should_send_newsletter_to_confirmed_subscribers() {
 
     let response = test_app.post_newsletters(newsletter_request_body).await;
 
-    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert_eq!(response.status(), reqwest::StatusCode::ACCEPTED);
+    test_app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn then_it_should_store_the_issue_and_enqueue_one_task_per_confirmed_subscriber() {
+    let test_app = spawn_server().await;
+
+    create_and_confirm_subscription(&test_app).await;
+
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "text": "Newsletter plain content",
+            "html": "<p>Newsletter HTML content</p>",
+        }
+    });
+
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    let response = test_app.post_newsletters(newsletter_request_body).await;
+
+    assert_eq!(response.status(), reqwest::StatusCode::ACCEPTED);
+
+    let issue = sqlx::query!("SELECT newsletter_issue_id, title FROM newsletter_issues")
+        .fetch_one(&test_app.db_connection_pool)
+        .await
+        .expect("Failed to fetch the stored newsletter issue");
+
+    assert_eq!(issue.title, "Newsletter title");
+
+    // Tasks are consumed by the background worker, but every enqueued task leaves a delivery
+    // record behind, so we can inspect those without racing the worker.
+    let deliveries = sqlx::query!(
+        "SELECT newsletter_issue_id, subscriber_email FROM deliveries ORDER BY subscriber_email"
+    )
+    .fetch_all(&test_app.db_connection_pool)
+    .await
+    .expect("Failed to fetch the deliveries");
+
+    assert_eq!(deliveries.len(), 1);
+    assert_eq!(deliveries[0].newsletter_issue_id, issue.newsletter_issue_id);
+    assert_eq!(deliveries[0].subscriber_email, "ursula_le_guin@gmail.com");
+
+    test_app.dispatch_all_pending_emails().await;
+
+    let pending_tasks = sqlx::query!("SELECT COUNT(*) AS \"count!\" FROM issue_delivery_queue")
+        .fetch_one(&test_app.db_connection_pool)
+        .await
+        .expect("Failed to count pending delivery tasks")
+        .count;
+
+    assert_eq!(pending_tasks, 0);
+}
+
+#[tokio::test]
+async fn given_a_repeated_idempotency_key_then_it_should_send_the_newsletter_only_once() {
+    let test_app = spawn_server().await;
+
+    create_and_confirm_subscription(&test_app).await;
+
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "text": "Newsletter plain content",
+            "html": "<p>Newsletter HTML content</p>",
+        }
+    });
+    let idempotency_key = Uuid::new_v4().to_string();
+
+    let response = test_app
+        .post_newsletters_with_idempotency_key(newsletter_request_body.clone(), &idempotency_key)
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::ACCEPTED);
+
+    let response = test_app
+        .post_newsletters_with_idempotency_key(newsletter_request_body, &idempotency_key)
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::ACCEPTED);
+
+    test_app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn given_concurrent_requests_with_the_same_idempotency_key_then_it_should_send_the_newsletter_only_once()
+ {
+    let test_app = spawn_server().await;
+
+    create_and_confirm_subscription(&test_app).await;
+
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "text": "Newsletter plain content",
+            "html": "<p>Newsletter HTML content</p>",
+        }
+    });
+    let idempotency_key = Uuid::new_v4().to_string();
+
+    let first_response = test_app
+        .post_newsletters_with_idempotency_key(newsletter_request_body.clone(), &idempotency_key);
+    let second_response =
+        test_app.post_newsletters_with_idempotency_key(newsletter_request_body, &idempotency_key);
+    let (first_response, second_response) = tokio::join!(first_response, second_response);
+
+    assert_eq!(first_response.status(), second_response.status());
+    assert_eq!(
+        first_response.bytes().await.unwrap(),
+        second_response.bytes().await.unwrap()
+    );
+
+    test_app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn given_an_invalid_idempotency_key_then_it_should_returns_400() {
+    let test_app = spawn_server().await;
+
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "text": "Newsletter plain content",
+            "html": "<p>Newsletter HTML content</p>",
+        }
+    });
+
+    let response = test_app
+        .post_newsletters_with_idempotency_key(newsletter_request_body, &"a".repeat(50))
+        .await;
+
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
 }
 
 #[tokio::test]
@@ -155,7 +305,7 @@ async fn given_a_wrong_password_then_it_should_returns_401() {
     )
 }
 
-async fn create_subscription_request(test_app: &TestApp) -> ConfirmationLinks {
+pub async fn create_subscription_request(test_app: &TestApp) -> ConfirmationLinks {
     let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
 
     let _mock_guard = Mock::given(path("/email"))
@@ -183,7 +333,7 @@ async fn create_subscription_request(test_app: &TestApp) -> ConfirmationLinks {
     test_app.get_confirmation_links(&confirm_email_request)
 }
 
-async fn create_and_confirm_subscription(test_app: &TestApp) {
+pub async fn create_and_confirm_subscription(test_app: &TestApp) {
     let confirmation_links = create_subscription_request(test_app).await;
 
     reqwest::get(confirmation_links.html)
@@ -192,3 +342,33 @@ async fn create_and_confirm_subscription(test_app: &TestApp) {
         .error_for_status()
         .unwrap();
 }
+
+#[tokio::test]
+async fn given_too_many_wrong_passwords_then_it_should_return_429_with_retry_after() {
+    let test_app = spawn_server().await;
+
+    let send_request = |password: String| {
+        reqwest::Client::new()
+            .post(format!("{}/newsletters", &test_app.address))
+            .basic_auth(&test_app.test_user.username, Some(password))
+            .json(&serde_json::json!(
+                { "title": "Title", "content": {"text": "Content", "html": "<p>Content</p>"} }
+            ))
+            .send()
+    };
+
+    for _ in 0..5 {
+        let response = send_request(Uuid::new_v4().to_string())
+            .await
+            .expect("Failed to execute request.");
+
+        assert_eq!(reqwest::StatusCode::UNAUTHORIZED, response.status());
+    }
+
+    let response = send_request(test_app.test_user.password.clone())
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(reqwest::StatusCode::TOO_MANY_REQUESTS, response.status());
+    assert!(response.headers().contains_key("Retry-After"));
+}
@@ -0,0 +1,263 @@
+use crate::helpers::{TestApp, spawn_server};
+use reqwest::Url;
+use uuid::Uuid;
+use wiremock::matchers::{any, method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+#[tokio::test]
+async fn then_the_login_form_should_link_to_the_password_reset_form() {
+    let test_app = spawn_server().await;
+
+    let html_page = test_app.get_login_html().await;
+
+    assert!(html_page.contains(r#"<a href="/password-reset">Forgot your password?</a>"#));
+}
+
+#[tokio::test]
+async fn given_a_known_username_then_it_should_email_a_reset_link() {
+    let test_app = spawn_server().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    let response = test_app
+        .post_password_reset_request(&test_app.test_user.username)
+        .await;
+
+    test_app.assert_is_redirect_to(&response, "/login");
+
+    let html_page = test_app.get_login_html().await;
+    assert!(html_page.contains(
+        "<p><i>If the account exists, a password reset link has been sent to its email address.</i></p>"
+    ));
+
+    let email_request = &test_app.wait_for_email_requests(1).await[0];
+    let email_request_body: serde_json::Value =
+        serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(email_request_body["To"], test_app.test_user.email);
+}
+
+#[tokio::test]
+async fn given_an_unknown_username_then_it_should_not_send_an_email_nor_reveal_it() {
+    let test_app = spawn_server().await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&test_app.email_server)
+        .await;
+
+    let response = test_app
+        .post_password_reset_request(&Uuid::new_v4().to_string())
+        .await;
+
+    test_app.assert_is_redirect_to(&response, "/login");
+
+    let html_page = test_app.get_login_html().await;
+    assert!(html_page.contains(
+        "<p><i>If the account exists, a password reset link has been sent to its email address.</i></p>"
+    ));
+}
+
+#[tokio::test]
+async fn given_too_many_requests_for_an_account_then_further_ones_should_not_send_an_email() {
+    let test_app = spawn_server().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(3)
+        .mount(&test_app.email_server)
+        .await;
+
+    for _ in 0..3 {
+        let response = test_app
+            .post_password_reset_request(&test_app.test_user.username)
+            .await;
+        test_app.assert_is_redirect_to(&response, "/login");
+    }
+
+    let response = test_app
+        .post_password_reset_request(&test_app.test_user.username)
+        .await;
+    test_app.assert_is_redirect_to(&response, "/password-reset");
+
+    let html_page = test_app.get_password_reset_html().await;
+    assert!(html_page.contains("Too many password reset requests."));
+
+    test_app.wait_for_email_requests(3).await;
+}
+
+#[tokio::test]
+async fn then_it_should_only_store_a_hash_of_the_token() {
+    let test_app = spawn_server().await;
+    let reset_link = request_reset_link(&test_app).await;
+
+    let stored_token = sqlx::query!("SELECT token_hash FROM password_reset_tokens")
+        .fetch_one(&test_app.db_connection_pool)
+        .await
+        .expect("Failed to fetch the password reset token")
+        .token_hash;
+
+    assert_ne!(stored_token, reset_token(&reset_link));
+}
+
+#[tokio::test]
+async fn given_a_valid_link_then_it_should_reset_the_password() {
+    let test_app = spawn_server().await;
+    let reset_link = request_reset_link(&test_app).await;
+    let new_password = Uuid::new_v4().to_string();
+
+    let response = test_app.get_password_reset(reset_link.clone()).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = test_app
+        .post_password_reset(&serde_json::json!({
+            "token": reset_token(&reset_link),
+            "new_password": &new_password,
+            "new_password_check": &new_password,
+        }))
+        .await;
+
+    test_app.assert_is_redirect_to(&response, "/login");
+
+    let html_page = test_app.get_login_html().await;
+    assert!(
+        html_page
+            .contains("<p><i>Your password has been reset. You can now log in with it.</i></p>")
+    );
+
+    let response = test_app
+        .post_login(&serde_json::json!({
+            "username": &test_app.test_user.username,
+            "password": &new_password,
+        }))
+        .await;
+
+    test_app.assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn given_passwords_that_do_not_match_then_it_should_redirect_back_with_the_token_encoded() {
+    let test_app = spawn_server().await;
+
+    let response = test_app
+        .post_password_reset(&serde_json::json!({
+            "token": "a&b=c",
+            "new_password": Uuid::new_v4().to_string(),
+            "new_password_check": Uuid::new_v4().to_string(),
+        }))
+        .await;
+
+    test_app.assert_is_redirect_to(&response, "/password-reset/confirm?token=a%26b%3Dc");
+}
+
+#[tokio::test]
+async fn given_a_link_that_was_already_used_then_it_should_be_rejected() {
+    let test_app = spawn_server().await;
+    let reset_link = request_reset_link(&test_app).await;
+    let new_password = Uuid::new_v4().to_string();
+    let request_body = serde_json::json!({
+        "token": reset_token(&reset_link),
+        "new_password": &new_password,
+        "new_password_check": &new_password,
+    });
+
+    test_app.post_password_reset(&request_body).await;
+    let response = test_app.post_password_reset(&request_body).await;
+
+    test_app.assert_is_redirect_to(&response, "/password-reset");
+
+    let response = test_app.get_password_reset(reset_link).await;
+
+    test_app.assert_is_redirect_to(&response, "/password-reset");
+}
+
+#[tokio::test]
+async fn given_an_expired_link_then_it_should_be_rejected() {
+    let test_app = spawn_server().await;
+    let reset_link = request_reset_link(&test_app).await;
+    let new_password = Uuid::new_v4().to_string();
+
+    sqlx::query!(
+        r#"
+        UPDATE password_reset_tokens
+        SET created_at = created_at - interval '1 day', expires_at = expires_at - interval '1 day'
+        "#
+    )
+    .execute(&test_app.db_connection_pool)
+    .await
+    .expect("Failed to expire the password reset token");
+
+    let response = test_app
+        .post_password_reset(&serde_json::json!({
+            "token": reset_token(&reset_link),
+            "new_password": &new_password,
+            "new_password_check": &new_password,
+        }))
+        .await;
+
+    test_app.assert_is_redirect_to(&response, "/password-reset");
+
+    let response = test_app
+        .post_login(&serde_json::json!({
+            "username": &test_app.test_user.username,
+            "password": &test_app.test_user.password,
+        }))
+        .await;
+
+    test_app.assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn given_a_password_reset_then_it_should_invalidate_existing_sessions() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+
+    let response = test_app.get_admin_dashboard().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let reset_link = request_reset_link(&test_app).await;
+    let new_password = Uuid::new_v4().to_string();
+
+    test_app
+        .post_password_reset(&serde_json::json!({
+            "token": reset_token(&reset_link),
+            "new_password": &new_password,
+            "new_password_check": &new_password,
+        }))
+        .await;
+
+    let response = test_app.get_admin_dashboard().await;
+
+    test_app.assert_is_redirect_to(&response, "/login");
+}
+
+async fn request_reset_link(test_app: &TestApp) -> Url {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&test_app.email_server)
+        .await;
+
+    test_app
+        .post_password_reset_request(&test_app.test_user.username)
+        .await;
+
+    let email_request = test_app.wait_for_email_requests(1).await.pop().unwrap();
+
+    test_app.get_confirmation_links(&email_request).html
+}
+
+fn reset_token(reset_link: &Url) -> String {
+    reset_link
+        .query_pairs()
+        .find(|(name, _)| name == "token")
+        .map(|(_, value)| value.into_owned())
+        .unwrap()
+}
@@ -0,0 +1,322 @@
+use crate::admin_newsletters::{count_queued_deliveries, create_draft, get_status};
+use crate::helpers::{TestApp, newsletter_request_body, spawn_server};
+use crate::newsletter_tests::create_and_confirm_subscription;
+use chrono::{DateTime, Duration, SubsecRound, Utc};
+use uuid::Uuid;
+use wiremock::matchers::{any, method, path};
+use wiremock::{Mock, ResponseTemplate};
+use zero2prod::newsletter_issues::{Publisher, schedule_issue};
+
+fn scheduled_newsletter_request_body(scheduled_at: DateTime<Utc>) -> serde_json::Value {
+    let mut request_body = newsletter_request_body();
+    request_body["scheduled_at"] = serde_json::json!(scheduled_at);
+    request_body
+}
+
+async fn schedule_newsletter(test_app: &TestApp, scheduled_at: DateTime<Utc>) -> Uuid {
+    let response = test_app
+        .post_newsletters(scheduled_newsletter_request_body(scheduled_at))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 202);
+
+    let body: serde_json::Value = response.json().await.unwrap();
+
+    body["newsletter_issue_id"]
+        .as_str()
+        .unwrap()
+        .parse()
+        .unwrap()
+}
+
+async fn get_scheduled_at(test_app: &TestApp, newsletter_issue_id: Uuid) -> Option<DateTime<Utc>> {
+    sqlx::query!(
+        "SELECT scheduled_at FROM newsletter_issues WHERE newsletter_issue_id = $1",
+        newsletter_issue_id
+    )
+    .fetch_one(&test_app.db_connection_pool)
+    .await
+    .expect("Failed to fetch the newsletter issue")
+    .scheduled_at
+}
+
+#[tokio::test]
+async fn given_a_future_scheduled_at_then_it_should_store_the_issue_without_sending_it() {
+    let test_app = spawn_server().await;
+    create_and_confirm_subscription(&test_app).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&test_app.email_server)
+        .await;
+
+    let scheduled_at = (Utc::now() + Duration::days(1)).trunc_subsecs(0);
+    let response = test_app
+        .post_newsletters(scheduled_newsletter_request_body(scheduled_at))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 202);
+
+    let body: serde_json::Value = response.json().await.unwrap();
+    let newsletter_issue_id = body["newsletter_issue_id"]
+        .as_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+
+    assert_eq!(body["status"], "scheduled");
+    assert_eq!(
+        body["scheduled_at"]
+            .as_str()
+            .unwrap()
+            .parse::<DateTime<Utc>>()
+            .unwrap(),
+        scheduled_at
+    );
+    assert_eq!(
+        get_status(&test_app, newsletter_issue_id).await,
+        "scheduled"
+    );
+    assert_eq!(count_queued_deliveries(&test_app).await, 0);
+}
+
+#[tokio::test]
+async fn given_a_past_scheduled_at_then_it_should_return_400() {
+    let test_app = spawn_server().await;
+
+    let response = test_app
+        .post_newsletters(scheduled_newsletter_request_body(
+            Utc::now() - Duration::minutes(1),
+        ))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn given_a_scheduled_issue_then_it_should_be_rescheduled_and_cancelled_through_the_api() {
+    let test_app = spawn_server().await;
+    let newsletter_issue_id = schedule_newsletter(&test_app, Utc::now() + Duration::days(1)).await;
+
+    let rescheduled_at = (Utc::now() + Duration::days(2)).trunc_subsecs(0);
+    let response = test_app
+        .put_newsletter_schedule(
+            newsletter_issue_id,
+            serde_json::json!({ "scheduled_at": rescheduled_at }),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        get_scheduled_at(&test_app, newsletter_issue_id).await,
+        Some(rescheduled_at)
+    );
+
+    let response = test_app
+        .put_newsletter_schedule(
+            newsletter_issue_id,
+            serde_json::json!({ "scheduled_at": Utc::now() - Duration::minutes(1) }),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = test_app
+        .delete_newsletter_schedule(newsletter_issue_id)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 204);
+    assert_eq!(get_status(&test_app, newsletter_issue_id).await, "draft");
+    assert_eq!(get_scheduled_at(&test_app, newsletter_issue_id).await, None);
+
+    let response = test_app
+        .delete_newsletter_schedule(newsletter_issue_id)
+        .await;
+    assert_eq!(response.status().as_u16(), 409);
+}
+
+#[tokio::test]
+async fn given_an_unknown_issue_then_rescheduling_and_cancelling_should_return_404() {
+    let test_app = spawn_server().await;
+    let newsletter_issue_id = Uuid::new_v4();
+
+    let response = test_app
+        .put_newsletter_schedule(
+            newsletter_issue_id,
+            serde_json::json!({ "scheduled_at": Utc::now() + Duration::days(1) }),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    let response = test_app
+        .delete_newsletter_schedule(newsletter_issue_id)
+        .await;
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn given_a_published_issue_then_rescheduling_should_return_409() {
+    let test_app = spawn_server().await;
+    let newsletter_issue_id = schedule_newsletter(&test_app, Utc::now() + Duration::days(1)).await;
+
+    sqlx::query!(
+        "UPDATE newsletter_issues SET status = 'sending', published_at = now() WHERE newsletter_issue_id = $1",
+        newsletter_issue_id
+    )
+    .execute(&test_app.db_connection_pool)
+    .await
+    .unwrap();
+
+    let response = test_app
+        .put_newsletter_schedule(
+            newsletter_issue_id,
+            serde_json::json!({ "scheduled_at": Utc::now() + Duration::days(2) }),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 409);
+}
+
+#[tokio::test]
+async fn given_a_publication_committed_while_scheduling_then_the_issue_should_stay_published() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+    let newsletter_issue_id = create_draft(&test_app).await;
+
+    // Publish the draft in a transaction that holds the row while the issue is being scheduled.
+    let mut transaction = test_app.db_connection_pool.begin().await.unwrap();
+    sqlx::query!(
+        "UPDATE newsletter_issues SET status = 'sending', published_at = now() WHERE newsletter_issue_id = $1",
+        newsletter_issue_id
+    )
+    .execute(&mut *transaction)
+    .await
+    .unwrap();
+
+    let db_connection_pool = test_app.db_connection_pool.clone();
+    let username = test_app.test_user.username.clone();
+    let user_id = test_app.test_user.user_id;
+    let scheduling = tokio::spawn(async move {
+        let scheduler = Publisher {
+            user_id: Some(user_id),
+            username: &username,
+            ip_address: None,
+        };
+
+        schedule_issue(
+            &db_connection_pool,
+            newsletter_issue_id,
+            Utc::now() + Duration::days(1),
+            &scheduler,
+        )
+        .await
+        .unwrap()
+    });
+
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    transaction.commit().await.unwrap();
+
+    assert!(!scheduling.await.unwrap());
+    assert_eq!(get_status(&test_app, newsletter_issue_id).await, "sending");
+}
+
+#[tokio::test]
+async fn given_a_due_issue_then_it_should_be_promoted_and_delivered_once() {
+    let test_app = spawn_server().await;
+    create_and_confirm_subscription(&test_app).await;
+    let newsletter_issue_id = schedule_newsletter(&test_app, Utc::now() + Duration::days(1)).await;
+
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    sqlx::query!(
+        "UPDATE newsletter_issues SET scheduled_at = now() - interval '1 minute' WHERE newsletter_issue_id = $1",
+        newsletter_issue_id
+    )
+    .execute(&test_app.db_connection_pool)
+    .await
+    .unwrap();
+
+    // The scheduler running alongside the server may get there first; either way the issue is
+    // promoted exactly once.
+    zero2prod::newsletter_issues::promote_due_issues(&test_app.db_connection_pool)
+        .await
+        .unwrap();
+
+    assert_eq!(get_status(&test_app, newsletter_issue_id).await, "sending");
+    test_app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn given_a_draft_then_it_should_be_scheduled_from_the_admin_area_in_the_chosen_timezone() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+    let newsletter_issue_id = create_draft(&test_app).await;
+    let issue_path = format!("/admin/newsletters/{}", newsletter_issue_id);
+
+    let html_page = test_app
+        .get_newsletter_issue_html(newsletter_issue_id)
+        .await;
+    assert!(html_page.contains(r#"name="scheduled_for""#));
+    assert!(html_page.contains(r#"name="timezone" value="UTC""#));
+
+    let response = test_app
+        .post_schedule_newsletter_issue(newsletter_issue_id, "2099-07-01T09:30", "Europe/Rome")
+        .await;
+    test_app.assert_is_redirect_to(&response, &issue_path);
+
+    let html_page = test_app
+        .get_newsletter_issue_html(newsletter_issue_id)
+        .await;
+    assert!(html_page.contains("The newsletter issue will be sent on 2099-07-01 07:30 UTC."));
+    assert!(html_page.contains("Scheduled for 2099-07-01 07:30 (UTC)"));
+    assert!(html_page.contains(r#"value="2099-07-01T07:30""#));
+    assert_eq!(
+        get_status(&test_app, newsletter_issue_id).await,
+        "scheduled"
+    );
+    assert_eq!(
+        get_scheduled_at(&test_app, newsletter_issue_id).await,
+        Some("2099-07-01T07:30:00Z".parse().unwrap())
+    );
+
+    let response = test_app
+        .post_cancel_newsletter_issue_schedule(newsletter_issue_id)
+        .await;
+    test_app.assert_is_redirect_to(&response, &issue_path);
+
+    let html_page = test_app
+        .get_newsletter_issue_html(newsletter_issue_id)
+        .await;
+    assert!(html_page.contains("The schedule has been cancelled. The issue is a draft again."));
+    assert_eq!(get_status(&test_app, newsletter_issue_id).await, "draft");
+}
+
+#[tokio::test]
+async fn given_an_invalid_schedule_then_the_admin_area_should_show_an_error() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+    let newsletter_issue_id = create_draft(&test_app).await;
+
+    test_app
+        .post_schedule_newsletter_issue(newsletter_issue_id, "2099-07-01T09:30", "Mars/Olympus")
+        .await;
+    let html_page = test_app
+        .get_newsletter_issue_html(newsletter_issue_id)
+        .await;
+    assert!(html_page.contains("Mars/Olympus is not a known time zone."));
+
+    test_app
+        .post_schedule_newsletter_issue(newsletter_issue_id, "2000-01-01T00:00", "UTC")
+        .await;
+    let html_page = test_app
+        .get_newsletter_issue_html(newsletter_issue_id)
+        .await;
+    assert!(html_page.contains("The scheduled time must be in the future."));
+
+    assert_eq!(get_status(&test_app, newsletter_issue_id).await, "draft");
+}
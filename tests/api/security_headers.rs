@@ -0,0 +1,37 @@
+use crate::helpers::spawn_server;
+
+#[tokio::test]
+async fn then_html_responses_should_carry_the_security_headers() {
+    let test_app = spawn_server().await;
+
+    let response = test_app
+        .http_client
+        .get(format!("{}/login", &test_app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    let headers = response.headers();
+
+    assert_eq!(
+        headers["Content-Security-Policy"],
+        "default-src 'none'; form-action 'self'; frame-ancestors 'none'; base-uri 'none'"
+    );
+    assert_eq!(headers["X-Frame-Options"], "DENY");
+    assert_eq!(headers["Referrer-Policy"], "no-referrer");
+    assert_eq!(headers["X-Content-Type-Options"], "nosniff");
+}
+
+#[tokio::test]
+async fn given_no_hsts_max_age_then_it_should_not_send_hsts() {
+    let test_app = spawn_server().await;
+
+    let response = test_app
+        .http_client
+        .get(format!("{}/health_check", &test_app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert!(!response.headers().contains_key("Strict-Transport-Security"));
+}
@@ -0,0 +1,140 @@
+use crate::helpers::{TEST_USER_AGENT, TestApp, TestUser, spawn_server};
+use chrono::Utc;
+use uuid::Uuid;
+
+// Stands in for a login from another device, which would otherwise need a second cookie jar.
+async fn store_session(test_app: &TestApp, user_id: Uuid) -> Uuid {
+    let session_id = Uuid::new_v4();
+
+    sqlx::query!(
+        r#"
+        INSERT INTO user_sessions
+            (session_id, user_id, created_at, last_seen_at, ip_address, user_agent)
+        VALUES ($1, $2, $3, $3, '203.0.113.7', '<b>Other device</b>')
+        "#,
+        session_id,
+        user_id,
+        Utc::now()
+    )
+    .execute(&test_app.db_connection_pool)
+    .await
+    .expect("Failed to store a session");
+
+    session_id
+}
+
+async fn session_exists(test_app: &TestApp, session_id: Uuid) -> bool {
+    sqlx::query!(
+        "SELECT session_id FROM user_sessions WHERE session_id = $1",
+        session_id
+    )
+    .fetch_optional(&test_app.db_connection_pool)
+    .await
+    .expect("Failed to fetch the session")
+    .is_some()
+}
+
+#[tokio::test]
+async fn given_an_unauthenticated_user_then_it_should_redirect_to_login() {
+    let test_app = spawn_server().await;
+
+    let response = test_app.get_sessions().await;
+
+    test_app.assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn given_a_login_then_it_should_list_the_session_with_its_metadata() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+    store_session(&test_app, test_app.test_user.user_id).await;
+
+    let session = sqlx::query!(
+        "SELECT ip_address, user_agent FROM user_sessions WHERE user_id = $1 AND ip_address = '127.0.0.1'",
+        test_app.test_user.user_id
+    )
+    .fetch_one(&test_app.db_connection_pool)
+    .await
+    .expect("Failed to fetch the session");
+    assert_eq!(session.user_agent.as_deref(), Some(TEST_USER_AGENT));
+
+    let html_page = test_app.get_sessions_html().await;
+    assert!(html_page.contains(TEST_USER_AGENT));
+    assert!(html_page.contains("This session"));
+    assert!(html_page.contains("203.0.113.7"));
+    assert!(html_page.contains("&lt;b&gt;Other device&lt;/b&gt;"));
+    assert!(!html_page.contains("<b>Other device</b>"));
+}
+
+#[tokio::test]
+async fn given_another_session_then_it_should_revoke_it() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+    let other_session_id = store_session(&test_app, test_app.test_user.user_id).await;
+
+    let response = test_app.post_revoke_session(other_session_id).await;
+    test_app.assert_is_redirect_to(&response, "/admin/sessions");
+
+    let html_page = test_app.get_sessions_html().await;
+    assert!(html_page.contains("<p><i>The session has been revoked.</i></p>"));
+    assert!(!session_exists(&test_app, other_session_id).await);
+
+    let response = test_app.get_admin_dashboard().await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn given_a_session_of_another_user_then_it_should_not_revoke_it() {
+    let test_app = spawn_server().await;
+    let editor = TestUser::generate_with_role("editor");
+    editor.store(&test_app.db_connection_pool).await;
+    let editor_session_id = store_session(&test_app, editor.user_id).await;
+    test_app.login_test_user().await;
+
+    test_app.post_revoke_session(editor_session_id).await;
+
+    let html_page = test_app.get_sessions_html().await;
+    assert!(html_page.contains("The session does not exist or has already ended."));
+    assert!(session_exists(&test_app, editor_session_id).await);
+}
+
+#[tokio::test]
+async fn given_a_request_to_revoke_other_sessions_then_it_should_keep_only_the_current_one() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+    let first_session_id = store_session(&test_app, test_app.test_user.user_id).await;
+    let second_session_id = store_session(&test_app, test_app.test_user.user_id).await;
+
+    let response = test_app.post_revoke_other_sessions().await;
+    test_app.assert_is_redirect_to(&response, "/admin/sessions");
+
+    let html_page = test_app.get_sessions_html().await;
+    assert!(html_page.contains("<p><i>2 other session(s) have been revoked.</i></p>"));
+    assert!(!session_exists(&test_app, first_session_id).await);
+    assert!(!session_exists(&test_app, second_session_id).await);
+
+    let response = test_app.get_admin_dashboard().await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn given_a_password_change_then_it_should_revoke_the_other_sessions() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+    let other_session_id = store_session(&test_app, test_app.test_user.user_id).await;
+
+    let new_password = Uuid::new_v4().to_string();
+    let response = test_app
+        .post_change_password(&serde_json::json!({
+            "current_password": &test_app.test_user.password,
+            "new_password": &new_password,
+            "new_password_check": &new_password,
+        }))
+        .await;
+    test_app.assert_is_redirect_to(&response, "/admin/password");
+
+    assert!(!session_exists(&test_app, other_session_id).await);
+
+    let response = test_app.get_admin_dashboard().await;
+    assert_eq!(response.status().as_u16(), 200);
+}
@@ -121,3 +121,78 @@ async fn given_fields_invalid_then_subscribe_returns_400() {
         )
     }
 }
+
+#[tokio::test]
+async fn given_a_pending_subscriber_subscribing_again_then_it_should_resend_a_new_confirmation_link()
+ {
+    let test_app = spawn_server().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&test_app.email_server)
+        .await;
+
+    let first_response = test_app.post_subscriptions(body.to_owned()).await;
+    let second_response = test_app.post_subscriptions(body.to_owned()).await;
+
+    assert_eq!(first_response.status().as_u16(), 200);
+    assert_eq!(second_response.status().as_u16(), 200);
+
+    let email_requests = test_app.email_server.received_requests().await.unwrap();
+    let first_links = test_app.get_confirmation_links(&email_requests[0]);
+    let second_links = test_app.get_confirmation_links(&email_requests[1]);
+
+    assert_ne!(first_links.html, second_links.html);
+
+    let stale_link_response = reqwest::get(first_links.html).await.unwrap();
+    assert_eq!(stale_link_response.status().as_u16(), 401);
+
+    let fresh_link_response = reqwest::get(second_links.html).await.unwrap();
+    assert_eq!(fresh_link_response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn given_a_confirmed_subscriber_subscribing_again_then_it_should_return_200_without_a_new_link()
+ {
+    let test_app = spawn_server().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&test_app.email_server)
+        .await;
+
+    test_app.post_subscriptions(body.to_owned()).await;
+    let email_request = &test_app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = test_app.get_confirmation_links(email_request);
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let response = test_app.post_subscriptions(body.to_owned()).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let notice_request = &test_app.email_server.received_requests().await.unwrap()[1];
+    let notice_body: serde_json::Value = serde_json::from_slice(&notice_request.body).unwrap();
+    assert!(
+        !notice_body["TextBody"]
+            .as_str()
+            .unwrap()
+            .contains("subscription_token")
+    );
+
+    let record_saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&test_app.db_connection_pool)
+        .await
+        .expect("Failed to fetch saved subscription");
+
+    assert_eq!(record_saved.status, "confirmed");
+}
@@ -0,0 +1,247 @@
+use crate::helpers::{TestApp, newsletter_request_body, spawn_server};
+use totp_rs::TOTP;
+
+#[tokio::test]
+async fn given_a_valid_code_then_it_should_enable_two_factor_and_show_recovery_codes() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+
+    let (_, recovery_codes) = enable_two_factor(&test_app).await;
+
+    assert_eq!(recovery_codes.len(), 10);
+
+    let html_page = test_app.get_two_factor_html().await;
+    assert!(html_page.contains("<p>Two-factor authentication is enabled.</p>"));
+}
+
+#[tokio::test]
+async fn then_it_should_store_the_totp_secret_encrypted() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+
+    let (totp, _) = enable_two_factor(&test_app).await;
+
+    let stored_secret = sqlx::query!(
+        "SELECT totp_secret FROM users WHERE user_id = $1",
+        test_app.test_user.user_id
+    )
+    .fetch_one(&test_app.db_connection_pool)
+    .await
+    .expect("Failed to fetch the TOTP secret")
+    .totp_secret
+    .unwrap();
+
+    assert!(!stored_secret.contains(&totp.get_secret_base32()));
+}
+
+#[tokio::test]
+async fn given_an_invalid_code_during_setup_then_it_should_not_enable_two_factor() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+    test_app.post_two_factor_setup().await;
+
+    let response = test_app.post_two_factor_confirm("000000x").await;
+
+    test_app.assert_is_redirect_to(&response, "/admin/two-factor");
+
+    let html_page = test_app.get_two_factor_html().await;
+    assert!(html_page.contains("<p><i>The authentication code is invalid.</i></p>"));
+    assert!(!html_page.contains("<p>Two-factor authentication is enabled.</p>"));
+}
+
+#[tokio::test]
+async fn given_two_factor_enabled_then_login_should_require_a_code() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+    let (totp, _) = enable_two_factor(&test_app).await;
+    test_app.post_logout().await;
+
+    let response = post_test_user_credentials(&test_app).await;
+
+    test_app.assert_is_redirect_to(&response, "/login/two-factor");
+
+    let response = test_app.get_admin_dashboard().await;
+    test_app.assert_is_redirect_to(&response, "/login");
+
+    let response = test_app.post_two_factor_login(&next_code(&totp)).await;
+
+    test_app.assert_is_redirect_to(&response, "/admin/dashboard");
+
+    let response = test_app.get_admin_dashboard().await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn given_an_invalid_code_at_login_then_it_should_show_an_error() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+    enable_two_factor(&test_app).await;
+    test_app.post_logout().await;
+    post_test_user_credentials(&test_app).await;
+
+    let response = test_app.post_two_factor_login("000000x").await;
+
+    test_app.assert_is_redirect_to(&response, "/login/two-factor");
+
+    let html_page = test_app.get_two_factor_login().await.text().await.unwrap();
+    assert!(html_page.contains("<p><i>The authentication code is invalid.</i></p>"));
+
+    let response = test_app.get_admin_dashboard().await;
+    test_app.assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn given_a_code_that_was_already_used_then_it_should_be_rejected() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+    let (totp, _) = enable_two_factor(&test_app).await;
+    let code = next_code(&totp);
+    test_app.post_logout().await;
+
+    post_test_user_credentials(&test_app).await;
+    let response = test_app.post_two_factor_login(&code).await;
+
+    test_app.assert_is_redirect_to(&response, "/admin/dashboard");
+
+    test_app.post_logout().await;
+    post_test_user_credentials(&test_app).await;
+    let response = test_app.post_two_factor_login(&code).await;
+
+    test_app.assert_is_redirect_to(&response, "/login/two-factor");
+}
+
+#[tokio::test]
+async fn given_too_many_invalid_codes_then_it_should_lock_the_second_factor_out_with_429() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+    let (totp, _) = enable_two_factor(&test_app).await;
+    test_app.post_logout().await;
+    post_test_user_credentials(&test_app).await;
+
+    for _ in 0..5 {
+        let response = test_app.post_two_factor_login("000000x").await;
+
+        test_app.assert_is_redirect_to(&response, "/login/two-factor");
+    }
+
+    let response = test_app.post_two_factor_login(&next_code(&totp)).await;
+
+    assert_eq!(response.status().as_u16(), 429);
+    assert!(response.headers().contains_key("Retry-After"));
+
+    let response = test_app.get_admin_dashboard().await;
+    test_app.assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn given_a_recovery_code_then_it_should_only_be_accepted_once() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+    let (_, recovery_codes) = enable_two_factor(&test_app).await;
+    test_app.post_logout().await;
+
+    post_test_user_credentials(&test_app).await;
+    let response = test_app.post_two_factor_login(&recovery_codes[0]).await;
+
+    test_app.assert_is_redirect_to(&response, "/admin/dashboard");
+
+    test_app.post_logout().await;
+    post_test_user_credentials(&test_app).await;
+    let response = test_app.post_two_factor_login(&recovery_codes[0]).await;
+
+    test_app.assert_is_redirect_to(&response, "/login/two-factor");
+}
+
+#[tokio::test]
+async fn given_no_pending_login_then_the_two_factor_step_should_redirect_to_login() {
+    let test_app = spawn_server().await;
+
+    let response = test_app.get_two_factor_login().await;
+
+    test_app.assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn given_a_valid_code_then_it_should_disable_two_factor() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+    let (totp, _) = enable_two_factor(&test_app).await;
+
+    let response = test_app.post_two_factor_disable(&next_code(&totp)).await;
+
+    test_app.assert_is_redirect_to(&response, "/admin/two-factor");
+
+    let html_page = test_app.get_two_factor_html().await;
+    assert!(html_page.contains("<p><i>Two-factor authentication has been disabled.</i></p>"));
+
+    test_app.post_logout().await;
+    let response = post_test_user_credentials(&test_app).await;
+
+    test_app.assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn given_two_factor_enabled_then_basic_auth_should_not_publish_newsletters() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+    enable_two_factor(&test_app).await;
+
+    let response = test_app.post_newsletters(newsletter_request_body()).await;
+
+    assert_eq!(response.status().as_u16(), 403);
+
+    let n_issues = sqlx::query!(r#"SELECT count(*) AS "count!" FROM newsletter_issues"#)
+        .fetch_one(&test_app.db_connection_pool)
+        .await
+        .expect("Failed to count the newsletter issues")
+        .count;
+
+    assert_eq!(n_issues, 0);
+}
+
+async fn enable_two_factor(test_app: &TestApp) -> (TOTP, Vec<String>) {
+    test_app.post_two_factor_setup().await;
+
+    let html_page = test_app.get_two_factor_html().await;
+    let provisioning_uri_start = html_page.find("otpauth://").unwrap();
+    let provisioning_uri_length = html_page[provisioning_uri_start..].find('"').unwrap();
+    let provisioning_uri =
+        &html_page[provisioning_uri_start..provisioning_uri_start + provisioning_uri_length];
+
+    let totp = TOTP::from_url(provisioning_uri).unwrap();
+
+    let response = test_app
+        .post_two_factor_confirm(&totp.generate_current().unwrap())
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let html_page = response.text().await.unwrap();
+    let recovery_codes = html_page
+        .split("<li><code>")
+        .skip(1)
+        .map(|fragment| fragment.split("</code>").next().unwrap().to_string())
+        .collect();
+
+    (totp, recovery_codes)
+}
+
+// The code used to confirm the setup cannot be used again, so later steps use the next one, which
+// is accepted as well to allow for clock drift.
+fn next_code(totp: &TOTP) -> String {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    totp.generate(now + totp.step)
+}
+
+async fn post_test_user_credentials(test_app: &TestApp) -> reqwest::Response {
+    test_app
+        .post_login(&serde_json::json!({
+            "username": &test_app.test_user.username,
+            "password": &test_app.test_user.password,
+        }))
+        .await
+}
@@ -0,0 +1,138 @@
+use crate::helpers::{TestApp, newsletter_request_body, spawn_server};
+use crate::newsletter_tests::create_and_confirm_subscription;
+use wiremock::matchers::{any, method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+#[tokio::test]
+async fn then_it_should_send_newsletters_with_list_unsubscribe_headers() {
+    let test_app = spawn_server().await;
+    create_and_confirm_subscription(&test_app).await;
+    let unsubscribe_token = get_unsubscribe_token(&test_app).await;
+
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    let response = test_app.post_newsletters(newsletter_request_body()).await;
+
+    assert_eq!(response.status(), reqwest::StatusCode::ACCEPTED);
+    test_app.dispatch_all_pending_emails().await;
+
+    let email_request = test_app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let email_request_body: serde_json::Value =
+        serde_json::from_slice(&email_request.body).unwrap();
+
+    assert_eq!(
+        email_request_body[0]["Headers"],
+        serde_json::json!([
+            {
+                "Name": "List-Unsubscribe",
+                "Value": format!(
+                    "<{}/subscriptions/unsubscribe?unsubscribe_token={}>",
+                    test_app.base_url, unsubscribe_token
+                ),
+            },
+            {
+                "Name": "List-Unsubscribe-Post",
+                "Value": "List-Unsubscribe=One-Click",
+            },
+        ])
+    );
+}
+
+#[tokio::test]
+async fn given_a_valid_token_then_it_should_render_the_unsubscribe_form() {
+    let test_app = spawn_server().await;
+    create_and_confirm_subscription(&test_app).await;
+    let unsubscribe_token = get_unsubscribe_token(&test_app).await;
+
+    let response = test_app.get_unsubscribe(&unsubscribe_token).await;
+
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert!(response.text().await.unwrap().contains(&format!(
+        r#"<form action="/subscriptions/unsubscribe?unsubscribe_token={}" method="post">"#,
+        unsubscribe_token
+    )));
+}
+
+#[tokio::test]
+async fn given_a_one_click_unsubscribe_request_then_it_should_mark_the_subscriber_as_unsubscribed()
+{
+    let test_app = spawn_server().await;
+    create_and_confirm_subscription(&test_app).await;
+    let unsubscribe_token = get_unsubscribe_token(&test_app).await;
+
+    let response = test_app.post_unsubscribe(&unsubscribe_token).await;
+
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    let subscription = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&test_app.db_connection_pool)
+        .await
+        .expect("Failed to fetch subscription");
+
+    assert_eq!(subscription.status, "unsubscribed");
+}
+
+#[tokio::test]
+async fn then_it_should_not_send_newsletters_to_unsubscribed_subscribers() {
+    let test_app = spawn_server().await;
+    create_and_confirm_subscription(&test_app).await;
+    let unsubscribe_token = get_unsubscribe_token(&test_app).await;
+
+    test_app
+        .post_unsubscribe(&unsubscribe_token)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&test_app.email_server)
+        .await;
+
+    let response = test_app.post_newsletters(newsletter_request_body()).await;
+
+    assert_eq!(response.status(), reqwest::StatusCode::ACCEPTED);
+    test_app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn given_an_unknown_token_then_it_should_return_401() {
+    let test_app = spawn_server().await;
+
+    let get_response = test_app.get_unsubscribe("unknown-token").await;
+    let post_response = test_app.post_unsubscribe("unknown-token").await;
+
+    assert_eq!(get_response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    assert_eq!(post_response.status(), reqwest::StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn given_a_request_without_token_then_it_should_return_400() {
+    let test_app = spawn_server().await;
+
+    let response = reqwest::get(format!("{}/subscriptions/unsubscribe", test_app.address))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+}
+
+async fn get_unsubscribe_token(test_app: &TestApp) -> String {
+    sqlx::query!("SELECT unsubscribe_token FROM subscriptions")
+        .fetch_one(&test_app.db_connection_pool)
+        .await
+        .expect("Failed to fetch the unsubscribe token")
+        .unsubscribe_token
+}
@@ -0,0 +1,270 @@
+use crate::helpers::{TestApp, TestUser, newsletter_request_body, spawn_server};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+async fn store_user_with_role(test_app: &TestApp, role: &str) -> TestUser {
+    let user = TestUser::generate_with_role(role);
+    user.store(&test_app.db_connection_pool).await;
+    user
+}
+
+#[tokio::test]
+async fn given_an_owner_then_it_should_invite_a_user_who_can_choose_a_password() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    let response = test_app
+        .post_invite_user(&[
+            ("username", "ursula"),
+            ("email", "ursula_le_guin@gmail.com"),
+            ("role", "editor"),
+        ])
+        .await;
+
+    test_app.assert_is_redirect_to(&response, "/admin/users");
+
+    let html_page = test_app.get_users_html().await;
+    assert!(html_page.contains(
+        "<p><i>ursula has been invited. They will receive an email to choose a password.</i></p>"
+    ));
+    assert!(html_page.contains("<td>ursula</td>"));
+    assert!(html_page.contains("<td>editor</td>"));
+
+    let email_request = &test_app.email_server.received_requests().await.unwrap()[0];
+    let invitation_link = test_app.get_confirmation_links(email_request).html;
+    let token = invitation_link
+        .query_pairs()
+        .find(|(name, _)| name == "token")
+        .map(|(_, value)| value.into_owned())
+        .unwrap();
+
+    test_app.post_logout().await;
+
+    let new_password = "a-brand-new-password";
+    test_app
+        .post_password_reset(&serde_json::json!({
+            "token": token,
+            "new_password": new_password,
+            "new_password_check": new_password,
+        }))
+        .await;
+
+    let response = test_app
+        .post_login(&serde_json::json!({
+            "username": "ursula",
+            "password": new_password,
+        }))
+        .await;
+
+    test_app.assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn given_an_invitation_sent_a_day_ago_then_its_link_should_still_be_valid() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    test_app
+        .post_invite_user(&[
+            ("username", "ursula"),
+            ("email", "ursula_le_guin@gmail.com"),
+            ("role", "editor"),
+        ])
+        .await;
+
+    sqlx::query!(
+        r#"
+        UPDATE password_reset_tokens
+        SET created_at = created_at - interval '1 day', expires_at = expires_at - interval '1 day'
+        "#
+    )
+    .execute(&test_app.db_connection_pool)
+    .await
+    .expect("Failed to age the invitation");
+
+    let email_request = &test_app.email_server.received_requests().await.unwrap()[0];
+    let invitation_link = test_app.get_confirmation_links(email_request).html;
+    test_app.post_logout().await;
+
+    let response = test_app.get_password_reset(invitation_link).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn given_an_existing_username_then_it_should_not_invite_the_user_again() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+
+    let response = test_app
+        .post_invite_user(&[
+            ("username", &test_app.test_user.username),
+            ("email", "ursula_le_guin@gmail.com"),
+            ("role", "viewer"),
+        ])
+        .await;
+
+    test_app.assert_is_redirect_to(&response, "/admin/users");
+
+    let html_page = test_app.get_users_html().await;
+    assert!(
+        html_page
+            .contains("<p><i>A user with this username or email address already exists.</i></p>")
+    );
+}
+
+#[tokio::test]
+async fn given_an_editor_then_user_management_should_be_forbidden() {
+    let test_app = spawn_server().await;
+    let editor = store_user_with_role(&test_app, "editor").await;
+    test_app.login_as(&editor).await;
+
+    let response = test_app.get_users().await;
+
+    assert_eq!(response.status().as_u16(), 403);
+
+    let html_page = test_app.get_admin_dashboard_html().await;
+    assert!(!html_page.contains(r#"<a href="/admin/users">"#));
+    assert!(html_page.contains(r#"<a href="/admin/api-tokens">"#));
+}
+
+#[tokio::test]
+async fn given_a_viewer_then_api_tokens_should_be_forbidden() {
+    let test_app = spawn_server().await;
+    let viewer = store_user_with_role(&test_app, "viewer").await;
+    test_app.login_as(&viewer).await;
+
+    let response = test_app
+        .post_api_tokens(&[("name", "CI pipeline"), ("scope", "newsletters:publish")])
+        .await;
+
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn given_a_viewer_then_publishing_a_newsletter_should_return_403() {
+    let test_app = spawn_server().await;
+    let viewer = store_user_with_role(&test_app, "viewer").await;
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/newsletters", &test_app.address))
+        .basic_auth(&viewer.username, Some(&viewer.password))
+        .json(&newsletter_request_body())
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn given_an_editor_then_publishing_a_newsletter_should_be_accepted() {
+    let test_app = spawn_server().await;
+    let editor = store_user_with_role(&test_app, "editor").await;
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/newsletters", &test_app.address))
+        .basic_auth(&editor.username, Some(&editor.password))
+        .json(&newsletter_request_body())
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 202);
+}
+
+#[tokio::test]
+async fn given_a_disabled_user_then_their_sessions_should_end_and_login_should_fail() {
+    let test_app = spawn_server().await;
+    let editor = store_user_with_role(&test_app, "editor").await;
+    test_app.login_as(&editor).await;
+
+    sqlx::query!(
+        "UPDATE users SET disabled_at = now() WHERE user_id = $1",
+        editor.user_id
+    )
+    .execute(&test_app.db_connection_pool)
+    .await
+    .expect("Failed to disable the user");
+
+    let response = test_app.get_admin_dashboard().await;
+    test_app.assert_is_redirect_to(&response, "/login");
+
+    let response = test_app
+        .post_login(&serde_json::json!({
+            "username": &editor.username,
+            "password": &editor.password,
+        }))
+        .await;
+    test_app.assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn given_an_owner_then_it_should_disable_enable_and_delete_other_users() {
+    let test_app = spawn_server().await;
+    let editor = store_user_with_role(&test_app, "editor").await;
+    test_app.login_test_user().await;
+
+    let response = test_app
+        .post_user_action(editor.user_id, "disable", &[])
+        .await;
+    test_app.assert_is_redirect_to(&response, "/admin/users");
+
+    let html_page = test_app.get_users_html().await;
+    assert!(html_page.contains("<td>Disabled</td>"));
+
+    test_app
+        .post_user_action(editor.user_id, "enable", &[])
+        .await;
+    test_app
+        .post_user_action(editor.user_id, "role", &[("role", "viewer")])
+        .await;
+
+    let role = sqlx::query!(
+        "SELECT role, disabled_at FROM users WHERE user_id = $1",
+        editor.user_id
+    )
+    .fetch_one(&test_app.db_connection_pool)
+    .await
+    .expect("Failed to fetch the user");
+    assert_eq!(role.role, "viewer");
+    assert!(role.disabled_at.is_none());
+
+    test_app
+        .post_user_action(editor.user_id, "delete", &[])
+        .await;
+
+    let html_page = test_app.get_users_html().await;
+    assert!(html_page.contains("<p><i>The user has been deleted.</i></p>"));
+    assert!(!html_page.contains(&editor.username));
+}
+
+#[tokio::test]
+async fn given_an_owner_then_it_should_not_delete_their_own_account() {
+    let test_app = spawn_server().await;
+    test_app.login_test_user().await;
+
+    let response = test_app
+        .post_user_action(test_app.test_user.user_id, "delete", &[])
+        .await;
+
+    test_app.assert_is_redirect_to(&response, "/admin/users");
+
+    let html_page = test_app.get_users_html().await;
+    assert!(html_page.contains("<p><i>You cannot change your own account from this page.</i></p>"));
+    assert!(html_page.contains(&test_app.test_user.username));
+}